@@ -0,0 +1,703 @@
+//! Rust port of the annotation writer in `utils/pdfExport.js`
+//!
+//! Comments use the viewer's percentage-based model (x/y and highlight rects in
+//! 0-100 of the page as displayed, origin top-left) and are written as the same
+//! Text, Highlight and Popup annotations that `addAnnotationToPage` produces.
+//! Positions are mapped through the MediaBox origin and /Rotate like the reader
+//! in `annotations` does, so exported comments list back where they were made.
+
+use crate::annotations::PageFrame;
+use crate::atomic_write::{self, FileError};
+use crate::cli::{AnnotateArgs, SaveMode, Side};
+use crate::unlock::{self, Unlocked};
+use chrono::{DateTime, Local};
+use lopdf::{dictionary, text_string, Document, IncrementalDocument, Object, ObjectId};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Letter size, used when a page has no resolvable MediaBox
+const DEFAULT_PAGE_SIZE: (f32, f32) = (612.0, 792.0);
+const HIGHLIGHT_COLOR: [f32; 3] = [1.0, 1.0, 0.0];
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Comment {
+    #[serde(default)]
+    pub id: String,
+    #[serde(default)]
+    pub side: Option<Side>,
+    #[serde(default)]
+    pub x: f64,
+    #[serde(default)]
+    pub y: f64,
+    /// 1-based page number
+    #[serde(default)]
+    pub page: Option<u32>,
+    #[serde(default)]
+    pub text: Option<String>,
+    #[serde(default)]
+    pub author: Option<String>,
+    /// ISO 8601 timestamp as written by `useAnnotations`
+    #[serde(default)]
+    pub timestamp: Option<String>,
+    #[serde(default)]
+    pub highlight_rects: Option<Vec<HighlightRect>>,
+    /// Single-rect form used by older sessions and imported highlights
+    #[serde(default)]
+    pub highlight_rect: Option<HighlightRect>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct HighlightRect {
+    pub left: f64,
+    pub top: f64,
+    pub right: f64,
+    pub bottom: f64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Bookmark {
+    /// 1-based page number
+    pub page: u32,
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+/// Comments are accepted either as the `{ id: comment }` map kept in React state
+/// (and in the `pdf_comments_backup` blob) or as a plain array
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CommentSet {
+    Map(BTreeMap<String, Comment>),
+    List(Vec<Comment>),
+}
+
+/// Bookmarks are accepted as a plain array or as a set exported from the viewer (`{ bookmarks: [...] }`)
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BookmarkSet {
+    List(Vec<Bookmark>),
+    Set { bookmarks: Vec<Bookmark> },
+}
+
+/// Entry point for `twice-pdf annotate`, returns the process exit code
+pub fn run(args: &AnnotateArgs) -> i32 {
+    match annotate_file(args) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("twice-pdf annotate: {}", e);
+            1
+        }
+    }
+}
+
+fn annotate_file(args: &AnnotateArgs) -> Result<(), String> {
+    let mut comments = match &args.comments {
+        Some(path) => load_comments(path)?,
+        None => Vec::new(),
+    };
+    if let Some(side) = args.side {
+        comments.retain(|c| c.side.is_none_or(|s| s == side));
+    }
+    let bookmarks = match &args.bookmarks {
+        Some(path) => load_bookmarks(path)?,
+        None => Vec::new(),
+    };
+
+    let options = ExportOptions {
+        mode: args.save_mode,
+        backup: args.backup,
+        password: args.password.clone().unwrap_or_default(),
+        keep_encryption: args.keep_encryption,
+    };
+    export_annotated_file(&args.input, None, &comments, &bookmarks, &args.output, &options).map_err(|e| e.to_string())
+}
+
+/// How an annotated export is written
+#[derive(Debug, Clone, Default)]
+pub struct ExportOptions {
+    pub mode: SaveMode,
+    /// Keep an overwritten output file as `.bak`
+    pub backup: bool,
+    /// User or owner password of an encrypted source
+    pub password: String,
+    /// Encrypt the output like the source (same passwords and permissions) instead of writing it decrypted
+    pub keep_encryption: bool,
+}
+
+/// Load `source` (or use its already decrypted `unlocked` document), add the annotations
+/// and atomically write the result to `output`
+pub fn export_annotated_file(
+    source: &Path,
+    unlocked: Option<Unlocked>,
+    comments: &[Comment],
+    bookmarks: &[Bookmark],
+    output: &Path,
+    options: &ExportOptions,
+) -> Result<(), FileError> {
+    let original = fs::read(source).map_err(|e| FileError::io("read", source, e))?;
+    let unlocked = match unlocked {
+        Some(unlocked) => unlocked,
+        None => unlock::unlock(&original, &options.password).map_err(|e| e.file_error(source))?,
+    };
+    let bytes = annotate_bytes(original, unlocked, comments, bookmarks, options.mode, options.keep_encryption)
+        .map_err(|e| FileError::other(source, format!("Failed to annotate {}: {}", source.display(), e)))?;
+    atomic_write::write_file(output, &bytes, options.backup)
+}
+
+/// Annotate a decrypted document and return the new file contents.
+/// `original` is the source file as it is on disk, which incremental saves append to.
+pub fn annotate_bytes(
+    original: Vec<u8>,
+    unlocked: Unlocked,
+    comments: &[Comment],
+    bookmarks: &[Bookmark],
+    mode: SaveMode,
+    keep_encryption: bool,
+) -> Result<Vec<u8>, String> {
+    let Unlocked { document: mut doc, encryption } = unlocked;
+    let mut out = Vec::new();
+    match mode {
+        SaveMode::Rewrite => {
+            apply_annotations(&mut doc, comments, bookmarks)?;
+            if let Some(state) = encryption.filter(|_| keep_encryption) {
+                doc.encrypt(&state).map_err(|e| format!("Failed to encrypt: {}", e))?;
+            }
+            doc.save_to(&mut out).map_err(|e| e.to_string())?;
+        }
+        SaveMode::Incremental => {
+            // New objects would have to be encrypted with the document key, which we don't do
+            if encryption.is_some() {
+                return Err("Incremental save is not supported for encrypted PDFs".to_string());
+            }
+
+            let mut updated = doc.clone();
+            apply_annotations(&mut updated, comments, bookmarks)?;
+
+            // Only new or modified objects (annotations, popups, outline items, and the pages,
+            // Annots arrays and catalog that reference them) go into the update section
+            let changed: Vec<(ObjectId, Object)> = updated
+                .objects
+                .into_iter()
+                .filter(|(id, obj)| doc.objects.get(id) != Some(obj))
+                .collect();
+
+            let mut incremental = IncrementalDocument::create_from(original, doc);
+            for (id, obj) in changed {
+                incremental.new_document.set_object(id, obj);
+            }
+            incremental.new_document.max_id = updated.max_id;
+            incremental.save_to(&mut out).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(out)
+}
+
+pub fn load_comments(path: &Path) -> Result<Vec<Comment>, String> {
+    let json = fs::read_to_string(path).map_err(|e| format!("Failed to read file {}: {}", path.display(), e))?;
+    let set: CommentSet =
+        serde_json::from_str(&json).map_err(|e| format!("Invalid comments file {}: {}", path.display(), e))?;
+    Ok(match set {
+        CommentSet::Map(map) => map.into_values().collect(),
+        CommentSet::List(list) => list,
+    })
+}
+
+pub fn load_bookmarks(path: &Path) -> Result<Vec<Bookmark>, String> {
+    let json = fs::read_to_string(path).map_err(|e| format!("Failed to read file {}: {}", path.display(), e))?;
+    let set: BookmarkSet =
+        serde_json::from_str(&json).map_err(|e| format!("Invalid bookmarks file {}: {}", path.display(), e))?;
+    Ok(match set {
+        BookmarkSet::List(list) => list,
+        BookmarkSet::Set { bookmarks } => bookmarks,
+    })
+}
+
+/// Port of `exportPDFWithAnnotations` minus the load/save steps
+pub fn apply_annotations(doc: &mut Document, comments: &[Comment], bookmarks: &[Bookmark]) -> Result<(), String> {
+    add_bookmarks_to_document(doc, bookmarks)?;
+
+    let pages = doc.get_pages();
+    let now = Local::now();
+    for comment in comments {
+        let Some(&page_id) = comment.page.and_then(|n| pages.get(&n)) else {
+            continue;
+        };
+        let frame = PageFrame::new(doc, page_id);
+        add_annotation_to_page(doc, comment, page_id, &frame, now)?;
+    }
+    Ok(())
+}
+
+/// Port of `formatPDFDate`: D:YYYYMMDDHHmmSS+HH'mm'
+pub fn format_pdf_date(date: DateTime<Local>) -> String {
+    let offset = date.offset().local_minus_utc() / 60;
+    let sign = if offset >= 0 { '+' } else { '-' };
+    format!(
+        "D:{}{}{:02}'{:02}'",
+        date.format("%Y%m%d%H%M%S"),
+        sign,
+        offset.abs() / 60,
+        offset.abs() % 60
+    )
+}
+
+/// Port of `addBookmarksToDocument`: replaces the document outline with one flat list
+pub fn add_bookmarks_to_document(doc: &mut Document, bookmarks: &[Bookmark]) -> Result<(), String> {
+    let pages = doc.get_pages();
+    let targets: Vec<(String, ObjectId)> = bookmarks
+        .iter()
+        .filter_map(|b| {
+            let page_id = *pages.get(&b.page)?;
+            let title = b.label.clone().unwrap_or_else(|| format!("Page {}", b.page));
+            Some((title, page_id))
+        })
+        .collect();
+    if targets.is_empty() {
+        return Ok(());
+    }
+
+    let outlines_id = doc.new_object_id();
+    let item_ids: Vec<ObjectId> = targets.iter().map(|_| doc.new_object_id()).collect();
+
+    for (i, (title, page_id)) in targets.iter().enumerate() {
+        let mut item = dictionary! {
+            "Title" => text_string(title),
+            "Dest" => vec![Object::Reference(*page_id), "Fit".into()],
+            "Parent" => outlines_id,
+        };
+        if i > 0 {
+            item.set("Prev", item_ids[i - 1]);
+        }
+        if i + 1 < item_ids.len() {
+            item.set("Next", item_ids[i + 1]);
+        }
+        doc.objects.insert(item_ids[i], Object::Dictionary(item));
+    }
+
+    let outlines = dictionary! {
+        "Type" => "Outlines",
+        "First" => item_ids[0],
+        "Last" => item_ids[item_ids.len() - 1],
+        "Count" => item_ids.len() as i64,
+    };
+    doc.objects.insert(outlines_id, Object::Dictionary(outlines));
+
+    doc.catalog_mut()
+        .map_err(|e| format!("PDF has no catalog: {}", e))?
+        .set("Outlines", outlines_id);
+    Ok(())
+}
+
+/// Port of `addAnnotationToPage`: a Highlight (or Text sticky note) plus a Popup when there is text
+pub fn add_annotation_to_page(
+    doc: &mut Document,
+    comment: &Comment,
+    page_id: ObjectId,
+    frame: &PageFrame,
+    now: DateTime<Local>,
+) -> Result<(), String> {
+    let point = |x: f64, y: f64| frame.to_pdf(x / 100.0, y / 100.0);
+
+    let date = comment
+        .timestamp
+        .as_deref()
+        .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+        .map(|d| d.with_timezone(&Local))
+        .unwrap_or(now);
+    let pdf_date = format_pdf_date(date);
+    let annot_name = format!("annot-{}-{}", comment.id, now.timestamp_millis());
+    let contents = comment.text.clone().unwrap_or_default();
+    let author = comment
+        .author
+        .clone()
+        .filter(|a| !a.is_empty())
+        .unwrap_or_else(|| "Author".to_string());
+
+    let rects: Vec<HighlightRect> = match (&comment.highlight_rects, comment.highlight_rect) {
+        (Some(rects), _) => rects.clone(),
+        (None, Some(rect)) => vec![rect],
+        (None, None) => Vec::new(),
+    };
+
+    let (annot_rect, mut annot) = if !rects.is_empty() {
+        let mut quad_points = Vec::with_capacity(rects.len() * 8);
+        let (mut min_l, mut min_b) = (f64::INFINITY, f64::INFINITY);
+        let (mut max_r, mut max_t) = (f64::NEG_INFINITY, f64::NEG_INFINITY);
+
+        for hr in &rects {
+            // Corners as displayed, so the quads follow the text on rotated pages
+            let corners = [
+                point(hr.left, hr.top),
+                point(hr.right, hr.top),
+                point(hr.left, hr.bottom),
+                point(hr.right, hr.bottom),
+            ];
+            for (x, y) in corners {
+                quad_points.extend([x, y]);
+                min_l = min_l.min(x);
+                min_b = min_b.min(y);
+                max_r = max_r.max(x);
+                max_t = max_t.max(y);
+            }
+        }
+
+        let rect = [min_l, min_b, max_r, max_t];
+        let annot = dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Highlight",
+            "NM" => text_string(&annot_name),
+            "Rect" => reals(&rect),
+            "QuadPoints" => reals(&quad_points),
+            "Contents" => text_string(&contents),
+            "C" => reals(&HIGHLIGHT_COLOR.map(f64::from)),
+            "CA" => 0.4,
+            "F" => 4,
+            "T" => text_string(&author),
+            "M" => text_string(&pdf_date),
+            "CreationDate" => text_string(&pdf_date),
+        };
+        (rect, annot)
+    } else {
+        // A 20pt icon hanging from the point as displayed
+        let (width, height) = frame.display_size();
+        let (ax, ay) = point(comment.x, comment.y);
+        let (bx, by) = point(comment.x + 2000.0 / width, comment.y + 2000.0 / height);
+        let rect = [ax.min(bx), ay.min(by), ax.max(bx), ay.max(by)];
+        let annot = dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Text",
+            "NM" => text_string(&annot_name),
+            "Rect" => reals(&rect),
+            "Contents" => text_string(&contents),
+            "C" => reals(&HIGHLIGHT_COLOR.map(f64::from)),
+            "Name" => "Comment",
+            "Open" => false,
+            "F" => 4,
+            "T" => text_string(&author),
+            "M" => text_string(&pdf_date),
+            "CreationDate" => text_string(&pdf_date),
+        };
+        (rect, annot)
+    };
+
+    let annot_id = doc.new_object_id();
+    let mut new_annots = vec![annot_id];
+
+    if !contents.trim().is_empty() {
+        let popup_rect = [
+            annot_rect[2],
+            annot_rect[1],
+            annot_rect[2] + 200.0,
+            annot_rect[1] + 100.0,
+        ];
+        let popup = dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Popup",
+            "Rect" => reals(&popup_rect),
+            "Parent" => annot_id,
+            "Open" => false,
+            "F" => 0,
+        };
+        let popup_id = doc.add_object(popup);
+        annot.set("Popup", popup_id);
+        new_annots.push(popup_id);
+    }
+
+    doc.objects.insert(annot_id, Object::Dictionary(annot));
+    push_page_annots(doc, page_id, &new_annots)
+}
+
+/// Append to the page's /Annots, which may be inline or an indirect array
+fn push_page_annots(doc: &mut Document, page_id: ObjectId, annots: &[ObjectId]) -> Result<(), String> {
+    let refs = annots.iter().map(|&id| Object::Reference(id));
+    let page = doc
+        .get_dictionary(page_id)
+        .map_err(|e| format!("Invalid page object {:?}: {}", page_id, e))?;
+
+    match page.get(b"Annots") {
+        Ok(Object::Reference(array_id)) => {
+            let array_id = *array_id;
+            if let Ok(array) = doc.get_object_mut(array_id).and_then(Object::as_array_mut) {
+                array.extend(refs);
+                return Ok(());
+            }
+        }
+        Ok(Object::Array(_)) => {
+            let page = doc.get_dictionary_mut(page_id).map_err(|e| e.to_string())?;
+            if let Ok(array) = page.get_mut(b"Annots").and_then(Object::as_array_mut) {
+                array.extend(refs);
+                return Ok(());
+            }
+        }
+        _ => {}
+    }
+
+    // Missing or malformed: start a fresh array
+    let page = doc.get_dictionary_mut(page_id).map_err(|e| e.to_string())?;
+    page.set("Annots", refs.collect::<Vec<Object>>());
+    Ok(())
+}
+
+/// Width/height of the page's (possibly inherited) MediaBox, like pdf-lib's `page.getSize()`
+pub fn page_size(doc: &Document, page_id: ObjectId) -> (f32, f32) {
+    match media_box(doc, page_id) {
+        Some([x0, y0, x1, y1]) => ((x1 - x0).abs(), (y1 - y0).abs()),
+        None => DEFAULT_PAGE_SIZE,
+    }
+}
+
+/// The page's (possibly inherited) MediaBox as [left, bottom, right, top]
+pub fn media_box(doc: &Document, page_id: ObjectId) -> Option<[f32; 4]> {
+    let values: Vec<f32> = inherited(doc, page_id, b"MediaBox")?
+        .as_array()
+        .ok()?
+        .iter()
+        .filter_map(|v| doc.dereference(v).ok()?.1.as_float().ok())
+        .collect();
+    let [x0, y0, x1, y1] = values[..] else {
+        return None;
+    };
+    Some([x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1)])
+}
+
+/// The page's (possibly inherited) /Rotate: 0, 90, 180 or 270 degrees clockwise
+pub fn page_rotation(doc: &Document, page_id: ObjectId) -> u32 {
+    let degrees = inherited(doc, page_id, b"Rotate")
+        .and_then(|obj| obj.as_i64().ok())
+        .unwrap_or(0);
+    (degrees.rem_euclid(360) as u32) / 90 * 90
+}
+
+/// A page attribute that may be set on the page or any of its ancestors
+fn inherited<'a>(doc: &'a Document, page_id: ObjectId, key: &[u8]) -> Option<&'a Object> {
+    let mut node_id = Some(page_id);
+    // Bounded walk up the page tree in case of a Parent cycle
+    for _ in 0..32 {
+        let dict = doc.get_dictionary(node_id?).ok()?;
+        if let Some((_, value)) = dict.get(key).ok().and_then(|value| doc.dereference(value).ok()) {
+            return Some(value);
+        }
+        node_id = dict.get(b"Parent").and_then(Object::as_reference).ok();
+    }
+    None
+}
+
+fn reals(values: &[f64]) -> Vec<Object> {
+    values.iter().map(|&v| Object::Real(v as f32)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::annotations::{extract_annotations, parse_pdf_date};
+
+    /// A saved document with `count` blank Letter pages
+    fn blank_pdf(count: u32) -> Vec<u8> {
+        let mut doc = Document::with_version("1.7");
+        let pages_id = doc.new_object_id();
+        let kids: Vec<Object> = (0..count)
+            .map(|_| {
+                doc.add_object(dictionary! {
+                    "Type" => "Page",
+                    "Parent" => pages_id,
+                    "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+                })
+                .into()
+            })
+            .collect();
+        let pages = dictionary! { "Type" => "Pages", "Kids" => kids, "Count" => count };
+        doc.objects.insert(pages_id, Object::Dictionary(pages));
+        let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        doc.trailer.set("Root", catalog_id);
+
+        let mut bytes = Vec::new();
+        doc.save_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    /// A saved one-page document with the given MediaBox and /Rotate
+    fn turned_pdf(media_box: [i64; 4], rotation: i64) -> Vec<u8> {
+        let mut doc = Document::load_mem(&blank_pdf(1)).unwrap();
+        let page_id = doc.page_iter().next().unwrap();
+        let page = doc.get_dictionary_mut(page_id).unwrap();
+        page.set("MediaBox", media_box.map(Object::Integer).to_vec());
+        page.set("Rotate", rotation);
+        let mut bytes = Vec::new();
+        doc.save_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    /// A PDF 1.5 file with `count` blank pages, its objects in an object stream and a cross-reference stream
+    fn compressed_pdf(count: u32) -> Vec<u8> {
+        let mut doc = Document::load_mem(&blank_pdf(count)).unwrap();
+        doc.version = "1.5".to_string();
+        let mut bytes = Vec::new();
+        doc.save_modern(&mut bytes).unwrap();
+        bytes
+    }
+
+    fn comment(page: u32, text: &str) -> Comment {
+        Comment {
+            id: "c1".to_string(),
+            side: None,
+            x: 10.0,
+            y: 20.0,
+            page: Some(page),
+            text: Some(text.to_string()),
+            author: None,
+            timestamp: Some("2024-01-31T15:45:00+01:00".to_string()),
+            highlight_rects: None,
+            highlight_rect: None,
+        }
+    }
+
+    fn annotate(original: &[u8], comments: &[Comment], bookmarks: &[Bookmark], mode: SaveMode) -> Document {
+        let unlocked = unlock::unlock(original, "").unwrap();
+        let bytes = annotate_bytes(original.to_vec(), unlocked, comments, bookmarks, mode, false).unwrap();
+        Document::load_mem(&bytes).unwrap()
+    }
+
+    #[test]
+    fn comments_read_back_at_the_same_position() {
+        let doc = annotate(&blank_pdf(1), &[comment(1, "Looks good")], &[], SaveMode::Rewrite);
+        let annotations = extract_annotations(&doc, Side::Left);
+
+        // The popup is not listed
+        assert_eq!(annotations.len(), 1);
+        let note = &annotations[0];
+        assert_eq!((note.subtype.as_str(), note.text.as_str()), ("Text", "Looks good"));
+        assert_eq!(note.author.as_deref(), Some("Author"));
+        assert!((note.x - 10.0).abs() < 1e-3 && (note.y - 20.0).abs() < 1e-3, "{} {}", note.x, note.y);
+        let created = note.created.as_deref().and_then(|d| DateTime::parse_from_rfc3339(d).ok());
+        assert_eq!(created, DateTime::parse_from_rfc3339("2024-01-31T15:45:00+01:00").ok());
+    }
+
+    #[test]
+    fn highlights_get_one_quad_per_rect() {
+        let mut highlight = comment(1, "");
+        highlight.highlight_rects = Some(vec![
+            HighlightRect { left: 10.0, top: 10.0, right: 50.0, bottom: 12.0 },
+            HighlightRect { left: 10.0, top: 12.0, right: 30.0, bottom: 14.0 },
+        ]);
+        let doc = annotate(&blank_pdf(1), &[highlight], &[], SaveMode::Rewrite);
+        let annotations = extract_annotations(&doc, Side::Left);
+
+        // No text, so no popup either
+        assert_eq!(annotations.len(), 1);
+        assert_eq!(annotations[0].subtype, "Highlight");
+        assert_eq!(annotations[0].quad_points.len(), 16);
+        let rects = annotations[0].highlight_rects.as_ref().unwrap();
+        assert!((rects[1].right - 30.0).abs() < 1e-3 && (rects[1].bottom - 14.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn positions_survive_rotation_and_box_origin() {
+        let mut highlight = comment(1, "");
+        highlight.highlight_rects = Some(vec![HighlightRect { left: 10.0, top: 30.0, right: 60.0, bottom: 35.0 }]);
+        for rotation in [0, 90, 180, 270] {
+            let original = turned_pdf([100, -50, 400, 350], rotation);
+            let doc = annotate(&original, &[comment(1, "Turned"), highlight.clone()], &[], SaveMode::Rewrite);
+            let annotations = extract_annotations(&doc, Side::Left);
+            assert_eq!(annotations.len(), 2);
+
+            let note = &annotations[0];
+            assert!((note.x - 10.0).abs() < 1e-3 && (note.y - 20.0).abs() < 1e-3, "{}: {} {}", rotation, note.x, note.y);
+            let rect = note.rect.unwrap();
+            assert!((rect[2] - rect[0] - 20.0).abs() < 1e-3 && (rect[3] - rect[1] - 20.0).abs() < 1e-3);
+
+            let rects = annotations[1].highlight_rects.as_ref().unwrap();
+            let r = rects[0];
+            for (actual, expected) in [(r.left, 10.0), (r.top, 30.0), (r.right, 60.0), (r.bottom, 35.0)] {
+                assert!((actual - expected).abs() < 1e-3, "{}: {:?}", rotation, r);
+            }
+        }
+    }
+
+    #[test]
+    fn comments_on_missing_pages_are_skipped() {
+        let doc = annotate(&blank_pdf(1), &[comment(2, "Nowhere")], &[], SaveMode::Rewrite);
+        assert!(extract_annotations(&doc, Side::Left).is_empty());
+    }
+
+    #[test]
+    fn bookmarks_replace_the_outline() {
+        let bookmarks = [
+            Bookmark { page: 2, label: Some("Second".to_string()) },
+            Bookmark { page: 1, label: None },
+            Bookmark { page: 9, label: None },
+        ];
+        let doc = annotate(&blank_pdf(2), &[], &bookmarks, SaveMode::Rewrite);
+
+        let outlines_id = doc.catalog().unwrap().get(b"Outlines").unwrap().as_reference().unwrap();
+        let outlines = doc.get_dictionary(outlines_id).unwrap();
+        assert_eq!(outlines.get(b"Count").unwrap().as_i64().unwrap(), 2);
+        let first = doc.get_dictionary(outlines.get(b"First").unwrap().as_reference().unwrap()).unwrap();
+        assert_eq!(lopdf::decode_text_string(first.get(b"Title").unwrap()).unwrap(), "Second");
+        let second = doc.get_dictionary(first.get(b"Next").unwrap().as_reference().unwrap()).unwrap();
+        assert_eq!(lopdf::decode_text_string(second.get(b"Title").unwrap()).unwrap(), "Page 1");
+    }
+
+    #[test]
+    fn incremental_saves_keep_the_original_bytes() {
+        let original = blank_pdf(1);
+        let unlocked = unlock::unlock(&original, "").unwrap();
+        let bytes = annotate_bytes(
+            original.clone(),
+            unlocked,
+            &[comment(1, "Appended")],
+            &[],
+            SaveMode::Incremental,
+            false,
+        )
+        .unwrap();
+
+        assert!(bytes.len() > original.len() && bytes.starts_with(&original));
+        let doc = Document::load_mem(&bytes).unwrap();
+        assert_eq!(extract_annotations(&doc, Side::Left)[0].text, "Appended");
+    }
+
+    #[test]
+    fn incremental_saves_append_to_cross_reference_streams() {
+        let original = compressed_pdf(2);
+        assert!(original.starts_with(b"%PDF-1.5"));
+        let contains = |bytes: &[u8], needle: &[u8]| bytes.windows(needle.len()).any(|w| w == needle);
+        assert!(contains(&original, b"/XRef") && contains(&original, b"/ObjStm"));
+        assert!(!contains(&original, b"\ntrailer"));
+
+        let unlocked = unlock::unlock(&original, "").unwrap();
+        let comments = [comment(2, "On page two")];
+        let bookmarks = [Bookmark { page: 1, label: Some("Start".to_string()) }];
+        let bytes = annotate_bytes(original.clone(), unlocked, &comments, &bookmarks, SaveMode::Incremental, false)
+            .unwrap();
+
+        assert!(bytes.len() > original.len() && bytes.starts_with(&original));
+        // The update has a cross-reference stream of its own that refers back to the original one
+        let update = &bytes[original.len()..];
+        assert!(contains(update, b"/XRef") && !contains(update, b"\ntrailer"));
+        let startxref = |bytes: &[u8]| {
+            let text = String::from_utf8_lossy(bytes);
+            let tail = &text[text.rfind("startxref").unwrap() + "startxref".len()..];
+            tail.split_whitespace().next().unwrap().to_string()
+        };
+        assert!(contains(update, format!("/Prev {}", startxref(&original)).as_bytes()));
+
+        let doc = Document::load_mem(&bytes).unwrap();
+        assert_eq!(doc.get_pages().len(), 2);
+        let annotations = extract_annotations(&doc, Side::Left);
+        assert_eq!(annotations.len(), 1);
+        assert_eq!((annotations[0].page, annotations[0].text.as_str()), (2, "On page two"));
+        let catalog = doc.catalog().unwrap();
+        assert!(catalog.get(b"Outlines").is_ok());
+    }
+
+    #[test]
+    fn pdf_dates_round_trip() {
+        let now = Local::now();
+        let parsed = parse_pdf_date(&format_pdf_date(now)).unwrap();
+        let parsed = DateTime::parse_from_rfc3339(&parsed).unwrap();
+        assert_eq!(parsed.timestamp(), now.timestamp());
+    }
+}
@@ -0,0 +1,537 @@
+//! Comments and bookmarks kept on disk for every document that was annotated
+//!
+//! Each document gets one JSON file in `<app data>/annotations/`, named after
+//! a fingerprint that follows the document rather than its path: the first
+//! half of the trailer `/ID` (which survives saves and copies), or a SHA-256
+//! of the whole file when there is none. Comments and bookmarks are stored as
+//! the frontend's JSON objects; comments lose the viewer side, so notes come
+//! back on either side.
+
+use crate::atomic_write::{self, FileError, FileErrorKind};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::SystemTime;
+
+/// Bytes searched for the trailer at the end of the file, at `startxref` and at the start
+const TRAILER_WINDOW: u64 = 16 * 1024;
+
+/// A comment as the frontend sends it: `{ id, x, y, page, text, highlightRects, ... }`
+pub type StoredComment = Map<String, Value>;
+
+/// A bookmark as `useBookmarks` keeps it: `{ id, page, label, created }`
+pub type StoredBookmark = Map<String, Value>;
+
+/// Size and mtime the cached key was computed for, and the key
+type CachedKey = (u64, Option<SystemTime>, String);
+
+#[derive(Serialize, Deserialize, Default)]
+struct DocumentRecord {
+    key: String,
+    /// Where the document was last opened from, for anyone browsing the store
+    path: String,
+    #[serde(default)]
+    comments: BTreeMap<String, StoredComment>,
+    #[serde(default)]
+    bookmarks: Vec<StoredBookmark>,
+}
+
+/// Bookmarks exported to (or imported from) a standalone JSON file
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct BookmarkSet {
+    /// Fingerprint of the document the set was made for
+    #[serde(default)]
+    pub fingerprint: Option<String>,
+    /// File name of that document
+    #[serde(default)]
+    pub name: Option<String>,
+    pub bookmarks: Vec<StoredBookmark>,
+}
+
+/// Bookmark files may also be a bare array, like `twice-pdf annotate --bookmarks` takes
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BookmarkFile {
+    Set(BookmarkSet),
+    List(Vec<StoredBookmark>),
+}
+
+/// Per-document comment and bookmark files (managed Tauri state)
+pub struct AnnotationStore {
+    dir: PathBuf,
+    /// Document keys by path, reused while the file is unchanged
+    keys: Mutex<HashMap<PathBuf, CachedKey>>,
+    /// Serialises read-modify-write of the record files
+    records: Mutex<()>,
+}
+
+impl AnnotationStore {
+    pub fn new(dir: PathBuf) -> Self {
+        AnnotationStore {
+            dir,
+            keys: Mutex::new(HashMap::new()),
+            records: Mutex::new(()),
+        }
+    }
+
+    /// Fingerprint the document at `path` is stored under
+    pub fn fingerprint(&self, path: &Path) -> Result<String, FileError> {
+        self.key(path)
+    }
+
+    /// All stored comments of the document at `path`
+    pub fn comments(&self, path: &Path) -> Result<Vec<StoredComment>, FileError> {
+        let key = self.key(path)?;
+        let _guard = lock(&self.records);
+        Ok(self.read_record(&key)?.comments.into_values().collect())
+    }
+
+    /// Stored bookmarks of the document at `path`
+    pub fn bookmarks(&self, path: &Path) -> Result<Vec<StoredBookmark>, FileError> {
+        let key = self.key(path)?;
+        let _guard = lock(&self.records);
+        Ok(self.read_record(&key)?.bookmarks)
+    }
+
+    /// Replace the bookmarks of the document at `path`
+    pub fn set_bookmarks(&self, path: &Path, bookmarks: Vec<StoredBookmark>) -> Result<(), FileError> {
+        self.update(path, |record| {
+            record.bookmarks = bookmarks;
+            Ok(())
+        })
+    }
+
+    /// Store a new comment; fails with `alreadyExists` if its id is taken
+    pub fn add(&self, path: &Path, comment: StoredComment) -> Result<(), FileError> {
+        let id = comment_id(path, &comment)?;
+        self.update(path, |record| {
+            if record.comments.contains_key(&id) {
+                return Err(FileError {
+                    kind: FileErrorKind::AlreadyExists,
+                    path: path.to_string_lossy().into_owned(),
+                    message: format!("Comment {} already exists", id),
+                });
+            }
+            record.comments.insert(id, without_side(comment));
+            Ok(())
+        })
+    }
+
+    /// Create or replace a comment
+    pub fn save(&self, path: &Path, comment: StoredComment) -> Result<(), FileError> {
+        let id = comment_id(path, &comment)?;
+        self.update(path, |record| {
+            record.comments.insert(id, without_side(comment));
+            Ok(())
+        })
+    }
+
+    /// Remove a comment, returns whether it existed
+    pub fn delete(&self, path: &Path, id: &str) -> Result<bool, FileError> {
+        self.update(path, |record| Ok(record.comments.remove(id).is_some()))
+    }
+
+    fn update<T>(
+        &self,
+        path: &Path,
+        change: impl FnOnce(&mut DocumentRecord) -> Result<T, FileError>,
+    ) -> Result<T, FileError> {
+        let key = self.key(path)?;
+        let _guard = lock(&self.records);
+        let mut record = self.read_record(&key)?;
+        let result = change(&mut record)?;
+
+        let file = self.record_path(&key);
+        if record.comments.is_empty() && record.bookmarks.is_empty() {
+            match fs::remove_file(&file) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(FileError::io("delete", &file, e)),
+                _ => {}
+            }
+            return Ok(result);
+        }
+        record.key = key;
+        record.path = path.to_string_lossy().into_owned();
+        let json = serde_json::to_vec_pretty(&record)
+            .map_err(|e| FileError::other(&file, format!("Failed to serialise annotations: {}", e)))?;
+        fs::create_dir_all(&self.dir).map_err(|e| FileError::io("create", &self.dir, e))?;
+        atomic_write::write_file(&file, &json, false)?;
+        Ok(result)
+    }
+
+    fn read_record(&self, key: &str) -> Result<DocumentRecord, FileError> {
+        let file = self.record_path(key);
+        match fs::read(&file) {
+            Ok(json) => serde_json::from_slice(&json)
+                .map_err(|e| FileError::other(&file, format!("Invalid annotation store {}: {}", file.display(), e))),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(DocumentRecord::default()),
+            Err(e) => Err(FileError::io("read", &file, e)),
+        }
+    }
+
+    fn record_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    fn key(&self, path: &Path) -> Result<String, FileError> {
+        let metadata = fs::metadata(path).map_err(|e| FileError::io("read", path, e))?;
+        let stamp = (metadata.len(), metadata.modified().ok());
+        if let Some((size, modified, key)) = lock(&self.keys).get(path) {
+            if (*size, *modified) == stamp {
+                return Ok(key.clone());
+            }
+        }
+        let key = document_key(path)?;
+        lock(&self.keys).insert(path.to_path_buf(), (stamp.0, stamp.1, key.clone()));
+        Ok(key)
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn comment_id(path: &Path, comment: &StoredComment) -> Result<String, FileError> {
+    match comment.get("id") {
+        Some(Value::String(id)) if !id.is_empty() => Ok(id.clone()),
+        _ => Err(FileError::other(path, "Comment has no id".to_string())),
+    }
+}
+
+/// The side is where the document is shown right now, not part of the note
+fn without_side(mut comment: StoredComment) -> StoredComment {
+    comment.remove("side");
+    comment
+}
+
+/// Write a bookmark set file
+pub fn write_bookmark_set(path: &Path, set: &BookmarkSet) -> Result<(), FileError> {
+    let json = serde_json::to_vec_pretty(set)
+        .map_err(|e| FileError::other(path, format!("Failed to serialise bookmarks: {}", e)))?;
+    atomic_write::write_file(path, &json, false)
+}
+
+/// Read a bookmark set file (or a bare bookmark array)
+pub fn read_bookmark_set(path: &Path) -> Result<BookmarkSet, FileError> {
+    let json = fs::read(path).map_err(|e| FileError::io("read", path, e))?;
+    match serde_json::from_slice(&json) {
+        Ok(BookmarkFile::Set(set)) => Ok(set),
+        Ok(BookmarkFile::List(bookmarks)) => Ok(BookmarkSet { bookmarks, ..Default::default() }),
+        Err(e) => Err(FileError::other(path, format!("Invalid bookmarks file {}: {}", path.display(), e))),
+    }
+}
+
+/// `id-<hex>` from the trailer `/ID`, or `sha256-<hex>` of the file contents
+pub fn document_key(path: &Path) -> Result<String, FileError> {
+    let read_error = |e| FileError::io("read", path, e);
+    let mut file = File::open(path).map_err(read_error)?;
+    if let Some(id) = trailer_id(&mut file).map_err(read_error)? {
+        return Ok(format!("id-{}", hex::encode(id)));
+    }
+    file.seek(SeekFrom::Start(0)).map_err(read_error)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher).map_err(read_error)?;
+    Ok(format!("sha256-{}", hex::encode(hasher.finalize())))
+}
+
+/// First string of the newest trailer's `/ID`, found without parsing the document.
+/// Looks at the end of the file (classic trailers), at the `startxref` target
+/// (cross-reference stream dictionaries) and at the start (linearized files).
+fn trailer_id(file: &mut File) -> io::Result<Option<Vec<u8>>> {
+    let size = file.metadata()?.len();
+    let tail = read_window(file, size.saturating_sub(TRAILER_WINDOW))?;
+    if let Some(id) = last_id(&tail) {
+        return Ok(Some(id));
+    }
+    let startxref = rfind(&tail, b"startxref").and_then(|pos| {
+        let digits: String = tail[pos + 9..]
+            .iter()
+            .skip_while(|b| b.is_ascii_whitespace())
+            .take_while(|b| b.is_ascii_digit())
+            .map(|&b| b as char)
+            .collect();
+        digits.parse::<u64>().ok()
+    });
+    if let Some(offset) = startxref.filter(|&offset| offset < size) {
+        if let Some(id) = last_id(&read_window(file, offset)?) {
+            return Ok(Some(id));
+        }
+    }
+    Ok(last_id(&read_window(file, 0)?))
+}
+
+fn read_window(file: &mut File, offset: u64) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    file.seek(SeekFrom::Start(offset))?;
+    file.take(TRAILER_WINDOW).read_to_end(&mut data)?;
+    Ok(data)
+}
+
+fn rfind(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).rposition(|w| w == needle)
+}
+
+/// Value of the last `/ID [<...> ...]` entry in `data` (not `/IDTree` etc.)
+fn last_id(data: &[u8]) -> Option<Vec<u8>> {
+    let mut end = data.len();
+    while let Some(pos) = rfind(&data[..end], b"/ID") {
+        let rest = &data[pos + 3..];
+        if rest.first().is_some_and(|b| b.is_ascii_whitespace() || *b == b'[') {
+            let id = first_string(rest).filter(|id| !id.is_empty());
+            if id.is_some() {
+                return id;
+            }
+        }
+        end = pos;
+    }
+    None
+}
+
+/// First hex or literal string of an array like `[<0a1b...> <...>]`
+fn first_string(data: &[u8]) -> Option<Vec<u8>> {
+    let skip_space = |d: &[u8]| d.iter().position(|b| !b.is_ascii_whitespace());
+    let rest = &data[skip_space(data)?..];
+    let rest = rest.strip_prefix(b"[")?;
+    let rest = &rest[skip_space(rest)?..];
+    match rest.first()? {
+        b'<' => {
+            let end = rest.iter().position(|&b| b == b'>')?;
+            let mut digits: Vec<u8> = rest[1..end].iter().copied().filter(|b| !b.is_ascii_whitespace()).collect();
+            // An odd final digit is followed by an implied 0
+            if digits.len() % 2 == 1 {
+                digits.push(b'0');
+            }
+            hex::decode(digits).ok()
+        }
+        b'(' => literal_string(&rest[1..]),
+        _ => None,
+    }
+}
+
+/// Body of a literal string (after the opening parenthesis), with escapes resolved
+fn literal_string(data: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut depth = 0;
+    let mut bytes = data.iter().copied().peekable();
+    while let Some(b) = bytes.next() {
+        match b {
+            b'\\' => match bytes.next()? {
+                b'n' => out.push(b'\n'),
+                b'r' => out.push(b'\r'),
+                b't' => out.push(b'\t'),
+                b'b' => out.push(0x08),
+                b'f' => out.push(0x0c),
+                digit @ b'0'..=b'7' => {
+                    let mut value = (digit - b'0') as u32;
+                    for _ in 0..2 {
+                        match bytes.peek() {
+                            Some(&d @ b'0'..=b'7') => {
+                                value = value * 8 + (d - b'0') as u32;
+                                bytes.next();
+                            }
+                            _ => break,
+                        }
+                    }
+                    out.push(value as u8);
+                }
+                // Line continuation
+                b'\r' | b'\n' => {}
+                other => out.push(other),
+            },
+            b'(' => {
+                depth += 1;
+                out.push(b);
+            }
+            b')' if depth == 0 => return Some(out),
+            b')' => {
+                depth -= 1;
+                out.push(b);
+            }
+            _ => out.push(b),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("twice-pdf-annotation-store-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn key_of(dir: &Path, contents: &[u8]) -> String {
+        let path = dir.join("doc.pdf");
+        fs::write(&path, contents).unwrap();
+        document_key(&path).unwrap()
+    }
+
+    fn comment(id: &str, text: &str) -> StoredComment {
+        match json!({ "id": id, "page": 1, "x": 10, "y": 20, "text": text, "side": "left" }) {
+            Value::Object(map) => map,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn reads_hex_ids() {
+        let dir = test_dir("hex");
+        let pdf = b"%PDF-1.4\n...\ntrailer\n<< /Size 4 /Root 1 0 R /ID [<0A1B 2c3d> <FFFF>] >>\nstartxref\n9\n%%EOF\n";
+        assert_eq!(key_of(&dir, pdf), "id-0a1b2c3d");
+        // An odd final digit is followed by an implied 0
+        assert_eq!(key_of(&dir, b"trailer << /ID [<ABC><ABC>] >>"), "id-abc0");
+    }
+
+    #[test]
+    fn reads_literal_ids_with_escapes() {
+        assert_eq!(literal_string(b"a\\(b\\)c) tail").unwrap(), b"a(b)c");
+        assert_eq!(literal_string(b"x(nested)y)").unwrap(), b"x(nested)y");
+        assert_eq!(literal_string(b"\\101\\0617\\n\\\\)").unwrap(), b"A17\n\\");
+        assert_eq!(literal_string(b"line\\\ncontinued)").unwrap(), b"linecontinued");
+        assert_eq!(literal_string(b"unterminated"), None);
+
+        let dir = test_dir("literal");
+        assert_eq!(key_of(&dir, b"trailer << /ID [ (\\001\\(z) (b) ] >>"), "id-01287a");
+    }
+
+    #[test]
+    fn skips_other_id_keys_and_empty_ids() {
+        assert_eq!(last_id(b"/ID [<01>] /IDTree 5 0 R"), Some(vec![1]));
+        assert_eq!(last_id(b"/ID [<02>] /ID [<>]"), Some(vec![2]));
+        assert_eq!(last_id(b"/IDs [<03>]"), None);
+    }
+
+    #[test]
+    fn the_newest_trailer_wins() {
+        let dir = test_dir("incremental");
+        let pdf = b"%PDF-1.4\ntrailer\n<< /ID [<1111> <1111>] >>\nstartxref\n9\n%%EOF\n\
+                    1 0 obj\n<< >>\nendobj\ntrailer\n<< /Prev 9 /ID [<2222> <2222>] >>\nstartxref\n50\n%%EOF\n";
+        assert_eq!(key_of(&dir, pdf), "id-2222");
+        let pdf = b"%PDF-1.4\ntrailer\n<< /ID [<1111> <1111>] >>\n%%EOF\ntrailer\n<< /ID [<3333> <4444>] >>\n%%EOF\n";
+        assert_eq!(key_of(&dir, pdf), "id-3333");
+    }
+
+    #[test]
+    fn finds_ids_at_startxref_beyond_the_tail() {
+        let dir = test_dir("startxref");
+        let mut pdf = b"%PDF-1.5\n".to_vec();
+        let xref = pdf.len();
+        pdf.extend_from_slice(b"9 0 obj\n<< /Type /XRef /ID [<abcd> <abcd>] >>\nendobj\n");
+        pdf.resize(pdf.len() + 2 * TRAILER_WINDOW as usize, b' ');
+        pdf.extend_from_slice(format!("\nstartxref\n{}\n%%EOF\n", xref).as_bytes());
+        assert_eq!(key_of(&dir, &pdf), "id-abcd");
+    }
+
+    #[test]
+    fn hashes_files_without_an_id() {
+        let dir = test_dir("sha");
+        let pdf = b"%PDF-1.4\ntrailer\n<< /Size 1 >>\n%%EOF\n";
+        let expected = format!("sha256-{}", hex::encode(Sha256::digest(pdf)));
+        assert_eq!(key_of(&dir, pdf), expected);
+    }
+
+    #[test]
+    fn stores_comments_per_document() {
+        let dir = test_dir("crud");
+        let store = AnnotationStore::new(dir.join("annotations"));
+        let path = dir.join("doc.pdf");
+        fs::write(&path, b"%PDF-1.4\ntrailer << /ID [<beef> <beef>] >>\n%%EOF\n").unwrap();
+        let record = dir.join("annotations").join("id-beef.json");
+
+        assert!(store.comments(&path).unwrap().is_empty());
+        store.add(&path, comment("a", "first")).unwrap();
+        store.add(&path, comment("b", "second")).unwrap();
+        assert_eq!(store.add(&path, comment("a", "again")).unwrap_err().kind, FileErrorKind::AlreadyExists);
+        assert!(store.add(&path, comment("", "no id")).is_err());
+        assert!(record.exists());
+
+        store.save(&path, comment("a", "edited")).unwrap();
+        let comments = store.comments(&path).unwrap();
+        assert_eq!(comments.len(), 2);
+        assert_eq!(comments[0]["text"], "edited");
+        assert!(comments.iter().all(|c| !c.contains_key("side")));
+
+        // The record follows the document to a copy with the same /ID
+        let copy = dir.join("copy.pdf");
+        fs::copy(&path, &copy).unwrap();
+        assert_eq!(store.comments(&copy).unwrap().len(), 2);
+
+        assert!(store.delete(&path, "a").unwrap());
+        assert!(!store.delete(&path, "a").unwrap());
+        assert!(record.exists());
+        assert!(store.delete(&path, "b").unwrap());
+        assert!(!record.exists());
+    }
+
+    #[test]
+    fn bookmarks_keep_the_record_alive() {
+        let dir = test_dir("bookmarks");
+        let store = AnnotationStore::new(dir.join("annotations"));
+        let path = dir.join("doc.pdf");
+        fs::write(&path, b"%PDF-1.4\n%%EOF\n").unwrap();
+        let bookmark = comment("m", "mark");
+
+        store.add(&path, comment("a", "note")).unwrap();
+        store.set_bookmarks(&path, vec![bookmark.clone()]).unwrap();
+        store.delete(&path, "a").unwrap();
+        assert_eq!(store.bookmarks(&path).unwrap(), vec![bookmark]);
+        store.set_bookmarks(&path, Vec::new()).unwrap();
+        assert_eq!(fs::read_dir(dir.join("annotations")).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn rekeys_changed_files() {
+        let dir = test_dir("rekey");
+        let store = AnnotationStore::new(dir.join("annotations"));
+        let path = dir.join("doc.pdf");
+        fs::write(&path, b"%PDF-1.4\n/ID [<01>]\n").unwrap();
+        assert_eq!(store.fingerprint(&path).unwrap(), "id-01");
+        fs::write(&path, b"%PDF-1.4\n/ID [<0203>]\n").unwrap();
+        assert_eq!(store.fingerprint(&path).unwrap(), "id-0203");
+    }
+
+    #[test]
+    fn bookmark_sets_round_trip() {
+        let dir = test_dir("bookmark-set");
+        let file = dir.join("doc.bookmarks.json");
+        let bookmarks = vec![comment("m1", "Intro"), comment("m2", "Results")];
+        let set = BookmarkSet {
+            fingerprint: Some("id-beef".into()),
+            name: Some("doc.pdf".into()),
+            bookmarks: bookmarks.clone(),
+        };
+        write_bookmark_set(&file, &set).unwrap();
+        let json: Value = serde_json::from_slice(&fs::read(&file).unwrap()).unwrap();
+        assert_eq!(json["fingerprint"], "id-beef");
+
+        let read = read_bookmark_set(&file).unwrap();
+        assert_eq!(read.fingerprint.as_deref(), Some("id-beef"));
+        assert_eq!(read.name.as_deref(), Some("doc.pdf"));
+        assert_eq!(read.bookmarks, bookmarks);
+    }
+
+    #[test]
+    fn bookmark_files_may_be_bare_arrays() {
+        let dir = test_dir("bookmark-list");
+        let file = dir.join("list.json");
+        fs::write(&file, r#"[{ "page": 3, "label": "Three" }]"#).unwrap();
+        let read = read_bookmark_set(&file).unwrap();
+        assert_eq!(read.fingerprint, None);
+        assert_eq!(read.bookmarks.len(), 1);
+        assert_eq!(read.bookmarks[0]["page"], 3);
+
+        fs::write(&file, r#"{ "name": "doc.pdf" }"#).unwrap();
+        assert!(read_bookmark_set(&file).is_err());
+        assert_eq!(read_bookmark_set(&dir.join("missing.json")).unwrap_err().kind, FileErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,464 @@
+//! Read existing annotations back out of a PDF
+//!
+//! Coordinates are normalised to the viewer's percentage model (0-100 of the
+//! page as displayed, i.e. after /Rotate, origin top-left) so JSON output can
+//! be loaded as comments again. Every subtype but popups is listed unless
+//! `--subtype` or `--comments-only` narrows it down.
+
+use crate::annotate::{media_box, page_rotation, page_size};
+use crate::cli::{AnnotationsArgs, AnnotationsCommand, ListAnnotationsArgs, ListFormat, Side};
+use crate::page_text::{page_glyphs, text_in_boxes};
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, TimeZone};
+use lopdf::{decode_text_string, Dictionary, Document, Object};
+use serde::Serialize;
+use std::io::Write;
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtractedAnnotation {
+    /// Same id scheme as annotations imported by the viewer
+    pub id: String,
+    pub side: Side,
+    /// 1-based page number
+    pub page: u32,
+    pub subtype: String,
+    /// Position of the top-left corner in percent of the page
+    pub x: f64,
+    pub y: f64,
+    /// /Contents
+    pub text: String,
+    /// /T
+    pub author: Option<String>,
+    /// /NM
+    pub name: Option<String>,
+    /// /M as ISO 8601, falling back to /CreationDate (viewer field)
+    pub timestamp: Option<String>,
+    pub created: Option<String>,
+    pub modified: Option<String>,
+    /// Raw /Rect in PDF points [left, bottom, right, top]
+    pub rect: Option<[f32; 4]>,
+    /// Raw /QuadPoints in PDF points
+    pub quad_points: Vec<f32>,
+    /// One rect per quad (or the /Rect for highlights without quads), in percent
+    pub highlight_rects: Option<Vec<PercentRect>>,
+    /// Page text underneath the quads/rect of text markup annotations
+    pub selected_text: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy)]
+pub struct PercentRect {
+    pub left: f64,
+    pub top: f64,
+    pub right: f64,
+    pub bottom: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// Text markup subtypes whose quads cover page text
+const MARKUP_SUBTYPES: &[&str] = &["Highlight", "Underline", "StrikeOut", "Squiggly"];
+
+/// Listed with `--comments-only`: notes plus text markup
+const COMMENT_SUBTYPES: &[&str] = &["Text", "Highlight", "Underline", "StrikeOut", "Squiggly"];
+
+/// Entry point for `twice-pdf annotations`, returns the process exit code
+pub fn run(args: &AnnotationsArgs) -> i32 {
+    let result = match &args.command {
+        AnnotationsCommand::List(list_args) => list(list_args),
+    };
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("twice-pdf annotations: {}", e);
+            1
+        }
+    }
+}
+
+fn list(args: &ListAnnotationsArgs) -> Result<(), String> {
+    let doc = Document::load(&args.input).map_err(|e| format!("Failed to read PDF {}: {}", args.input.display(), e))?;
+    let mut annotations = extract_annotations(&doc, args.side);
+    if args.comments_only {
+        annotations.retain(|a| COMMENT_SUBTYPES.contains(&a.subtype.as_str()));
+    } else if !args.subtype.is_empty() {
+        annotations.retain(|a| args.subtype.iter().any(|s| s.eq_ignore_ascii_case(&a.subtype)));
+    }
+
+    let output = match args.format {
+        ListFormat::Json => serde_json::to_string_pretty(&annotations).map_err(|e| e.to_string())? + "\n",
+        ListFormat::Csv => to_csv(&annotations)?,
+    };
+    // A closed pipe is not an error
+    let _ = std::io::stdout().write_all(output.as_bytes());
+    Ok(())
+}
+
+/// Every /Annots entry on every page except popups (the text of their parent), in page order
+pub fn extract_annotations(doc: &Document, side: Side) -> Vec<ExtractedAnnotation> {
+    let mut result = Vec::new();
+    for (page_number, page_id) in doc.get_pages() {
+        let annots = doc.get_page_annotations(page_id).unwrap_or_default();
+        if annots.is_empty() {
+            continue;
+        }
+        let frame = PageFrame::new(doc, page_id);
+        // Only lay out the page text when something needs it
+        let mut glyphs = None;
+
+        for (index, annot) in annots.into_iter().enumerate() {
+            let subtype = name(doc, annot, b"Subtype").unwrap_or_default();
+            if subtype == "Popup" {
+                continue;
+            }
+            let rect = numbers(doc, annot, b"Rect")
+                .filter(|r| r.len() == 4)
+                .map(|r| [r[0].min(r[2]), r[1].min(r[3]), r[0].max(r[2]), r[1].max(r[3])]);
+            let quad_points = numbers(doc, annot, b"QuadPoints").unwrap_or_default();
+
+            let is_markup = MARKUP_SUBTYPES.contains(&subtype.as_str());
+            let boxes: Vec<[f32; 4]> = if !quad_points.is_empty() {
+                quad_points.chunks_exact(8).map(quad_bounds).collect()
+            } else if is_markup {
+                rect.into_iter().collect()
+            } else {
+                Vec::new()
+            };
+
+            let selected_text = if is_markup && !boxes.is_empty() {
+                let glyphs = glyphs.get_or_insert_with(|| page_glyphs(doc, page_id).unwrap_or_default());
+                Some(text_in_boxes(glyphs, &boxes))
+            } else {
+                None
+            };
+            let highlight_rects = (!boxes.is_empty())
+                .then(|| boxes.iter().map(|b| frame.to_percent(b)).collect());
+
+            let annot_name = text(doc, annot, b"NM");
+            let created = date(doc, annot, b"CreationDate");
+            let modified = date(doc, annot, b"M");
+            let (x, y) = rect.map_or((0.0, 0.0), |r| {
+                let p = frame.to_percent(&r);
+                (p.left, p.top)
+            });
+
+            result.push(ExtractedAnnotation {
+                id: format!(
+                    "imported-{}-{}",
+                    side_name(side),
+                    annot_name.clone().unwrap_or_else(|| format!("p{}-{}", page_number, index))
+                ),
+                side,
+                page: page_number,
+                subtype,
+                x,
+                y,
+                text: text(doc, annot, b"Contents").unwrap_or_default(),
+                author: text(doc, annot, b"T"),
+                name: annot_name,
+                timestamp: modified.clone().or_else(|| created.clone()),
+                created,
+                modified,
+                rect,
+                quad_points,
+                highlight_rects,
+                selected_text,
+            });
+        }
+    }
+    result
+}
+
+fn side_name(side: Side) -> &'static str {
+    match side {
+        Side::Left => "left",
+        Side::Right => "right",
+    }
+}
+
+/// Quads are 4 points in the order used by the viewer export (top-left, top-right, bottom-left,
+/// bottom-right), but other writers vary, so just take the bounding box
+fn quad_bounds(quad: &[f32]) -> [f32; 4] {
+    let xs = [quad[0], quad[2], quad[4], quad[6]];
+    let ys = [quad[1], quad[3], quad[5], quad[7]];
+    [
+        xs.iter().copied().fold(f32::INFINITY, f32::min),
+        ys.iter().copied().fold(f32::INFINITY, f32::min),
+        xs.iter().copied().fold(f32::NEG_INFINITY, f32::max),
+        ys.iter().copied().fold(f32::NEG_INFINITY, f32::max),
+    ]
+}
+
+/// The page as the viewer shows it: its MediaBox, turned by /Rotate
+pub struct PageFrame {
+    /// [left, bottom, right, top] in PDF points
+    pub media_box: [f32; 4],
+    pub rotation: u32,
+}
+
+impl PageFrame {
+    pub fn new(doc: &Document, page_id: lopdf::ObjectId) -> Self {
+        let media_box = media_box(doc, page_id).unwrap_or_else(|| {
+            let (width, height) = page_size(doc, page_id);
+            [0.0, 0.0, width, height]
+        });
+        PageFrame { media_box, rotation: page_rotation(doc, page_id) }
+    }
+
+    /// Width and height in points as displayed
+    pub fn display_size(&self) -> (f64, f64) {
+        let [x0, y0, x1, y1] = self.media_box.map(f64::from);
+        match self.rotation {
+            90 | 270 => (y1 - y0, x1 - x0),
+            _ => (x1 - x0, y1 - y0),
+        }
+    }
+
+    /// A point in PDF points as fractions of the displayed page, origin top-left
+    pub fn to_display(&self, x: f64, y: f64) -> (f64, f64) {
+        let [x0, y0, x1, y1] = self.media_box.map(f64::from);
+        let (u, v) = ((x - x0) / (x1 - x0), (y - y0) / (y1 - y0));
+        match self.rotation {
+            90 => (v, u),
+            180 => (1.0 - u, v),
+            270 => (1.0 - v, 1.0 - u),
+            _ => (u, 1.0 - v),
+        }
+    }
+
+    /// Inverse of `to_display`
+    pub fn to_pdf(&self, x: f64, y: f64) -> (f64, f64) {
+        let [x0, y0, x1, y1] = self.media_box.map(f64::from);
+        let (u, v) = match self.rotation {
+            90 => (y, x),
+            180 => (1.0 - x, y),
+            270 => (1.0 - y, 1.0 - x),
+            _ => (x, 1.0 - y),
+        };
+        (x0 + u * (x1 - x0), y0 + v * (y1 - y0))
+    }
+
+    /// Same conversion as the import code in `handleFileUpload`, plus the box origin and rotation
+    fn to_percent(&self, rect: &[f32; 4]) -> PercentRect {
+        let [left, bottom, right, top] = rect.map(f64::from);
+        let (ax, ay) = self.to_display(left, bottom);
+        let (bx, by) = self.to_display(right, top);
+        let (left, right) = (ax.min(bx) * 100.0, ax.max(bx) * 100.0);
+        let (top, bottom) = (ay.min(by) * 100.0, ay.max(by) * 100.0);
+        PercentRect { left, top, right, bottom, width: right - left, height: bottom - top }
+    }
+}
+
+fn resolve<'a>(doc: &'a Document, dict: &'a Dictionary, key: &[u8]) -> Option<&'a Object> {
+    dict.get(key).ok().and_then(|obj| doc.dereference(obj).ok()).map(|(_, obj)| obj)
+}
+
+fn name(doc: &Document, dict: &Dictionary, key: &[u8]) -> Option<String> {
+    resolve(doc, dict, key)
+        .and_then(|obj| obj.as_name().ok())
+        .map(|n| String::from_utf8_lossy(n).into_owned())
+}
+
+fn text(doc: &Document, dict: &Dictionary, key: &[u8]) -> Option<String> {
+    resolve(doc, dict, key).and_then(|obj| decode_text_string(obj).ok())
+}
+
+fn numbers(doc: &Document, dict: &Dictionary, key: &[u8]) -> Option<Vec<f32>> {
+    let array = resolve(doc, dict, key)?.as_array().ok()?;
+    Some(
+        array
+            .iter()
+            .filter_map(|v| doc.dereference(v).ok()?.1.as_float().ok())
+            .collect(),
+    )
+}
+
+fn date(doc: &Document, dict: &Dictionary, key: &[u8]) -> Option<String> {
+    text(doc, dict, key).and_then(|raw| parse_pdf_date(&raw))
+}
+
+/// Parse `D:YYYYMMDDHHmmSSOHH'mm'` (every part after the year optional) into ISO 8601.
+/// Dates without a zone are taken as local time, like the viewer's import.
+pub fn parse_pdf_date(raw: &str) -> Option<String> {
+    let s = raw.trim().trim_start_matches("D:");
+    let digits: String = s.chars().take_while(char::is_ascii_digit).collect();
+    if digits.len() < 4 {
+        return None;
+    }
+    let part = |start: usize, default: u32| -> u32 {
+        digits.get(start..start + 2).and_then(|p| p.parse().ok()).unwrap_or(default)
+    };
+    let year: i32 = digits[..4].parse().ok()?;
+    let naive = NaiveDate::from_ymd_opt(year, part(4, 1), part(6, 1))?.and_hms_opt(part(8, 0), part(10, 0), part(12, 0))?;
+
+    let zone = &s[digits.len()..];
+    let offset = match zone.chars().next() {
+        Some('Z') => FixedOffset::east_opt(0),
+        Some(sign @ ('+' | '-')) => {
+            let nums: Vec<i32> = zone[1..]
+                .split('\'')
+                .filter_map(|p| p.get(..2).and_then(|n| n.parse().ok()))
+                .collect();
+            let seconds = nums.first().copied().unwrap_or(0) * 3600 + nums.get(1).copied().unwrap_or(0) * 60;
+            FixedOffset::east_opt(if sign == '-' { -seconds } else { seconds })
+        }
+        _ => None,
+    };
+
+    let date: DateTime<FixedOffset> = match offset {
+        Some(offset) => offset.from_local_datetime(&naive).single()?,
+        None => Local.from_local_datetime(&naive).earliest()?.fixed_offset(),
+    };
+    Some(date.to_rfc3339())
+}
+
+fn to_csv(annotations: &[ExtractedAnnotation]) -> Result<String, String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer
+        .write_record([
+            "id", "page", "subtype", "author", "contents", "selectedText", "created", "modified", "x", "y", "rect",
+            "quadPoints",
+        ])
+        .map_err(|e| e.to_string())?;
+
+    let join = |values: &[f32]| values.iter().map(f32::to_string).collect::<Vec<_>>().join(" ");
+    for a in annotations {
+        writer
+            .write_record([
+                a.id.clone(),
+                a.page.to_string(),
+                a.subtype.clone(),
+                a.author.clone().unwrap_or_default(),
+                a.text.clone(),
+                a.selected_text.clone().unwrap_or_default(),
+                a.created.clone().unwrap_or_default(),
+                a.modified.clone().unwrap_or_default(),
+                format!("{:.4}", a.x),
+                format!("{:.4}", a.y),
+                a.rect.map(|r| join(&r)).unwrap_or_default(),
+                join(&a.quad_points),
+            ])
+            .map_err(|e| e.to_string())?;
+    }
+
+    let bytes = writer.into_inner().map_err(|e| e.to_string())?;
+    String::from_utf8(bytes).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::dictionary;
+
+    /// A document with one page, which inherits the attributes in `pages`
+    fn one_page(mut pages: Dictionary, annots: Vec<Dictionary>) -> Document {
+        let mut doc = Document::with_version("1.7");
+        let pages_id = doc.new_object_id();
+        let annots: Vec<Object> = annots.into_iter().map(|annot| doc.add_object(annot).into()).collect();
+        let page_id = doc.add_object(dictionary! { "Type" => "Page", "Parent" => pages_id, "Annots" => annots });
+        pages.set("Type", "Pages");
+        pages.set("Kids", vec![page_id.into()]);
+        pages.set("Count", 1);
+        doc.objects.insert(pages_id, Object::Dictionary(pages));
+        let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        doc.trailer.set("Root", catalog_id);
+        doc
+    }
+
+    fn note(rect: [f32; 4]) -> Dictionary {
+        let rect: Vec<Object> = rect.into_iter().map(Object::Real).collect();
+        dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Text",
+            "Rect" => rect,
+            "Contents" => Object::string_literal("Hi"),
+        }
+    }
+
+    fn assert_near(actual: (f64, f64), expected: (f64, f64)) {
+        assert!(
+            (actual.0 - expected.0).abs() < 1e-4 && (actual.1 - expected.1).abs() < 1e-4,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn parses_dates_with_offsets() {
+        assert_eq!(parse_pdf_date("D:20240131154500+01'00'").as_deref(), Some("2024-01-31T15:45:00+01:00"));
+        assert_eq!(parse_pdf_date("D:20240131154500-05'30").as_deref(), Some("2024-01-31T15:45:00-05:30"));
+        assert_eq!(parse_pdf_date("D:20240131154500Z").as_deref(), Some("2024-01-31T15:45:00+00:00"));
+        // Missing parts default to the start of the period
+        assert_eq!(parse_pdf_date("D:202401Z").as_deref(), Some("2024-01-01T00:00:00+00:00"));
+        assert!(parse_pdf_date("D:2024").is_some());
+    }
+
+    #[test]
+    fn rejects_invalid_dates() {
+        assert_eq!(parse_pdf_date(""), None);
+        assert_eq!(parse_pdf_date("D:24"), None);
+        assert_eq!(parse_pdf_date("yesterday"), None);
+        assert_eq!(parse_pdf_date("D:20241332Z"), None);
+    }
+
+    #[test]
+    fn positions_are_relative_to_the_media_box() {
+        let pages = dictionary! { "MediaBox" => vec![100.into(), 100.into(), 300.into(), 200.into()] };
+        // Top-left corner of the page
+        let doc = one_page(pages, vec![note([100.0, 180.0, 120.0, 200.0])]);
+        let annotations = extract_annotations(&doc, Side::Left);
+
+        assert_eq!(annotations.len(), 1);
+        assert_eq!(annotations[0].id, "imported-left-p1-0");
+        assert_eq!(annotations[0].text, "Hi");
+        assert_near((annotations[0].x, annotations[0].y), (0.0, 0.0));
+    }
+
+    #[test]
+    fn positions_follow_inherited_rotation() {
+        let pages = dictionary! {
+            "MediaBox" => vec![100.into(), 100.into(), 300.into(), 200.into()],
+            "Rotate" => 90,
+        };
+        let doc = one_page(pages, vec![note([100.0, 180.0, 120.0, 200.0])]);
+        let annotations = extract_annotations(&doc, Side::Right);
+
+        // Turned clockwise, the unrotated top-left corner ends up top-right
+        assert_near((annotations[0].x, annotations[0].y), (80.0, 0.0));
+    }
+
+    #[test]
+    fn rotation_maps_corners_clockwise() {
+        let frame = |rotation| PageFrame { media_box: [0.0, 0.0, 200.0, 100.0], rotation };
+        // Bottom-left corner in PDF space
+        assert_near(frame(0).to_display(0.0, 0.0), (0.0, 1.0));
+        assert_near(frame(90).to_display(0.0, 0.0), (0.0, 0.0));
+        assert_near(frame(180).to_display(0.0, 0.0), (1.0, 0.0));
+        assert_near(frame(270).to_display(0.0, 0.0), (1.0, 1.0));
+    }
+
+    #[test]
+    fn to_pdf_inverts_to_display() {
+        for rotation in [0, 90, 180, 270] {
+            let frame = PageFrame { media_box: [50.0, -20.0, 250.0, 80.0], rotation };
+            let (x, y) = frame.to_display(70.0, 60.0);
+            assert_near(frame.to_pdf(x, y), (70.0, 60.0));
+        }
+    }
+
+    #[test]
+    fn skips_popups() {
+        let popup = dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Popup",
+            "Rect" => vec![0.into(), 0.into(), 10.into(), 10.into()],
+        };
+        let pages = dictionary! { "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()] };
+        let doc = one_page(pages, vec![popup, note([10.0, 10.0, 30.0, 30.0])]);
+        let annotations = extract_annotations(&doc, Side::Left);
+
+        assert_eq!(annotations.len(), 1);
+        assert_eq!(annotations[0].subtype, "Text");
+        assert_eq!(annotations[0].id, "imported-left-p1-1");
+    }
+}
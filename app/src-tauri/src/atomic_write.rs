@@ -0,0 +1,247 @@
+//! Crash-safe file writes
+//!
+//! Data goes to a temp file next to the target, is fsynced and then renamed
+//! over the target, so a crash or a full disk never leaves a truncated file
+//! behind. The previous version can be kept as `<name>.bak`.
+
+use serde::Serialize;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum FileErrorKind {
+    PermissionDenied,
+    DiskFull,
+    /// Another process holds the file open or locked (Windows sharing violations)
+    Locked,
+    NotFound,
+    AlreadyExists,
+    /// Outside the files and folders the user opened (see `file_scope`)
+    NotGranted,
+    /// Encrypted and the password was missing or wrong
+    PasswordRequired,
+    /// Encrypted with something other than the Standard security handler
+    UnsupportedEncryption,
+    Other,
+}
+
+/// File error returned to the frontend as `{ kind, path, message }`
+#[derive(Serialize, Debug, Clone)]
+pub struct FileError {
+    pub kind: FileErrorKind,
+    pub path: String,
+    pub message: String,
+}
+
+impl FileError {
+    /// Classify an I/O error; `action` is e.g. "write" or "read"
+    pub fn io(action: &str, path: &Path, err: io::Error) -> Self {
+        FileError {
+            kind: classify(&err),
+            path: path.to_string_lossy().into_owned(),
+            message: format!("Failed to {} file {}: {}", action, path.display(), err),
+        }
+    }
+
+    /// Any other failure tied to a file
+    pub fn other(path: &Path, message: String) -> Self {
+        FileError {
+            kind: FileErrorKind::Other,
+            path: path.to_string_lossy().into_owned(),
+            message,
+        }
+    }
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+fn classify(err: &io::Error) -> FileErrorKind {
+    // Windows: ERROR_SHARING_VIOLATION, ERROR_LOCK_VIOLATION, ERROR_HANDLE_DISK_FULL, ERROR_DISK_FULL
+    #[cfg(windows)]
+    match err.raw_os_error() {
+        Some(32 | 33) => return FileErrorKind::Locked,
+        Some(39 | 112) => return FileErrorKind::DiskFull,
+        _ => {}
+    }
+    match err.kind() {
+        ErrorKind::PermissionDenied | ErrorKind::ReadOnlyFilesystem => FileErrorKind::PermissionDenied,
+        ErrorKind::StorageFull => FileErrorKind::DiskFull,
+        ErrorKind::ResourceBusy | ErrorKind::ExecutableFileBusy => FileErrorKind::Locked,
+        ErrorKind::NotFound => FileErrorKind::NotFound,
+        ErrorKind::AlreadyExists => FileErrorKind::AlreadyExists,
+        _ => FileErrorKind::Other,
+    }
+}
+
+/// Path of the backup kept for `path` (`report.pdf` -> `report.pdf.bak`)
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".bak");
+    path.with_file_name(name)
+}
+
+/// Atomically replace `path` with `data`, keeping the old contents as `.bak` when `backup` is set
+pub fn write_file(path: &Path, data: &[u8], backup: bool) -> Result<(), FileError> {
+    let write_error = |e| FileError::io("write", path, e);
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let existing = fs::metadata(path).ok().filter(|m| m.is_file());
+
+    let temp_path = temp_path(dir, path);
+    let result = (|| {
+        let mut temp = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp_path)?;
+        temp.write_all(data)?;
+        if let Some(metadata) = &existing {
+            // Keep the original file mode (read-only bits etc.)
+            temp.set_permissions(metadata.permissions())?;
+        }
+        temp.sync_all()
+    })();
+    if let Err(e) = result {
+        let _ = fs::remove_file(&temp_path);
+        return Err(write_error(e));
+    }
+
+    if backup && existing.is_some() {
+        let backup = backup_path(path);
+        if let Err(e) = fs::copy(path, &backup) {
+            let _ = fs::remove_file(&temp_path);
+            return Err(FileError::io("back up", path, e));
+        }
+    }
+
+    if let Err(e) = fs::rename(&temp_path, path) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(write_error(e));
+    }
+    sync_dir(dir);
+    Ok(())
+}
+
+/// Hidden, unique name in the target's directory so the rename stays on one filesystem
+fn temp_path(dir: &Path, path: &Path) -> PathBuf {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    dir.join(format!(".{}.{}-{}.tmp", name, std::process::id(), nanos))
+}
+
+/// Persist the rename itself; not possible (or needed) on Windows
+fn sync_dir(dir: &Path) {
+    #[cfg(unix)]
+    if let Ok(dir) = fs::File::open(dir) {
+        let _ = dir.sync_all();
+    }
+    #[cfg(not(unix))]
+    let _ = dir;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty directory for one test
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("twice-pdf-atomic-write-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn file_names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn creates_a_new_file_without_backup() {
+        let dir = test_dir("create");
+        let path = dir.join("out.pdf");
+        write_file(&path, b"new", true).unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        assert_eq!(file_names(&dir), ["out.pdf"]);
+    }
+
+    #[test]
+    fn keeps_the_previous_version_as_bak() {
+        let dir = test_dir("backup");
+        let path = dir.join("out.pdf");
+        fs::write(&path, b"old").unwrap();
+        write_file(&path, b"new", true).unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        assert_eq!(fs::read(backup_path(&path)).unwrap(), b"old");
+        assert_eq!(file_names(&dir), ["out.pdf", "out.pdf.bak"]);
+    }
+
+    #[test]
+    fn replaces_without_backup_when_disabled() {
+        let dir = test_dir("no-backup");
+        let path = dir.join("out.pdf");
+        fs::write(&path, b"old").unwrap();
+        write_file(&path, b"new", false).unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        assert_eq!(file_names(&dir), ["out.pdf"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keeps_the_file_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = test_dir("mode");
+        let path = dir.join("out.pdf");
+        fs::write(&path, b"old").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+        write_file(&path, b"new", false).unwrap();
+
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o640);
+    }
+
+    #[test]
+    fn missing_folders_are_classified() {
+        let dir = test_dir("missing");
+        let path = dir.join("gone").join("out.pdf");
+        let error = write_file(&path, b"new", true).unwrap_err();
+
+        assert_eq!(error.kind, FileErrorKind::NotFound);
+        assert_eq!(error.path, path.to_string_lossy());
+        assert!(file_names(&dir).is_empty());
+    }
+
+    #[test]
+    fn classifies_io_errors() {
+        let kind = |kind| classify(&io::Error::from(kind));
+        assert_eq!(kind(ErrorKind::PermissionDenied), FileErrorKind::PermissionDenied);
+        assert_eq!(kind(ErrorKind::StorageFull), FileErrorKind::DiskFull);
+        assert_eq!(kind(ErrorKind::AlreadyExists), FileErrorKind::AlreadyExists);
+        assert_eq!(kind(ErrorKind::InvalidData), FileErrorKind::Other);
+    }
+
+    #[test]
+    fn backup_names_append_bak() {
+        assert_eq!(backup_path(Path::new("/tmp/report.pdf")), Path::new("/tmp/report.pdf.bak"));
+        assert_eq!(backup_path(Path::new("report")), Path::new("report.bak"));
+    }
+}
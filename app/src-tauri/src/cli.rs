@@ -0,0 +1,555 @@
+use crate::{probe, project, queue};
+use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Command line interface for the desktop binary
+#[derive(Parser, Debug)]
+#[command(
+    name = "twice-pdf",
+    version,
+    about = "Dual PDF viewer",
+    args_conflicts_with_subcommands = true
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// `twice-pdf a.pdf b.pdf` is shorthand for `twice-pdf open a.pdf b.pdf`
+    #[command(flatten)]
+    pub open: OpenArgs,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Open one or two PDFs side by side (default)
+    Open(OpenArgs),
+    /// Compare the text of two PDFs without opening a window
+    ///
+    /// Exits with 0 when the text is identical, 1 when it differs and 2 on errors.
+    Diff(DiffArgs),
+    /// Write comments and bookmarks (JSON) into a copy of a PDF without opening a window
+    Annotate(AnnotateArgs),
+    /// Read annotations that already exist in a PDF
+    Annotations(AnnotationsArgs),
+    /// Review many document pairs one after another, from two folders or a manifest
+    ///
+    /// Progress is saved as you go; running the same command again resumes it.
+    Queue(QueueArgs),
+}
+
+#[derive(Args, Debug, Default, Clone)]
+pub struct OpenArgs {
+    /// PDF files to open (first goes left, second goes right), or a single `.twice` project
+    #[arg(value_name = "FILE", num_args = 0..=2)]
+    pub files: Vec<PathBuf>,
+
+    /// PDF to open in the left viewer
+    #[arg(long, value_name = "FILE")]
+    pub left: Option<PathBuf>,
+
+    /// PDF to open in the right viewer
+    #[arg(long, value_name = "FILE")]
+    pub right: Option<PathBuf>,
+
+    /// Page to open the left document at (1-based)
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub page: Option<u32>,
+
+    /// Page offset of the right document relative to the left one
+    #[arg(long, allow_hyphen_values = true)]
+    pub sync_offset: Option<i32>,
+
+    /// Page layout for both viewers
+    #[arg(long, value_enum)]
+    pub view: Option<ViewMode>,
+
+    /// Initial zoom: `fit` or a percentage between 25 and 400
+    #[arg(long, value_parser = parse_zoom)]
+    pub zoom: Option<Zoom>,
+
+    /// Author name used for new comments
+    #[arg(long)]
+    pub author: Option<String>,
+
+    /// Start a separate instance instead of opening the files in the running one
+    #[arg(long)]
+    pub new_instance: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct DiffArgs {
+    /// Original PDF
+    pub left: PathBuf,
+
+    /// Revised PDF
+    pub right: PathBuf,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = DiffFormat::Unified)]
+    pub format: DiffFormat,
+
+    /// Compare left page N with right page N + offset
+    #[arg(long, allow_hyphen_values = true, default_value_t = 0)]
+    pub sync_offset: i32,
+}
+
+#[derive(Args, Debug, Clone)]
+#[command(group(ArgGroup::new("pairs").args(["left", "manifest"]).required(true)))]
+pub struct QueueArgs {
+    /// Folder with the left documents
+    #[arg(long, value_name = "DIR", requires = "right")]
+    pub left: Option<PathBuf>,
+
+    /// Folder with the right documents
+    #[arg(long, value_name = "DIR", requires = "left")]
+    pub right: Option<PathBuf>,
+
+    /// Pair files by a key taken from their names instead of the file stem:
+    /// the `key` group, the first capture group or the whole match,
+    /// e.g. `^(.+)_(en|de)\.pdf$`
+    #[arg(long, value_name = "REGEX", conflicts_with = "manifest")]
+    pub pattern: Option<String>,
+
+    /// CSV file of `left,right` paths, relative to the manifest's folder
+    #[arg(long, value_name = "FILE")]
+    pub manifest: Option<PathBuf>,
+}
+
+impl QueueArgs {
+    /// Build the list of pairs (saved progress is applied once the app data folder is known)
+    pub fn into_queue(self) -> Result<queue::QueueSource, String> {
+        match (self.manifest, self.left, self.right) {
+            (Some(manifest), _, _) => queue::from_manifest(&manifest),
+            (None, Some(left), Some(right)) => queue::from_folders(&left, &right, self.pattern.as_deref()),
+            _ => Err("either --left and --right or --manifest is required".to_string()),
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffFormat {
+    Unified,
+    Json,
+}
+
+#[derive(Args, Debug, Clone)]
+#[command(group(ArgGroup::new("annotations").args(["comments", "bookmarks"]).required(true).multiple(true)))]
+pub struct AnnotateArgs {
+    /// Source PDF
+    pub input: PathBuf,
+
+    /// Comments JSON, either the viewer's `{ id: comment }` map or an array
+    #[arg(long, value_name = "FILE")]
+    pub comments: Option<PathBuf>,
+
+    /// Bookmarks JSON array of `{ page, label }`
+    #[arg(long, value_name = "FILE")]
+    pub bookmarks: Option<PathBuf>,
+
+    /// Only apply comments made on this side of the viewer
+    #[arg(long, value_enum)]
+    pub side: Option<Side>,
+
+    /// Where to write the annotated PDF
+    #[arg(short, long, value_name = "FILE")]
+    pub output: PathBuf,
+
+    /// `incremental` appends the annotations and keeps the original bytes (and digital signatures) intact
+    #[arg(long, value_enum, default_value_t = SaveMode::Rewrite)]
+    pub save_mode: SaveMode,
+
+    /// Keep the previous contents of an existing output file as `<output>.bak`
+    #[arg(long)]
+    pub backup: bool,
+
+    /// User or owner password of an encrypted input
+    #[arg(long)]
+    pub password: Option<String>,
+
+    /// Encrypt the output with the input's passwords instead of writing it decrypted
+    #[arg(long)]
+    pub keep_encryption: bool,
+}
+
+/// How an annotated document is written
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SaveMode {
+    /// Re-serialise the whole document, like pdf-lib's `save()`
+    #[default]
+    Rewrite,
+    /// Append an incremental update section with only the new and changed objects
+    Incremental,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct AnnotationsArgs {
+    #[command(subcommand)]
+    pub command: AnnotationsCommand,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum AnnotationsCommand {
+    /// Print every annotation with its author, dates, position and highlighted text
+    List(ListAnnotationsArgs),
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ListAnnotationsArgs {
+    /// PDF to read
+    pub input: PathBuf,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = ListFormat::Json)]
+    pub format: ListFormat,
+
+    /// Viewer side recorded in the output, for loading it back as comments
+    #[arg(long, value_enum, default_value_t = Side::Left)]
+    pub side: Side,
+
+    /// Only list these subtypes, e.g. `--subtype Link,FreeText` (default: all but popups)
+    #[arg(long, value_delimiter = ',')]
+    pub subtype: Vec<String>,
+
+    /// Only list notes and the text markup subtypes the viewer imports as comments
+    #[arg(long, conflicts_with = "subtype")]
+    pub comments_only: bool,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListFormat {
+    Json,
+    Csv,
+}
+
+#[derive(ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Left,
+    Right,
+}
+
+#[derive(ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ViewMode {
+    Single,
+    Continuous,
+}
+
+/// Mirrors the frontend's `scaleMode` / `defaultScaleLevel` settings
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(tag = "mode", rename_all = "lowercase")]
+pub enum Zoom {
+    Fit,
+    Level { level: u32 },
+}
+
+fn parse_zoom(value: &str) -> Result<Zoom, String> {
+    if value.eq_ignore_ascii_case("fit") {
+        return Ok(Zoom::Fit);
+    }
+    let level: u32 = value
+        .trim_end_matches('%')
+        .parse()
+        .map_err(|_| format!("expected `fit` or a percentage, got `{}`", value))?;
+    if !(25..=400).contains(&level) {
+        return Err(format!("zoom must be between 25 and 400, got {}", level));
+    }
+    Ok(Zoom::Level { level })
+}
+
+/// Launch options handed to the frontend via `get_launch_options`,
+/// or forwarded to the running instance (see `single_instance`)
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LaunchOptions {
+    pub left: Option<String>,
+    pub right: Option<String>,
+    pub page: Option<u32>,
+    pub sync_offset: Option<i32>,
+    pub view_mode: Option<ViewMode>,
+    pub zoom: Option<Zoom>,
+    pub author: Option<String>,
+    /// `.twice` project to restore instead of single files
+    pub project: Option<String>,
+    /// Started with `queue`: the documents come from `queue_state`
+    pub queue: bool,
+}
+
+impl LaunchOptions {
+    /// Documents and project named on the command line
+    pub fn files(&self) -> impl Iterator<Item = &Path> {
+        [&self.left, &self.right, &self.project]
+            .into_iter()
+            .flatten()
+            .map(Path::new)
+    }
+}
+
+impl OpenArgs {
+    /// Resolve positional and `--left/--right` files into launch options
+    pub fn into_launch_options(self) -> Result<LaunchOptions, String> {
+        if let Some(path) = self.files.first().filter(|p| project::is_project_file(p)) {
+            if self.files.len() > 1 || self.left.is_some() || self.right.is_some() {
+                return Err("a project file cannot be combined with other files".to_string());
+            }
+            if self.page.is_some() || self.sync_offset.is_some() {
+                return Err("--page and --sync-offset are taken from the project file".to_string());
+            }
+            if !path.is_file() {
+                return Err(format!("no such file: {}", path.display()));
+            }
+            return Ok(LaunchOptions {
+                project: Some(absolute(path.clone())),
+                view_mode: self.view,
+                zoom: self.zoom,
+                author: self.author,
+                ..Default::default()
+            });
+        }
+
+        let mut positional = self.files.into_iter();
+        let left = match self.left {
+            Some(path) => Some(path),
+            None => positional.next(),
+        };
+        let right = match self.right {
+            Some(path) => Some(path),
+            None => positional.next(),
+        };
+        if let Some(extra) = positional.next() {
+            return Err(format!(
+                "too many files: `{}` has no free side (use --left/--right)",
+                extra.display()
+            ));
+        }
+
+        Ok(LaunchOptions {
+            left: left.map(resolve_file).transpose()?,
+            right: right.map(resolve_file).transpose()?,
+            page: self.page,
+            sync_offset: self.sync_offset,
+            view_mode: self.view,
+            zoom: self.zoom,
+            author: self.author,
+            project: None,
+            queue: false,
+        })
+    }
+}
+
+/// Make a CLI path absolute so the frontend does not depend on our cwd.
+/// Files are accepted by content, whatever their extension.
+fn resolve_file(path: PathBuf) -> Result<String, String> {
+    if !path.is_file() {
+        return Err(format!("no such file: {}", path.display()));
+    }
+    if !probe::is_pdf_file(&path) {
+        return Err(format!("not a PDF file: {}", path.display()));
+    }
+    Ok(absolute(path))
+}
+
+fn absolute(path: PathBuf) -> String {
+    let absolute = if path.is_absolute() {
+        path
+    } else {
+        std::env::current_dir()
+            .map(|cwd| cwd.join(&path))
+            .unwrap_or(path)
+    };
+    absolute.to_string_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::error::ErrorKind;
+    use clap::CommandFactory;
+    use std::fs;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("twice-pdf-cli-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// A file that passes the PDF header check
+    fn pdf(dir: &Path, name: &str) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, b"%PDF-1.7\n%%EOF\n").unwrap();
+        path
+    }
+
+    fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        Cli::try_parse_from(std::iter::once("twice-pdf").chain(args.iter().copied()))
+    }
+
+    fn open_args(args: &[&str]) -> OpenArgs {
+        match parse(args).unwrap() {
+            Cli { command: Some(Command::Open(open)), .. } => open,
+            Cli { command: None, open } => open,
+            cli => panic!("expected open arguments, got {:?}", cli.command),
+        }
+    }
+
+    #[test]
+    fn command_definition_is_consistent() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn zoom_is_fit_or_a_percentage() {
+        assert_eq!(parse_zoom("fit"), Ok(Zoom::Fit));
+        assert_eq!(parse_zoom("FIT"), Ok(Zoom::Fit));
+        assert_eq!(parse_zoom("150"), Ok(Zoom::Level { level: 150 }));
+        assert_eq!(parse_zoom("25%"), Ok(Zoom::Level { level: 25 }));
+        assert_eq!(parse_zoom("400"), Ok(Zoom::Level { level: 400 }));
+
+        assert!(parse_zoom("24").unwrap_err().contains("between 25 and 400"));
+        assert!(parse_zoom("401").is_err());
+        assert!(parse_zoom("wide").unwrap_err().contains("`wide`"));
+        assert!(parse_zoom("-50").is_err());
+    }
+
+    #[test]
+    fn invalid_options_are_usage_errors() {
+        assert_eq!(parse(&["--zoom", "500", "a.pdf"]).unwrap_err().kind(), ErrorKind::ValueValidation);
+        assert_eq!(parse(&["--page", "0", "a.pdf"]).unwrap_err().kind(), ErrorKind::ValueValidation);
+        assert_eq!(parse(&["--view", "spread"]).unwrap_err().kind(), ErrorKind::InvalidValue);
+        assert_eq!(parse(&["a.pdf", "b.pdf", "c.pdf"]).unwrap_err().kind(), ErrorKind::TooManyValues);
+        assert_eq!(
+            parse(&["annotations", "list", "a.pdf", "--comments-only", "--subtype", "Link"])
+                .unwrap_err()
+                .kind(),
+            ErrorKind::ArgumentConflict
+        );
+        assert_eq!(parse(&["queue"]).unwrap_err().kind(), ErrorKind::MissingRequiredArgument);
+        assert_eq!(parse(&["annotate", "a.pdf", "-o", "b.pdf"]).unwrap_err().kind(), ErrorKind::MissingRequiredArgument);
+    }
+
+    #[test]
+    fn negative_sync_offsets_are_values() {
+        let open = open_args(&["a.pdf", "b.pdf", "--sync-offset", "-2"]);
+        assert_eq!(open.sync_offset, Some(-2));
+        assert_eq!(open.files, [PathBuf::from("a.pdf"), PathBuf::from("b.pdf")]);
+
+        let open = open_args(&["open", "--sync-offset", "-1", "a.pdf"]);
+        assert_eq!(open.sync_offset, Some(-1));
+
+        match parse(&["diff", "a.pdf", "b.pdf", "--sync-offset", "-3", "--format", "json"]).unwrap().command {
+            Some(Command::Diff(diff)) => {
+                assert_eq!(diff.sync_offset, -3);
+                assert_eq!(diff.format, DiffFormat::Json);
+                assert_eq!(diff.left, PathBuf::from("a.pdf"));
+            }
+            command => panic!("expected diff, got {:?}", command),
+        }
+    }
+
+    #[test]
+    fn subcommands_parse_their_arguments() {
+        match parse(&["annotations", "list", "a.pdf", "--subtype", "Link,FreeText", "--format", "csv"])
+            .unwrap()
+            .command
+        {
+            Some(Command::Annotations(AnnotationsArgs { command: AnnotationsCommand::List(list) })) => {
+                assert_eq!(list.subtype, ["Link", "FreeText"]);
+                assert_eq!(list.format, ListFormat::Csv);
+                assert_eq!(list.side, Side::Left);
+                assert!(!list.comments_only);
+            }
+            command => panic!("expected annotations list, got {:?}", command),
+        }
+
+        match parse(&["annotate", "a.pdf", "--comments", "c.json", "-o", "out.pdf", "--save-mode", "incremental"])
+            .unwrap()
+            .command
+        {
+            Some(Command::Annotate(annotate)) => {
+                assert_eq!(annotate.save_mode, SaveMode::Incremental);
+                assert_eq!(annotate.output, PathBuf::from("out.pdf"));
+                assert!(annotate.bookmarks.is_none());
+            }
+            command => panic!("expected annotate, got {:?}", command),
+        }
+
+        match parse(&["queue", "--left", "old", "--right", "new"]).unwrap().command {
+            Some(Command::Queue(queue)) => assert_eq!(queue.left, Some(PathBuf::from("old"))),
+            command => panic!("expected queue, got {:?}", command),
+        }
+        // Options of the default `open` do not mix with subcommands
+        assert!(parse(&["--page", "2", "diff", "a.pdf", "b.pdf"]).is_err());
+    }
+
+    #[test]
+    fn files_fill_the_free_sides() {
+        let dir = test_dir("sides");
+        let a = pdf(&dir, "a.pdf");
+        let b = pdf(&dir, "b.pdf");
+        let (a_arg, b_arg) = (a.to_str().unwrap(), b.to_str().unwrap());
+
+        let options = open_args(&[a_arg, b_arg, "--page", "3", "--zoom", "fit", "--view", "single"])
+            .into_launch_options()
+            .unwrap();
+        assert_eq!(options.left.as_deref(), Some(a_arg));
+        assert_eq!(options.right.as_deref(), Some(b_arg));
+        assert_eq!(options.page, Some(3));
+        assert_eq!(options.zoom, Some(Zoom::Fit));
+        assert_eq!(options.view_mode, Some(ViewMode::Single));
+
+        // A positional file takes whichever side --left/--right left free
+        let options = open_args(&["--right", a_arg, b_arg]).into_launch_options().unwrap();
+        assert_eq!(options.left.as_deref(), Some(b_arg));
+        assert_eq!(options.right.as_deref(), Some(a_arg));
+
+        let error = open_args(&["--left", a_arg, "--right", a_arg, b_arg]).into_launch_options().unwrap_err();
+        assert!(error.starts_with("too many files"), "{}", error);
+
+        let options = open_args(&[]).into_launch_options().unwrap();
+        assert!(options.left.is_none() && options.right.is_none() && options.project.is_none());
+    }
+
+    #[test]
+    fn files_must_exist_and_be_pdfs() {
+        let dir = test_dir("checks");
+        let text = dir.join("notes.pdf");
+        fs::write(&text, "not a pdf").unwrap();
+        // Content decides, not the extension
+        let renamed = pdf(&dir, "scan.bin");
+
+        let error = open_args(&[text.to_str().unwrap()]).into_launch_options().unwrap_err();
+        assert!(error.starts_with("not a PDF file"), "{}", error);
+
+        let missing = dir.join("missing.pdf");
+        let error = open_args(&[missing.to_str().unwrap()]).into_launch_options().unwrap_err();
+        assert!(error.starts_with("no such file"), "{}", error);
+
+        let options = open_args(&[renamed.to_str().unwrap()]).into_launch_options().unwrap();
+        assert_eq!(options.left.as_deref(), renamed.to_str());
+    }
+
+    #[test]
+    fn project_files_stand_alone() {
+        let dir = test_dir("project");
+        let project = dir.join("review.twice");
+        fs::write(&project, "{}").unwrap();
+        let a = pdf(&dir, "a.pdf");
+        let project_arg = project.to_str().unwrap();
+
+        let options = open_args(&[project_arg, "--zoom", "120", "--author", "Sam"])
+            .into_launch_options()
+            .unwrap();
+        assert_eq!(options.project.as_deref(), Some(project_arg));
+        assert_eq!(options.zoom, Some(Zoom::Level { level: 120 }));
+        assert_eq!(options.author.as_deref(), Some("Sam"));
+        assert!(options.left.is_none());
+
+        assert!(open_args(&[project_arg, a.to_str().unwrap()]).into_launch_options().is_err());
+        assert!(open_args(&[project_arg, "--right", a.to_str().unwrap()]).into_launch_options().is_err());
+        assert!(open_args(&[project_arg, "--sync-offset", "-1"]).into_launch_options().is_err());
+        assert!(open_args(&[dir.join("gone.twice").to_str().unwrap()]).into_launch_options().is_err());
+    }
+}
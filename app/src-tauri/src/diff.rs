@@ -0,0 +1,406 @@
+use crate::cli::{DiffArgs, DiffFormat};
+use lopdf::Document;
+use serde::Serialize;
+use similar::{Algorithm, ChangeTag, DiffOp, TextDiff};
+use std::io::Write;
+use std::path::Path;
+
+// Exit codes follow diff(1): 0 = identical, 1 = different, 2 = trouble
+pub const EXIT_SAME: i32 = 0;
+pub const EXIT_DIFFERENT: i32 = 1;
+pub const EXIT_ERROR: i32 = 2;
+
+/// Words of unchanged context printed around each change in unified output
+const CONTEXT_WORDS: usize = 5;
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffReport {
+    pub left: String,
+    pub right: String,
+    pub identical: bool,
+    /// Only pages with at least one change are listed
+    pub pages: Vec<PageDiff>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PageDiff {
+    /// 1-based page numbers; `None` when the page only exists on one side
+    pub left_page: Option<u32>,
+    pub right_page: Option<u32>,
+    pub changes: Vec<WordChange>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "op", rename_all = "lowercase", rename_all_fields = "camelCase")]
+pub enum WordChange {
+    Delete { left_index: usize, old: String },
+    Insert { right_index: usize, new: String },
+    Replace { left_index: usize, right_index: usize, old: String, new: String },
+}
+
+/// Entry point for `twice-pdf diff`, returns the process exit code
+pub fn run(args: &DiffArgs) -> i32 {
+    let (left_pages, right_pages) = match (extract_page_texts(&args.left), extract_page_texts(&args.right)) {
+        (Ok(left), Ok(right)) => (left, right),
+        (Err(e), _) | (_, Err(e)) => {
+            eprintln!("twice-pdf diff: {}", e);
+            return EXIT_ERROR;
+        }
+    };
+
+    let pages = compare_pages(&left_pages, &right_pages, args.sync_offset);
+    let report = DiffReport {
+        left: args.left.to_string_lossy().into_owned(),
+        right: args.right.to_string_lossy().into_owned(),
+        identical: pages.is_empty(),
+        pages,
+    };
+
+    let output = match args.format {
+        DiffFormat::Unified => render_unified(&report, &left_pages, &right_pages),
+        DiffFormat::Json => match serde_json::to_string_pretty(&report) {
+            Ok(json) => json + "\n",
+            Err(e) => {
+                eprintln!("twice-pdf diff: failed to serialize report: {}", e);
+                return EXIT_ERROR;
+            }
+        },
+    };
+    // A closed pipe (`twice-pdf diff a.pdf b.pdf | head`) is not an error
+    let _ = std::io::stdout().write_all(output.as_bytes());
+
+    if report.identical {
+        EXIT_SAME
+    } else {
+        EXIT_DIFFERENT
+    }
+}
+
+/// Extract the text of every page, in page order
+pub fn extract_page_texts(path: &Path) -> Result<Vec<String>, String> {
+    let doc = Document::load(path).map_err(|e| format!("Failed to read PDF {}: {}", path.display(), e))?;
+    doc.get_pages()
+        .keys()
+        // An undecodable page fails the diff; treating it as empty could report broken files as identical
+        .map(|&number| {
+            doc.extract_text(&[number])
+                .map_err(|e| format!("Failed to extract the text of page {} of {}: {}", number, path.display(), e))
+        })
+        .collect()
+}
+
+/// Word-level diff of two documents, pairing left page `n` with right page `n + sync_offset`
+pub fn compare_pages(left: &[String], right: &[String], sync_offset: i32) -> Vec<PageDiff> {
+    let offset = i64::from(sync_offset);
+    // Walk the pages that exist on either side, in left-page coordinates. The two ranges
+    // need not overlap, so a huge offset costs no more than the pages themselves.
+    let left_range = 0..left.len() as i64;
+    let right_only = (-offset..right.len() as i64 - offset).filter(|index| !left_range.contains(index));
+    let mut indices: Vec<i64> = left_range.clone().chain(right_only).collect();
+    indices.sort_unstable();
+
+    let mut pages = Vec::new();
+    for left_index in indices {
+        let right_index = left_index + offset;
+        let left_text = page_at(left, left_index);
+        let right_text = page_at(right, right_index);
+
+        let old_words: Vec<&str> = left_text.unwrap_or_default().split_whitespace().collect();
+        let new_words: Vec<&str> = right_text.unwrap_or_default().split_whitespace().collect();
+        let changes = diff_words(&old_words, &new_words);
+        if changes.is_empty() {
+            continue;
+        }
+
+        pages.push(PageDiff {
+            left_page: left_text.map(|_| left_index as u32 + 1),
+            right_page: right_text.map(|_| right_index as u32 + 1),
+            changes,
+        });
+    }
+    pages
+}
+
+fn page_at(pages: &[String], index: i64) -> Option<&str> {
+    usize::try_from(index).ok().and_then(|i| pages.get(i)).map(String::as_str)
+}
+
+fn diff_words(old: &[&str], new: &[&str]) -> Vec<WordChange> {
+    let ops = similar::capture_diff_slices(Algorithm::Myers, old, new);
+    ops.iter()
+        .filter_map(|op| match *op {
+            DiffOp::Equal { .. } => None,
+            DiffOp::Delete { old_index, old_len, .. } => Some(WordChange::Delete {
+                left_index: old_index,
+                old: old[old_index..old_index + old_len].join(" "),
+            }),
+            DiffOp::Insert { new_index, new_len, .. } => Some(WordChange::Insert {
+                right_index: new_index,
+                new: new[new_index..new_index + new_len].join(" "),
+            }),
+            DiffOp::Replace { old_index, old_len, new_index, new_len } => Some(WordChange::Replace {
+                left_index: old_index,
+                right_index: new_index,
+                old: old[old_index..old_index + old_len].join(" "),
+                new: new[new_index..new_index + new_len].join(" "),
+            }),
+        })
+        .collect()
+}
+
+/// Render the report like `diff -u`, with one `@@` hunk per group of nearby word changes
+pub fn render_unified(report: &DiffReport, left_pages: &[String], right_pages: &[String]) -> String {
+    if report.identical {
+        return String::new();
+    }
+
+    let mut out = format!("--- {}\n+++ {}\n", report.left, report.right);
+    for page in &report.pages {
+        let old_words: Vec<&str> = page
+            .left_page
+            .and_then(|n| left_pages.get(n as usize - 1))
+            .map(|t| t.split_whitespace().collect())
+            .unwrap_or_default();
+        let new_words: Vec<&str> = page
+            .right_page
+            .and_then(|n| right_pages.get(n as usize - 1))
+            .map(|t| t.split_whitespace().collect())
+            .unwrap_or_default();
+
+        let diff = TextDiff::configure()
+            .algorithm(Algorithm::Myers)
+            .diff_slices(&old_words, &new_words);
+
+        for group in diff.grouped_ops(CONTEXT_WORDS) {
+            out.push_str(&format!(
+                "@@ page {} / page {} @@\n",
+                page_label(page.left_page),
+                page_label(page.right_page)
+            ));
+            for op in &group {
+                for tag in [ChangeTag::Equal, ChangeTag::Delete, ChangeTag::Insert] {
+                    let words: Vec<&str> = diff
+                        .iter_changes(op)
+                        .filter(|change| change.tag() == tag)
+                        .map(|change| change.value())
+                        .collect();
+                    if words.is_empty() {
+                        continue;
+                    }
+                    let marker = match tag {
+                        ChangeTag::Equal => ' ',
+                        ChangeTag::Delete => '-',
+                        ChangeTag::Insert => '+',
+                    };
+                    out.push_str(&format!("{}{}\n", marker, words.join(" ")));
+                }
+            }
+        }
+    }
+    out
+}
+
+fn page_label(page: Option<u32>) -> String {
+    page.map(|n| n.to_string()).unwrap_or_else(|| "-".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{dictionary, Object, Stream};
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn pages(texts: &[&str]) -> Vec<String> {
+        texts.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn identical_pages_have_no_diff() {
+        let left = pages(&["one two", "three"]);
+        assert!(compare_pages(&left, &left, 0).is_empty());
+    }
+
+    #[test]
+    fn lists_only_changed_pages() {
+        let left = pages(&["same page", "the quick fox"]);
+        let right = pages(&["same page", "the slow fox jumps"]);
+        let diff = compare_pages(&left, &right, 0);
+
+        assert_eq!(diff.len(), 1);
+        assert_eq!((diff[0].left_page, diff[0].right_page), (Some(2), Some(2)));
+        assert!(matches!(
+            diff[0].changes.as_slice(),
+            [
+                WordChange::Replace { left_index: 1, right_index: 1, old, new },
+                WordChange::Insert { right_index: 3, new: jumps },
+            ] if old == "quick" && new == "slow" && jumps == "jumps"
+        ));
+    }
+
+    #[test]
+    fn sync_offset_pairs_shifted_pages() {
+        // The right document has an extra cover page
+        let left = pages(&["intro", "body"]);
+        let right = pages(&["cover", "intro", "body"]);
+        let diff = compare_pages(&left, &right, 1);
+
+        assert_eq!(diff.len(), 1);
+        assert_eq!((diff[0].left_page, diff[0].right_page), (None, Some(1)));
+        assert!(matches!(diff[0].changes.as_slice(), [WordChange::Insert { new, .. }] if new == "cover"));
+    }
+
+    #[test]
+    fn pages_past_the_other_side_are_one_sided() {
+        let left = pages(&["a", "b", "c"]);
+        let right = pages(&["a"]);
+        let diff = compare_pages(&left, &right, 0);
+
+        let numbers: Vec<_> = diff.iter().map(|p| (p.left_page, p.right_page)).collect();
+        assert_eq!(numbers, [(Some(2), None), (Some(3), None)]);
+    }
+
+    #[test]
+    fn huge_offsets_only_visit_existing_pages() {
+        let left = pages(&["left"]);
+        let right = pages(&["right"]);
+
+        let diff = compare_pages(&left, &right, i32::MAX);
+        let numbers: Vec<_> = diff.iter().map(|p| (p.left_page, p.right_page)).collect();
+        assert_eq!(numbers, [(None, Some(1)), (Some(1), None)]);
+
+        let diff = compare_pages(&left, &right, i32::MIN);
+        let numbers: Vec<_> = diff.iter().map(|p| (p.left_page, p.right_page)).collect();
+        assert_eq!(numbers, [(Some(1), None), (None, Some(1))]);
+    }
+
+    /// A saved document with one page per entry, each showing its text in Helvetica
+    fn text_pdf(dir: &Path, name: &str, texts: &[&str]) -> PathBuf {
+        let mut doc = Document::with_version("1.7");
+        let pages_id = doc.new_object_id();
+        let font_id = doc.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Helvetica",
+            "Encoding" => "WinAnsiEncoding",
+        });
+        let resources_id = doc.add_object(dictionary! { "Font" => dictionary! { "F1" => font_id } });
+        let kids: Vec<Object> = texts
+            .iter()
+            .map(|text| {
+                let content = format!("BT /F1 12 Tf 72 720 Td ({}) Tj ET", text);
+                let content_id = doc.add_object(Stream::new(dictionary! {}, content.into_bytes()));
+                doc.add_object(dictionary! {
+                    "Type" => "Page",
+                    "Parent" => pages_id,
+                    "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+                    "Resources" => resources_id,
+                    "Contents" => content_id,
+                })
+                .into()
+            })
+            .collect();
+        let pages = dictionary! { "Type" => "Pages", "Kids" => kids, "Count" => texts.len() as i64 };
+        doc.objects.insert(pages_id, Object::Dictionary(pages));
+        let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        doc.trailer.set("Root", catalog_id);
+
+        let path = dir.join(name);
+        doc.save(&path).unwrap();
+        path
+    }
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("twice-pdf-diff-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn diff_args(left: &Path, right: &Path, sync_offset: i32) -> DiffArgs {
+        DiffArgs { left: left.to_path_buf(), right: right.to_path_buf(), format: DiffFormat::Json, sync_offset }
+    }
+
+    #[test]
+    fn extracts_the_text_of_every_page() {
+        let dir = test_dir("extract");
+        let path = text_pdf(&dir, "a.pdf", &["first page", "second page"]);
+        let texts = extract_page_texts(&path).unwrap();
+        let words: Vec<Vec<&str>> = texts.iter().map(|t| t.split_whitespace().collect()).collect();
+        assert_eq!(words, [["first", "page"], ["second", "page"]]);
+    }
+
+    #[test]
+    fn exit_codes_follow_diff() {
+        let dir = test_dir("exit");
+        let left = text_pdf(&dir, "left.pdf", &["alpha beta", "gamma"]);
+        let same = text_pdf(&dir, "same.pdf", &["alpha beta", "gamma"]);
+        let changed = text_pdf(&dir, "changed.pdf", &["alpha beta", "delta"]);
+        let shifted = text_pdf(&dir, "shifted.pdf", &["cover", "alpha beta", "gamma"]);
+
+        assert_eq!(run(&diff_args(&left, &same, 0)), EXIT_SAME);
+        assert_eq!(run(&diff_args(&left, &changed, 0)), EXIT_DIFFERENT);
+        assert_eq!(run(&diff_args(&left, &shifted, 0)), EXIT_DIFFERENT);
+        assert_eq!(run(&diff_args(&left, &dir.join("missing.pdf"), 0)), EXIT_ERROR);
+    }
+
+    #[test]
+    fn unified_output_has_one_hunk_per_changed_page() {
+        let dir = test_dir("unified");
+        let left = text_pdf(&dir, "left.pdf", &["unchanged words here", "the quick brown fox"]);
+        let right = text_pdf(&dir, "right.pdf", &["unchanged words here", "the slow brown fox jumps"]);
+        let (left_pages, right_pages) = (extract_page_texts(&left).unwrap(), extract_page_texts(&right).unwrap());
+        let report = DiffReport {
+            left: "left.pdf".into(),
+            right: "right.pdf".into(),
+            identical: false,
+            pages: compare_pages(&left_pages, &right_pages, 0),
+        };
+
+        assert_eq!(
+            render_unified(&report, &left_pages, &right_pages),
+            "--- left.pdf\n+++ right.pdf\n@@ page 2 / page 2 @@\n the\n-quick\n+slow\n brown fox\n+jumps\n"
+        );
+    }
+
+    #[test]
+    fn unified_output_labels_one_sided_pages() {
+        let dir = test_dir("one-sided");
+        let left = text_pdf(&dir, "left.pdf", &["body"]);
+        let right = text_pdf(&dir, "right.pdf", &["cover", "body"]);
+        let (left_pages, right_pages) = (extract_page_texts(&left).unwrap(), extract_page_texts(&right).unwrap());
+        let pages = compare_pages(&left_pages, &right_pages, 1);
+        let report = DiffReport { left: "l".into(), right: "r".into(), identical: pages.is_empty(), pages };
+
+        assert_eq!(render_unified(&report, &left_pages, &right_pages), "--- l\n+++ r\n@@ page - / page 1 @@\n+cover\n");
+        let identical = DiffReport { left: "l".into(), right: "r".into(), identical: true, pages: Vec::new() };
+        assert_eq!(render_unified(&identical, &left_pages, &left_pages), "");
+    }
+
+    #[test]
+    fn undecodable_pages_are_an_error() {
+        let dir = test_dir("undecodable");
+        let good = text_pdf(&dir, "good.pdf", &["text"]);
+        let broken = text_pdf(&dir, "broken.pdf", &["text"]);
+        // A composite font without a /ToUnicode map cannot be turned back into text
+        let mut doc = Document::load(&broken).unwrap();
+        let font_id = doc
+            .objects
+            .iter()
+            .find(|(_, object)| object.as_dict().is_ok_and(|d| d.has_type(b"Font")))
+            .map(|(&id, _)| id)
+            .unwrap();
+        doc.objects.insert(
+            font_id,
+            Object::Dictionary(dictionary! { "Type" => "Font", "Subtype" => "Type0", "Encoding" => "Identity-H" }),
+        );
+        doc.save(&broken).unwrap();
+
+        let error = extract_page_texts(&broken).unwrap_err();
+        assert!(error.contains("page 1"), "{}", error);
+        assert_eq!(run(&diff_args(&good, &broken, 0)), EXIT_ERROR);
+        assert_eq!(run(&diff_args(&broken, &broken, 0)), EXIT_ERROR);
+    }
+}
@@ -0,0 +1,168 @@
+//! Output paths for "save to source" exports
+//!
+//! The export lands next to the source file as `{prefix}{name}{suffix}.pdf`.
+//! The prefix and suffix may contain `{date}`, `{author}` and `{side}`
+//! placeholders, and `{name}` to put the source name somewhere else.
+
+use crate::atomic_write::{FileError, FileErrorKind};
+use crate::cli::Side;
+use chrono::Local;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// Gives up numbering after this many taken names
+const MAX_NUMBERED: u32 = 9999;
+
+/// What to do when the export file already exists
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CollisionPolicy {
+    #[default]
+    Overwrite,
+    /// `report_commented (2).pdf`, `report_commented (3).pdf`, ...
+    Number,
+    Fail,
+}
+
+/// Values substituted into the prefix/suffix patterns
+#[derive(Debug, Default, Clone)]
+pub struct PatternValues {
+    pub author: Option<String>,
+    pub side: Option<Side>,
+}
+
+/// Final output path for an export of `source`
+pub fn resolve(
+    source: &Path,
+    prefix: &str,
+    suffix: &str,
+    values: &PatternValues,
+    policy: CollisionPolicy,
+) -> Result<PathBuf, FileError> {
+    let stem = source
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .filter(|s| !s.is_empty())
+        .ok_or_else(|| FileError::other(source, format!("Not a file path: {}", source.display())))?;
+    let dir = source.parent().unwrap_or(Path::new(""));
+
+    // `{name}` may be placed explicitly, otherwise it goes between prefix and suffix
+    let template = if prefix.contains("{name}") || suffix.contains("{name}") {
+        format!("{}{}", prefix, suffix)
+    } else {
+        format!("{}{{name}}{}", prefix, suffix)
+    };
+    let base = expand(&template, &stem, values);
+    let candidate = dir.join(format!("{}.pdf", base));
+
+    if !candidate.exists() {
+        return Ok(candidate);
+    }
+    match policy {
+        CollisionPolicy::Overwrite => Ok(candidate),
+        CollisionPolicy::Fail => Err(FileError {
+            kind: FileErrorKind::AlreadyExists,
+            path: candidate.to_string_lossy().into_owned(),
+            message: format!("File already exists: {}", candidate.display()),
+        }),
+        CollisionPolicy::Number => (2..=MAX_NUMBERED)
+            .map(|n| dir.join(format!("{} ({}).pdf", base, n)))
+            .find(|path| !path.exists())
+            .ok_or_else(|| FileError::other(&candidate, format!("No free file name for {}", candidate.display()))),
+    }
+}
+
+fn expand(pattern: &str, stem: &str, values: &PatternValues) -> String {
+    let side = match values.side {
+        Some(Side::Left) => "left",
+        Some(Side::Right) => "right",
+        None => "",
+    };
+    let expanded = pattern
+        .replace("{name}", stem)
+        .replace("{date}", &Local::now().format("%Y-%m-%d").to_string())
+        .replace("{author}", values.author.as_deref().unwrap_or(""))
+        .replace("{side}", side);
+    sanitize(&expanded)
+}
+
+/// Keep user-supplied parts (author names etc.) from adding directories or invalid characters
+fn sanitize(part: &str) -> String {
+    part.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// An empty directory for one test
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("twice-pdf-export-path-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn values(author: &str, side: Option<Side>) -> PatternValues {
+        PatternValues { author: Some(author.to_string()), side }
+    }
+
+    #[test]
+    fn name_goes_between_prefix_and_suffix() {
+        let dir = test_dir("plain");
+        let source = dir.join("report.pdf");
+        let path = resolve(&source, "", "_{author}_{side}", &values("Ann", Some(Side::Left)), Default::default());
+        assert_eq!(path.unwrap(), dir.join("report_Ann_left.pdf"));
+    }
+
+    #[test]
+    fn name_can_be_placed_explicitly() {
+        let dir = test_dir("explicit");
+        let source = dir.join("report.pdf");
+        let path = resolve(&source, "{side}-{name}-v2", "", &values("", Some(Side::Right)), Default::default());
+        assert_eq!(path.unwrap(), dir.join("right-report-v2.pdf"));
+    }
+
+    #[test]
+    fn dates_are_expanded() {
+        let dir = test_dir("date");
+        let source = dir.join("report.pdf");
+        let path = resolve(&source, "{date}_", "", &PatternValues::default(), Default::default()).unwrap();
+        let expected = format!("{}_report.pdf", Local::now().format("%Y-%m-%d"));
+        assert_eq!(path, dir.join(expected));
+    }
+
+    #[test]
+    fn placeholders_cannot_add_folders() {
+        let dir = test_dir("sanitize");
+        let source = dir.join("report.pdf");
+        let path = resolve(&source, "", "_{author}", &values("../a/b:c", None), Default::default()).unwrap();
+        assert_eq!(path, dir.join("report_.._a_b_c.pdf"));
+        assert_eq!(path.parent(), Some(dir.as_path()));
+    }
+
+    #[test]
+    fn collisions_follow_the_policy() {
+        let dir = test_dir("collision");
+        let source = dir.join("report.pdf");
+        fs::write(dir.join("report_c.pdf"), b"").unwrap();
+        fs::write(dir.join("report_c (2).pdf"), b"").unwrap();
+        let resolve = |policy| resolve(&source, "", "_c", &PatternValues::default(), policy);
+
+        assert_eq!(resolve(CollisionPolicy::Overwrite).unwrap(), dir.join("report_c.pdf"));
+        assert_eq!(resolve(CollisionPolicy::Number).unwrap(), dir.join("report_c (3).pdf"));
+        assert_eq!(resolve(CollisionPolicy::Fail).unwrap_err().kind, FileErrorKind::AlreadyExists);
+    }
+
+    #[test]
+    fn paths_without_a_name_are_rejected() {
+        assert!(resolve(Path::new("/"), "", "_c", &PatternValues::default(), Default::default()).is_err());
+    }
+}
@@ -0,0 +1,269 @@
+//! Crash-safe journal of in-progress review changes
+//!
+//! Every comment, draft and bookmark change is appended to
+//! `<app data>/journal.jsonl` as one JSON line and fsynced before the command
+//! returns, so a crashed webview loses at most the keystrokes of the last
+//! draft interval. Entries stay until their document is exported (or the user
+//! declines to recover them on the next start). A line torn by a crash is
+//! skipped when reading.
+
+use crate::atomic_write::{self, FileError};
+use crate::cli::Side;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum JournalOp {
+    AddComment,
+    SaveComment,
+    DeleteComment,
+    /// Text of the comment being edited (`data` is null once it was saved or cancelled)
+    Draft,
+    /// Complete bookmark list after an edit
+    SetBookmarks,
+}
+
+/// A change as the frontend reports it
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct JournalInput {
+    pub op: JournalOp,
+    #[serde(default)]
+    pub side: Option<Side>,
+    /// Local path or URL of the document
+    #[serde(default)]
+    pub source: Option<String>,
+    /// pdf.js fingerprint, used for documents without a local file
+    #[serde(default)]
+    pub fingerprint: Option<String>,
+    #[serde(default)]
+    pub data: Value,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct JournalEntry {
+    /// Milliseconds since the Unix epoch
+    pub timestamp: u64,
+    /// Document fingerprint (see `annotation_store::document_key`)
+    pub fingerprint: String,
+    #[serde(default)]
+    pub source: Option<String>,
+    #[serde(default)]
+    pub side: Option<Side>,
+    pub op: JournalOp,
+    #[serde(default)]
+    pub data: Value,
+}
+
+impl JournalEntry {
+    pub fn new(input: JournalInput, fingerprint: String) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        JournalEntry {
+            timestamp,
+            fingerprint,
+            source: input.source,
+            side: input.side,
+            op: input.op,
+            data: input.data,
+        }
+    }
+}
+
+/// The append-only journal file (managed Tauri state)
+pub struct Journal {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl Journal {
+    pub fn new(path: PathBuf) -> Self {
+        Journal { path, lock: Mutex::new(()) }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ()> {
+        self.lock.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Append one entry and flush it to disk
+    pub fn append(&self, entry: &JournalEntry) -> Result<(), FileError> {
+        let mut line = serde_json::to_vec(entry)
+            .map_err(|e| FileError::other(&self.path, format!("Failed to serialise journal entry: {}", e)))?;
+        line.push(b'\n');
+
+        let _guard = self.lock();
+        let write_error = |e| FileError::io("write", &self.path, e);
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(write_error)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(write_error)?;
+        // A crash may have left a torn last line; start on a fresh one
+        let mut last = [0u8];
+        if file.seek(SeekFrom::End(-1)).is_ok() && file.read_exact(&mut last).is_ok() && last[0] != b'\n' {
+            line.insert(0, b'\n');
+        }
+        file.write_all(&line).map_err(write_error)?;
+        file.sync_data().map_err(write_error)
+    }
+
+    /// All readable entries, oldest first
+    pub fn entries(&self) -> Result<Vec<JournalEntry>, FileError> {
+        let _guard = self.lock();
+        self.read()
+    }
+
+    /// Drop the entries of the document with `fingerprint`, or all of them with `None`
+    pub fn compact(&self, fingerprint: Option<&str>) -> Result<(), FileError> {
+        let _guard = self.lock();
+        let remaining: Vec<_> = match fingerprint {
+            Some(fingerprint) => self.read()?.into_iter().filter(|e| e.fingerprint != fingerprint).collect(),
+            None => Vec::new(),
+        };
+        if remaining.is_empty() {
+            return match fs::remove_file(&self.path) {
+                Err(e) if e.kind() != ErrorKind::NotFound => Err(FileError::io("remove", &self.path, e)),
+                _ => Ok(()),
+            };
+        }
+
+        let mut data = Vec::new();
+        for entry in &remaining {
+            serde_json::to_writer(&mut data, entry)
+                .map_err(|e| FileError::other(&self.path, format!("Failed to serialise journal entry: {}", e)))?;
+            data.push(b'\n');
+        }
+        atomic_write::write_file(&self.path, &data, false)
+    }
+
+    fn read(&self) -> Result<Vec<JournalEntry>, FileError> {
+        let data = match fs::read(&self.path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(FileError::io("read", &self.path, e)),
+        };
+        Ok(data
+            .split(|&b| b == b'\n')
+            .filter_map(|line| serde_json::from_slice(line).ok())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::path::Path;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("twice-pdf-journal-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn entry(fingerprint: &str, op: JournalOp, data: Value) -> JournalEntry {
+        let input = JournalInput {
+            op,
+            side: Some(Side::Left),
+            source: Some(format!("/docs/{}.pdf", fingerprint)),
+            fingerprint: None,
+            data,
+        };
+        JournalEntry::new(input, fingerprint.to_string())
+    }
+
+    fn ops(journal: &Journal) -> Vec<(String, JournalOp)> {
+        journal.entries().unwrap().into_iter().map(|e| (e.fingerprint, e.op)).collect()
+    }
+
+    fn lines(path: &Path) -> Vec<String> {
+        fs::read_to_string(path).unwrap().lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn appends_one_line_per_entry() {
+        let dir = test_dir("append");
+        let journal = Journal::new(dir.join("nested").join("journal.jsonl"));
+        assert!(journal.entries().unwrap().is_empty());
+
+        journal.append(&entry("a", JournalOp::AddComment, json!({ "id": "c1" }))).unwrap();
+        journal.append(&entry("a", JournalOp::Draft, Value::Null)).unwrap();
+        assert_eq!(lines(&dir.join("nested").join("journal.jsonl")).len(), 2);
+
+        let entries = journal.entries().unwrap();
+        assert_eq!(entries[0].data, json!({ "id": "c1" }));
+        assert_eq!(entries[0].side, Some(Side::Left));
+        assert_eq!(entries[1].op, JournalOp::Draft);
+        assert!(entries[0].timestamp > 0);
+    }
+
+    #[test]
+    fn starts_a_fresh_line_after_a_torn_one() {
+        let dir = test_dir("torn");
+        let path = dir.join("journal.jsonl");
+        let journal = Journal::new(path.clone());
+        journal.append(&entry("a", JournalOp::AddComment, json!({}))).unwrap();
+        // A crash in the middle of the next write
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(br#"{"timestamp":1,"fingerpr"#).unwrap();
+        drop(file);
+
+        journal.append(&entry("b", JournalOp::SaveComment, json!({}))).unwrap();
+        let lines = lines(&path);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1], r#"{"timestamp":1,"fingerpr"#);
+        assert_eq!(ops(&journal), [("a".into(), JournalOp::AddComment), ("b".into(), JournalOp::SaveComment)]);
+    }
+
+    #[test]
+    fn skips_malformed_lines() {
+        let dir = test_dir("malformed");
+        let path = dir.join("journal.jsonl");
+        let good = serde_json::to_string(&entry("a", JournalOp::SetBookmarks, json!([]))).unwrap();
+        let unknown_op = good.replace("setBookmarks", "rewriteHistory");
+        fs::write(&path, format!("not json\n{}\n\n{{}}\n{}\n[1,2]", good, unknown_op)).unwrap();
+
+        let journal = Journal::new(path);
+        assert_eq!(ops(&journal), [("a".into(), JournalOp::SetBookmarks)]);
+    }
+
+    #[test]
+    fn compacts_one_document_or_all() {
+        let dir = test_dir("compact");
+        let path = dir.join("journal.jsonl");
+        let journal = Journal::new(path.clone());
+        for fingerprint in ["a", "b", "a"] {
+            journal.append(&entry(fingerprint, JournalOp::SaveComment, json!({}))).unwrap();
+        }
+        fs::write(&path, fs::read_to_string(&path).unwrap() + "torn").unwrap();
+
+        journal.compact(Some("a")).unwrap();
+        assert_eq!(ops(&journal), [("b".into(), JournalOp::SaveComment)]);
+        // The torn line is gone too
+        assert_eq!(lines(&path).len(), 1);
+
+        journal.compact(Some("missing")).unwrap();
+        assert_eq!(ops(&journal).len(), 1);
+        journal.compact(Some("b")).unwrap();
+        assert!(!path.exists());
+
+        journal.append(&entry("c", JournalOp::Draft, json!("typing"))).unwrap();
+        journal.compact(None).unwrap();
+        assert!(!path.exists());
+        journal.compact(None).unwrap();
+    }
+}
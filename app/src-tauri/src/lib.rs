@@ -1,70 +1,940 @@
+mod annotate;
+mod annotation_store;
+mod annotations;
+mod atomic_write;
+mod cli;
+mod diff;
+mod export_path;
+mod file_scope;
+mod journal;
+mod menu;
+mod page_text;
+mod pdf_file;
+mod probe;
+mod project;
+mod queue;
+mod recent;
+mod remote;
+mod reveal;
+mod settings;
+mod single_instance;
+mod unlock;
+mod watcher;
+mod windows;
+
+use annotation_store::{AnnotationStore, BookmarkSet, StoredBookmark, StoredComment};
+use atomic_write::{FileError, FileErrorKind};
+use clap::{CommandFactory, Parser};
+use cli::{Cli, Command, LaunchOptions, OpenArgs, SaveMode, Side};
+use export_path::{CollisionPolicy, PatternValues};
+use file_scope::FileScope;
+use journal::{Journal, JournalEntry, JournalInput};
+use menu::{MenuAction, RecentMenuItem};
+use pdf_file::{FileInfo, OpenFiles};
+use percent_encoding::percent_decode_str;
+use probe::ProbeResult;
+use project::Project;
+use queue::{PairStatus, Queue, ReviewQueue};
+use recent::{Recent, RecentDocument, RecentEntry, RecentInput, RecentList};
+use remote::{FetchError, Fetcher};
+use reveal::RevealError;
+use settings::Settings;
+use single_instance::Instance;
+use std::collections::HashSet;
 use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
 use std::sync::OnceLock;
+use tauri::ipc::{Channel, InvokeBody, InvokeResponseBody, Request, Response};
+use tauri::{AppHandle, DragDropEvent, Emitter, Manager, State, WebviewEvent, WebviewWindow, WindowEvent};
+use tauri_plugin_dialog::DialogExt;
+use unlock::UnlockedFiles;
+use watcher::Watchers;
+use windows::{ComparisonWindows, WindowDocuments, WindowInfo};
 
-// Store CLI args at startup (before Tauri takes over the event loop)
-static CLI_PDF_PATHS: OnceLock<Vec<String>> = OnceLock::new();
+// Store CLI options at startup (before Tauri takes over the event loop)
+static LAUNCH_OPTIONS: OnceLock<LaunchOptions> = OnceLock::new();
 
-/// Get PDF paths passed via CLI arguments (called by frontend on mount)
+/// Get the files and view options passed on the command line (called by frontend on mount).
+/// Comparison windows get the documents they were opened with instead.
 #[tauri::command]
-fn get_cli_pdf_paths() -> Vec<String> {
-    CLI_PDF_PATHS.get().cloned().unwrap_or_default()
+fn get_launch_options(window: WebviewWindow, windows: State<'_, ComparisonWindows>) -> LaunchOptions {
+    if window.label() == windows::MAIN {
+        LAUNCH_OPTIONS.get().cloned().unwrap_or_default()
+    } else {
+        windows.launch_options(window.label())
+    }
 }
 
+/// Chunk size for streamed reads
+const READ_CHUNK_SIZE: usize = 16 * 1024 * 1024;
+
 /// Read a PDF file from the local filesystem
+/// Returned as a raw binary response (ArrayBuffer in JS), not a JSON number array
 #[tauri::command]
-fn read_pdf_file(path: String) -> Result<Vec<u8>, String> {
-    fs::read(&path).map_err(|e| format!("Failed to read file {}: {}", path, e))
+fn read_pdf_file(path: String, scope: State<'_, FileScope>) -> Result<Response, FileError> {
+    let path = scope.check_read(Path::new(&path))?;
+    let data = fs::read(&path).map_err(|e| FileError::io("read", &path, e))?;
+    Ok(Response::new(data))
 }
 
-/// Write a PDF file to the local filesystem
+/// Stream a PDF file to the frontend in raw chunks, returns the total size in bytes
 #[tauri::command]
-fn write_pdf_file(path: String, data: Vec<u8>) -> Result<(), String> {
-    fs::write(&path, &data).map_err(|e| format!("Failed to write file {}: {}", path, e))
+async fn read_pdf_file_chunked(
+    path: String,
+    on_chunk: Channel<InvokeResponseBody>,
+    scope: State<'_, FileScope>,
+) -> Result<u64, FileError> {
+    let path = scope.check_read(Path::new(&path))?;
+    let mut file = fs::File::open(&path).map_err(|e| FileError::io("read", &path, e))?;
+    let mut total = 0u64;
+    loop {
+        let mut chunk = Vec::with_capacity(READ_CHUNK_SIZE);
+        let read = (&mut file)
+            .take(READ_CHUNK_SIZE as u64)
+            .read_to_end(&mut chunk)
+            .map_err(|e| FileError::io("read", &path, e))?;
+        if read == 0 {
+            break;
+        }
+        total += read as u64;
+        on_chunk
+            .send(InvokeResponseBody::Raw(chunk))
+            .map_err(|e| FileError::other(&path, format!("Failed to send file data: {}", e)))?;
+    }
+    Ok(total)
 }
 
+/// Size, modification time and change token of a local PDF (first step of a lazy range load)
+#[tauri::command]
+async fn pdf_file_info(
+    path: String,
+    open_files: State<'_, OpenFiles>,
+    scope: State<'_, FileScope>,
+) -> Result<FileInfo, FileError> {
+    let path = scope.check_read(Path::new(&path))?;
+    open_files.info(&path).map_err(|e| FileError::other(&path, e))
+}
 
-/// Open the file explorer with the file selected
+/// Read `length` bytes at `offset` of a local PDF, for PDF.js range requests
 #[tauri::command]
-fn show_in_folder(path: String) -> Result<(), String> {
-    #[cfg(target_os = "windows")]
-    {
-        std::process::Command::new("explorer")
-            .args(["/select,", &path]) // Comma is important for explorer /select
-            .spawn()
-            .map_err(|e| format!("Failed to open explorer: {}", e))?;
+async fn read_pdf_range(
+    path: String,
+    offset: u64,
+    length: u64,
+    open_files: State<'_, OpenFiles>,
+    scope: State<'_, FileScope>,
+) -> Result<Response, FileError> {
+    let path = scope.check_read(Path::new(&path))?;
+    let data = open_files
+        .read_range(&path, offset, length)
+        .map_err(|e| FileError::other(&path, e))?;
+    Ok(Response::new(data))
+}
+
+/// Check whether a file really is a PDF (by its `%PDF-` header, not its extension)
+/// and report version, encryption, linearization and page count
+#[tauri::command]
+async fn probe_file(path: String, scope: State<'_, FileScope>) -> Result<ProbeResult, FileError> {
+    let path = scope.check_read(Path::new(&path))?;
+    probe::probe_file(&path).map_err(|e| FileError::other(&path, e))
+}
+
+/// Download a remote PDF in the backend instead of through public CORS proxies.
+/// Internal addresses are refused (see `remote`); returned as raw bytes like local files.
+#[tauri::command]
+async fn fetch_remote_pdf(url: String) -> Result<Response, FetchError> {
+    Fetcher::default().fetch(&url).await.map(Response::new)
+}
+
+/// Decrypt a password-protected PDF with its user or owner password and return it as a plain PDF.
+/// The decrypted document is kept for `export_annotated_pdf` while a window shows the file.
+#[tauri::command]
+async fn unlock_pdf_file(
+    path: String,
+    password: String,
+    unlocked_files: State<'_, UnlockedFiles>,
+    scope: State<'_, FileScope>,
+) -> Result<Response, FileError> {
+    let path = scope.check_read(Path::new(&path))?;
+    let mut document = unlocked_files.unlock(&path, &password)?.document;
+    let mut data = Vec::new();
+    document
+        .save_to(&mut data)
+        .map_err(|e| FileError::other(&path, format!("Failed to write decrypted PDF: {}", e)))?;
+    Ok(Response::new(data))
+}
+
+/// Release the cached handle of a file opened for range reads
+#[tauri::command]
+fn close_pdf_file(path: String, open_files: State<'_, OpenFiles>) {
+    // Handles are keyed by the canonical path `pdf_file_info` checked
+    if let Ok(path) = fs::canonicalize(&path) {
+        open_files.close(&path);
     }
-    #[cfg(not(target_os = "windows"))]
-    {
-        // Fallback for other OS if ever needed (xdg-open, open, etc.)
-        // For now, return error or no-op
-        return Err("Not supported on this OS".to_string());
+}
+
+/// Watch the file shown on `side` of the calling window and emit `pdf-file-changed` to it when it is rewritten
+#[tauri::command]
+fn watch_pdf_file(
+    app: AppHandle,
+    window: WebviewWindow,
+    side: Side,
+    path: String,
+    watchers: State<'_, Watchers>,
+    scope: State<'_, FileScope>,
+) -> Result<(), FileError> {
+    let file = scope.check_read(Path::new(&path))?;
+    watchers
+        .watch(app, window.label(), side, file.clone(), path)
+        .map_err(|e| FileError::other(&file, e))
+}
+
+/// Stop watching the file shown on `side` of the calling window
+#[tauri::command]
+fn unwatch_pdf_file(window: WebviewWindow, side: Side, watchers: State<'_, Watchers>) -> Result<(), String> {
+    watchers.unwatch(window.label(), side)
+}
+
+/// Open another comparison window, or focus the one already showing these documents; returns its label
+#[tauri::command]
+async fn open_comparison_window(
+    app: AppHandle,
+    left: Option<String>,
+    right: Option<String>,
+    windows: State<'_, ComparisonWindows>,
+    scope: State<'_, FileScope>,
+) -> Result<String, FileError> {
+    let documents = window_documents(left, right, &scope)?;
+    let existing = windows
+        .list(&app)
+        .into_iter()
+        .find(|info| documents != WindowDocuments::default() && info.documents == documents);
+    if let Some(window) = existing.and_then(|info| app.get_webview_window(&info.label)) {
+        let _ = window.unminimize();
+        let _ = window.set_focus();
+        return Ok(window.label().to_string());
     }
+    windows
+        .open(&app, documents, None)
+        .map_err(|e| FileError::other(Path::new(""), e))
+}
+
+/// Record the local documents the calling window shows (also sets its title)
+#[tauri::command]
+fn set_window_documents(
+    window: WebviewWindow,
+    left: Option<String>,
+    right: Option<String>,
+    windows: State<'_, ComparisonWindows>,
+    scope: State<'_, FileScope>,
+) -> Result<(), FileError> {
+    let previous = windows.set_documents(&window, window_documents(left, right, &scope)?);
+    release_unlocked(window.app_handle(), previous);
     Ok(())
 }
 
+/// Drop the decrypted copies of documents that no window shows any more
+fn release_unlocked(app: &AppHandle, documents: Option<WindowDocuments>) {
+    let windows = app.state::<ComparisonWindows>();
+    let unlocked = app.state::<UnlockedFiles>();
+    for path in documents.into_iter().flat_map(|documents| [documents.left, documents.right]).flatten() {
+        if !windows.shows(&path) {
+            unlocked.remove(Path::new(&path));
+        }
+    }
+}
+
+/// Granted documents by their canonical paths; windows.json is trusted on the next start
+fn window_documents(left: Option<String>, right: Option<String>, scope: &FileScope) -> Result<WindowDocuments, FileError> {
+    let check = |path: Option<String>| {
+        path.map(|path| scope.check_read(Path::new(&path)).map(|path| path.to_string_lossy().into_owned()))
+            .transpose()
+    };
+    Ok(WindowDocuments { left: check(left)?, right: check(right)? })
+}
+
+/// Open windows with their documents, size and position
+#[tauri::command]
+fn list_comparison_windows(app: AppHandle, windows: State<'_, ComparisonWindows>) -> Vec<WindowInfo> {
+    windows.list(&app)
+}
+
+/// Recently opened files and pairs whose files still exist; they are granted so they can be reopened
+#[tauri::command]
+async fn list_recent(app: AppHandle, recent: State<'_, Recent>, scope: State<'_, FileScope>) -> Result<RecentList, FileError> {
+    let list = recent.list()?;
+    for path in list.paths() {
+        let _ = scope.grant_file(Path::new(path));
+    }
+    if let Err(e) = menu::set_recent(&app, &list) {
+        log::warn!("Failed to update the recent files menu: {}", e);
+    }
+    Ok(list)
+}
+
+/// Record the local documents the calling window shows, with their current page
+#[tauri::command]
+async fn record_recent(
+    app: AppHandle,
+    left: Option<RecentInput>,
+    right: Option<RecentInput>,
+    recent: State<'_, Recent>,
+    store: State<'_, AnnotationStore>,
+    scope: State<'_, FileScope>,
+) -> Result<RecentList, FileError> {
+    let document = |input: Option<RecentInput>| {
+        input
+            .map(|input| {
+                let path = scope.check_read(Path::new(&input.path))?;
+                Ok(RecentDocument { fingerprint: store.fingerprint(&path)?, path: input.path, page: input.page })
+            })
+            .transpose()
+    };
+    let list = recent.record(document(left)?, document(right)?)?;
+    recent_changed(&app, &list);
+    Ok(list)
+}
+
+/// Keep a recent file on top of the list, or stop doing so
+#[tauri::command]
+async fn pin_recent_file(app: AppHandle, path: String, pinned: bool, recent: State<'_, Recent>) -> Result<RecentList, FileError> {
+    let list = recent.pin_file(&path, pinned)?;
+    recent_changed(&app, &list);
+    Ok(list)
+}
+
+/// Keep a recent pair on top of the list, or stop doing so
+#[tauri::command]
+async fn pin_recent_pair(
+    app: AppHandle,
+    left: String,
+    right: String,
+    pinned: bool,
+    recent: State<'_, Recent>,
+) -> Result<RecentList, FileError> {
+    let list = recent.pin_pair(&left, &right, pinned)?;
+    recent_changed(&app, &list);
+    Ok(list)
+}
+
+/// Forget all recent files and pairs except the pinned ones
+#[tauri::command]
+async fn clear_recent(app: AppHandle, recent: State<'_, Recent>) -> Result<RecentList, FileError> {
+    let list = recent.clear()?;
+    recent_changed(&app, &list);
+    Ok(list)
+}
+
+/// Show the new list in File ▸ Recent and every window
+fn recent_changed(app: &AppHandle, list: &RecentList) {
+    if let Err(e) = menu::set_recent(app, list) {
+        log::warn!("Failed to update the recent files menu: {}", e);
+    }
+    let _ = app.emit(recent::RECENT_CHANGED_EVENT, list);
+}
+
+/// Open an entry picked in File ▸ Recent in the focused window
+fn open_recent(app: &AppHandle, item: RecentMenuItem) {
+    let list = app.state::<Recent>().current();
+    let entry = match item {
+        RecentMenuItem::File(index) => list.files.get(index).cloned().map(RecentEntry::File),
+        RecentMenuItem::Pair(index) => list.pairs.get(index).cloned().map(RecentEntry::Pair),
+    };
+    let (Some(entry), Some(window)) = (entry, menu::target_window(app)) else {
+        return;
+    };
+    let scope = app.state::<FileScope>();
+    for path in entry.paths() {
+        if let Err(e) = scope.grant_file(Path::new(path)) {
+            log::warn!("{}", e);
+        }
+    }
+    if let Err(e) = app.emit_to(window.label(), recent::OPEN_RECENT_EVENT, &entry) {
+        log::warn!("Failed to open a recent file: {}", e);
+    }
+}
+
+/// Quit the app, remembering the comparison windows for the next start
+fn quit(app: &AppHandle) {
+    if let Err(e) = app.state::<ComparisonWindows>().save(app) {
+        log::warn!("{}", e);
+    }
+    app.exit(0);
+}
+
+/// Reopen the windows saved when the app last quit, with the documents that still exist.
+/// Only documents that are also in the recent list are granted again.
+fn restore_windows(app: &AppHandle) {
+    let windows = app.state::<ComparisonWindows>();
+    let scope = app.state::<FileScope>();
+    let known: HashSet<PathBuf> = app
+        .state::<Recent>()
+        .current()
+        .paths()
+        .filter_map(|path| fs::canonicalize(path).ok())
+        .collect();
+    let saved = match windows.saved() {
+        Ok(saved) => saved,
+        Err(e) => {
+            log::warn!("{}", e);
+            return;
+        }
+    };
+    for info in saved {
+        if info.label == windows::MAIN {
+            if let (Some(window), Some(geometry)) = (app.get_webview_window(windows::MAIN), info.geometry) {
+                windows::restore_geometry(&window, &geometry);
+            }
+            continue;
+        }
+        let available = |path: Option<String>| {
+            path.filter(|p| {
+                let known = fs::canonicalize(p).is_ok_and(|p| known.contains(&p));
+                if !known {
+                    log::warn!("Not restoring {}: it is not a recent file", p);
+                }
+                known && scope.grant_file(Path::new(p)).is_ok()
+            })
+        };
+        let documents = WindowDocuments {
+            left: available(info.documents.left),
+            right: available(info.documents.right),
+        };
+        if documents == WindowDocuments::default() {
+            continue;
+        }
+        if let Err(e) = windows.open(app, documents, info.geometry) {
+            log::warn!("{}", e);
+        }
+    }
+}
+
+/// Show the native open dialog and grant access to the picked PDF
+#[tauri::command]
+async fn pick_pdf_file(app: AppHandle, scope: State<'_, FileScope>) -> Result<Option<String>, FileError> {
+    // PDFs are recognised by content when loaded, so other extensions can be picked too
+    let dialog = app.dialog().file().add_filter("PDF", &["pdf"]).add_filter("All files", &["*"]);
+    let Some(picked) = dialog.blocking_pick_file() else {
+        return Ok(None);
+    };
+    let path = picked
+        .into_path()
+        .map_err(|e| FileError::other(Path::new(""), format!("Unsupported file location: {}", e)))?;
+    scope.grant_file(&path)?;
+    Ok(Some(path.to_string_lossy().into_owned()))
+}
+
+/// Show the native save dialog and grant writing to the chosen path
+#[tauri::command]
+async fn pick_save_path(
+    app: AppHandle,
+    file_name: String,
+    directory: Option<String>,
+    scope: State<'_, FileScope>,
+) -> Result<Option<String>, FileError> {
+    let mut dialog = app.dialog().file().add_filter("PDF", &["pdf"]).set_file_name(file_name);
+    if let Some(directory) = directory {
+        dialog = dialog.set_directory(directory);
+    }
+    let Some(picked) = dialog.blocking_save_file() else {
+        return Ok(None);
+    };
+    let path = picked
+        .into_path()
+        .map_err(|e| FileError::other(Path::new(""), format!("Unsupported file location: {}", e)))?;
+    scope.grant_new_file(&path)?;
+    Ok(Some(path.to_string_lossy().into_owned()))
+}
+
+/// Show the native open dialog for a `.twice` project and grant access to it
+#[tauri::command]
+async fn pick_project_file(app: AppHandle, scope: State<'_, FileScope>) -> Result<Option<String>, FileError> {
+    let dialog = app.dialog().file().add_filter("Twice project", &[project::EXTENSION]);
+    let Some(picked) = dialog.blocking_pick_file() else {
+        return Ok(None);
+    };
+    let path = picked
+        .into_path()
+        .map_err(|e| FileError::other(Path::new(""), format!("Unsupported file location: {}", e)))?;
+    scope.grant_opened(&path)?;
+    Ok(Some(path.to_string_lossy().into_owned()))
+}
+
+/// Read a project file; its local documents are granted along with it if the user opened it
+#[tauri::command]
+async fn read_project_file(path: String, scope: State<'_, FileScope>) -> Result<Project, FileError> {
+    let path = scope.check_read(Path::new(&path))?;
+    let project = project::read_project(&path)?;
+    for source in project.local_sources() {
+        // Refused and missing documents are reported when the frontend tries to load them
+        if let Err(e) = scope.grant_project_source(&path, source) {
+            log::warn!("{}", e);
+        }
+    }
+    Ok(project)
+}
+
+/// Write a project file, returns its path. Without `path` (or for "Save as")
+/// the native save dialog picks the location.
+#[tauri::command]
+async fn save_project_file(
+    app: AppHandle,
+    path: Option<String>,
+    project: Project,
+    scope: State<'_, FileScope>,
+) -> Result<Option<String>, FileError> {
+    let target = match path {
+        // Projects that were opened or saved before may be rewritten, but no other granted file
+        Some(path) => {
+            let target = scope.check_read(Path::new(&path))?;
+            if !project::is_project_file(&target) {
+                return Err(FileError {
+                    kind: FileErrorKind::NotGranted,
+                    path,
+                    message: format!("Only .{} project files can be saved over", project::EXTENSION),
+                });
+            }
+            target
+        }
+        None => {
+            let dialog = app
+                .dialog()
+                .file()
+                .add_filter("Twice project", &[project::EXTENSION])
+                .set_file_name(format!("comparison.{}", project::EXTENSION));
+            let Some(picked) = dialog.blocking_save_file() else {
+                return Ok(None);
+            };
+            let mut path = picked
+                .into_path()
+                .map_err(|e| FileError::other(Path::new(""), format!("Unsupported file location: {}", e)))?;
+            if !project::is_project_file(&path) {
+                path.as_mut_os_string().push(format!(".{}", project::EXTENSION));
+            }
+            scope.grant_new_file(&path)?
+        }
+    };
+    // The sources now come from the webview, so reopening it must not grant them
+    scope.revoke_project(&target);
+    project::write_project(&target, &project)?;
+    Ok(Some(target.to_string_lossy().into_owned()))
+}
+
+/// Write a PDF file to the local filesystem
+/// The body is the raw file contents; the target path is passed URI-encoded in the `path` header.
+/// The file is replaced atomically, keeping the old version as `.bak` unless the `backup` header is "false".
+#[tauri::command]
+fn write_pdf_file(request: Request<'_>, scope: State<'_, FileScope>) -> Result<(), FileError> {
+    let header = |name: &str| request.headers().get(name).and_then(|value| value.to_str().ok());
+    let path = header("path")
+        .map(decode_path_header)
+        .ok_or_else(|| FileError::other(Path::new(""), "Missing `path` header".to_string()))?;
+    let backup = header("backup") != Some("false");
+    let InvokeBody::Raw(data) = request.body() else {
+        return Err(FileError::other(Path::new(&path), "Expected a raw binary body".to_string()));
+    };
+    let target = scope.check_write(Path::new(&path))?;
+    atomic_write::write_file(&target, data, backup)?;
+    scope.grant_written(&target)?;
+    Ok(())
+}
+
+/// The `path` header of `write_pdf_file`, encoded with `encodeURIComponent` by the frontend
+fn decode_path_header(value: &str) -> String {
+    percent_decode_str(value).decode_utf8_lossy().into_owned()
+}
+
+/// Add comments/bookmarks to a PDF on disk and write the result, without sending the document over IPC.
+/// Encrypted sources are written decrypted unless `keep_encryption` is set.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn export_annotated_pdf(
+    source_path: String,
+    comments: Vec<annotate::Comment>,
+    bookmarks: Vec<annotate::Bookmark>,
+    output_path: String,
+    save_mode: Option<SaveMode>,
+    backup: Option<bool>,
+    keep_encryption: Option<bool>,
+    unlocked_files: State<'_, UnlockedFiles>,
+    scope: State<'_, FileScope>,
+) -> Result<(), FileError> {
+    let source = scope.check_read(Path::new(&source_path))?;
+    let output = scope.check_write(Path::new(&output_path))?;
+    let options = annotate::ExportOptions {
+        mode: save_mode.unwrap_or_default(),
+        backup: backup.unwrap_or(true),
+        password: String::new(),
+        keep_encryption: keep_encryption.unwrap_or(false),
+    };
+    // Password-protected sources were decrypted when they were opened
+    let unlocked = unlocked_files.get(&source);
+    annotate::export_annotated_file(&source, unlocked, &comments, &bookmarks, &output, &options)?;
+    // So the export can be shown in its folder or opened
+    scope.grant_written(&output)?;
+    Ok(())
+}
+
+/// Path for a "save to source" export next to `source_path`, with `{name}`, `{date}`,
+/// `{author}` and `{side}` expanded in the prefix/suffix
+#[tauri::command]
+fn resolve_export_path(
+    source_path: String,
+    prefix: String,
+    suffix: String,
+    collision_policy: Option<CollisionPolicy>,
+    author: Option<String>,
+    side: Option<Side>,
+    scope: State<'_, FileScope>,
+) -> Result<String, FileError> {
+    let source = scope.check_read(Path::new(&source_path))?;
+    let path = export_path::resolve(
+        &source,
+        &prefix,
+        &suffix,
+        &PatternValues { author, side },
+        collision_policy.unwrap_or_default(),
+    )?;
+    // A pattern cannot lead the export out of the source folder
+    let path = scope.check_write(&path)?;
+    Ok(path.to_string_lossy().into_owned())
+}
+
+/// Comments stored for the document at `path` (matched by its /ID or content, not the path)
+#[tauri::command]
+async fn list_comments(
+    path: String,
+    store: State<'_, AnnotationStore>,
+    scope: State<'_, FileScope>,
+) -> Result<Vec<StoredComment>, FileError> {
+    let path = scope.check_read(Path::new(&path))?;
+    store.comments(&path)
+}
+
+/// Store a new comment for the document at `path`
+#[tauri::command]
+async fn add_comment(
+    path: String,
+    comment: StoredComment,
+    store: State<'_, AnnotationStore>,
+    scope: State<'_, FileScope>,
+) -> Result<(), FileError> {
+    let path = scope.check_read(Path::new(&path))?;
+    store.add(&path, comment)
+}
+
+/// Create or update a stored comment
+#[tauri::command]
+async fn save_comment(
+    path: String,
+    comment: StoredComment,
+    store: State<'_, AnnotationStore>,
+    scope: State<'_, FileScope>,
+) -> Result<(), FileError> {
+    let path = scope.check_read(Path::new(&path))?;
+    store.save(&path, comment)
+}
+
+/// Remove a stored comment, returns whether it existed
+#[tauri::command]
+async fn delete_comment(
+    path: String,
+    id: String,
+    store: State<'_, AnnotationStore>,
+    scope: State<'_, FileScope>,
+) -> Result<bool, FileError> {
+    let path = scope.check_read(Path::new(&path))?;
+    store.delete(&path, &id)
+}
+
+/// Stable fingerprint of a document: its trailer /ID, or a SHA-256 of the file
+#[tauri::command]
+async fn document_fingerprint(
+    path: String,
+    store: State<'_, AnnotationStore>,
+    scope: State<'_, FileScope>,
+) -> Result<String, FileError> {
+    let path = scope.check_read(Path::new(&path))?;
+    store.fingerprint(&path)
+}
+
+/// Bookmarks stored for the document at `path`
+#[tauri::command]
+async fn get_bookmarks(
+    path: String,
+    store: State<'_, AnnotationStore>,
+    scope: State<'_, FileScope>,
+) -> Result<Vec<StoredBookmark>, FileError> {
+    let path = scope.check_read(Path::new(&path))?;
+    store.bookmarks(&path)
+}
+
+/// Replace the bookmarks stored for the document at `path`
+#[tauri::command]
+async fn set_bookmarks(
+    path: String,
+    bookmarks: Vec<StoredBookmark>,
+    store: State<'_, AnnotationStore>,
+    scope: State<'_, FileScope>,
+) -> Result<(), FileError> {
+    let path = scope.check_read(Path::new(&path))?;
+    store.set_bookmarks(&path, bookmarks)
+}
+
+/// Save a bookmark set through the native save dialog, returns the chosen path
+#[tauri::command]
+async fn export_bookmarks(
+    app: AppHandle,
+    path: Option<String>,
+    name: String,
+    bookmarks: Vec<StoredBookmark>,
+    store: State<'_, AnnotationStore>,
+    scope: State<'_, FileScope>,
+) -> Result<Option<String>, FileError> {
+    let fingerprint = match path {
+        Some(path) => Some(store.fingerprint(&scope.check_read(Path::new(&path))?)?),
+        None => None,
+    };
+    let stem = Path::new(&name).file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let dialog = app
+        .dialog()
+        .file()
+        .add_filter("Bookmarks", &["json"])
+        .set_file_name(format!("{}.bookmarks.json", stem));
+    let Some(picked) = dialog.blocking_save_file() else {
+        return Ok(None);
+    };
+    let target = picked
+        .into_path()
+        .map_err(|e| FileError::other(Path::new(""), format!("Unsupported file location: {}", e)))?;
+    let set = BookmarkSet { fingerprint, name: Some(name), bookmarks };
+    annotation_store::write_bookmark_set(&target, &set)?;
+    Ok(Some(target.to_string_lossy().into_owned()))
+}
+
+/// Pick a bookmark set file with the native open dialog and read it
+#[tauri::command]
+async fn import_bookmarks(app: AppHandle) -> Result<Option<BookmarkSet>, FileError> {
+    let dialog = app.dialog().file().add_filter("Bookmarks", &["json"]);
+    let Some(picked) = dialog.blocking_pick_file() else {
+        return Ok(None);
+    };
+    let path = picked
+        .into_path()
+        .map_err(|e| FileError::other(Path::new(""), format!("Unsupported file location: {}", e)))?;
+    annotation_store::read_bookmark_set(&path).map(Some)
+}
+
+/// Journal entries are keyed like the annotation store for local files, by the pdf.js fingerprint otherwise
+fn journal_fingerprint(
+    source: Option<&str>,
+    fingerprint: Option<String>,
+    store: &AnnotationStore,
+    scope: &FileScope,
+) -> Result<String, FileError> {
+    match source {
+        Some(source) if !project::is_url(source) => store.fingerprint(&scope.check_read(Path::new(source))?),
+        _ => fingerprint
+            .ok_or_else(|| FileError::other(Path::new(""), "Journal entry has no document fingerprint".to_string())),
+    }
+}
+
+/// Record an annotation, draft or bookmark change in the autosave journal
+#[tauri::command]
+async fn journal_append(
+    entry: JournalInput,
+    journal: State<'_, Journal>,
+    store: State<'_, AnnotationStore>,
+    scope: State<'_, FileScope>,
+) -> Result<(), FileError> {
+    let fingerprint = journal_fingerprint(entry.source.as_deref(), entry.fingerprint.clone(), &store, &scope)?;
+    journal.append(&JournalEntry::new(entry, fingerprint))
+}
+
+/// Changes not yet committed by an export; their local documents are granted so they can be reopened
+#[tauri::command]
+async fn journal_entries(journal: State<'_, Journal>, scope: State<'_, FileScope>) -> Result<Vec<JournalEntry>, FileError> {
+    let entries = journal.entries()?;
+    for source in entries.iter().filter_map(|e| e.source.as_deref()).filter(|s| !project::is_url(s)) {
+        let _ = scope.grant_file(Path::new(source));
+    }
+    Ok(entries)
+}
+
+/// Drop the journal entries of one document (after exporting it), or all entries when none is given
+#[tauri::command]
+async fn journal_compact(
+    source: Option<String>,
+    fingerprint: Option<String>,
+    journal: State<'_, Journal>,
+    store: State<'_, AnnotationStore>,
+    scope: State<'_, FileScope>,
+) -> Result<(), FileError> {
+    if source.is_none() && fingerprint.is_none() {
+        return journal.compact(None);
+    }
+    let fingerprint = journal_fingerprint(source.as_deref(), fingerprint, &store, &scope)?;
+    journal.compact(Some(&fingerprint))
+}
+
+/// The documents of the pair on screen may be read
+fn grant_current_pair(queue: &Queue, scope: &FileScope) {
+    for path in queue.current_pair().into_iter().flat_map(|pair| [&pair.left, &pair.right]) {
+        if let Err(e) = scope.grant_file(Path::new(path)) {
+            log::warn!("{}", e);
+        }
+    }
+}
+
+/// The review queue opened with `twice-pdf queue`, or null
+#[tauri::command]
+fn queue_state(queue: State<'_, ReviewQueue>) -> Option<Queue> {
+    queue.get()
+}
+
+/// Move to the pair at `index` of the review queue
+#[tauri::command]
+fn queue_goto(index: usize, queue: State<'_, ReviewQueue>, scope: State<'_, FileScope>) -> Result<Queue, FileError> {
+    let queue = queue.update(|queue| {
+        if index >= queue.pairs.len() {
+            return Err(format!("The queue has no pair {}", index + 1));
+        }
+        queue.current = index;
+        Ok(())
+    })?;
+    grant_current_pair(&queue, &scope);
+    Ok(queue)
+}
+
+/// Move to the next pair (stays on the last one)
+#[tauri::command]
+fn queue_next(queue: State<'_, ReviewQueue>, scope: State<'_, FileScope>) -> Result<Queue, FileError> {
+    let queue = queue.update(|queue| {
+        queue.current = (queue.current + 1).min(queue.pairs.len().saturating_sub(1));
+        Ok(())
+    })?;
+    grant_current_pair(&queue, &scope);
+    Ok(queue)
+}
+
+/// Move to the previous pair (stays on the first one)
+#[tauri::command]
+fn queue_previous(queue: State<'_, ReviewQueue>, scope: State<'_, FileScope>) -> Result<Queue, FileError> {
+    let queue = queue.update(|queue| {
+        queue.current = queue.current.saturating_sub(1);
+        Ok(())
+    })?;
+    grant_current_pair(&queue, &scope);
+    Ok(queue)
+}
+
+/// Mark the pair at `index` as pending, reviewed or flagged
+#[tauri::command]
+fn queue_set_status(index: usize, status: PairStatus, queue: State<'_, ReviewQueue>) -> Result<Queue, FileError> {
+    queue.update(|queue| {
+        let pair = queue
+            .pairs
+            .get_mut(index)
+            .ok_or_else(|| format!("The queue has no pair {}", index + 1))?;
+        pair.status = status;
+        Ok(())
+    })
+}
+
+/// Open the file explorer with the file selected
+#[tauri::command]
+async fn show_in_folder(path: String, scope: State<'_, FileScope>) -> Result<(), RevealError> {
+    let path = scope.check_read(Path::new(&path))?;
+    reveal::show_in_folder(&path)
+}
+
 // Note: URL opening is handled by tauri-plugin-opener (window.__TAURI__.opener.openUrl)
 
+/// Run a subcommand that writes to the terminal it was started from, and exit with its code
+fn headless(run: impl FnOnce() -> i32) -> ! {
+    // Release builds use the GUI subsystem, which starts without a console
+    #[cfg(windows)]
+    {
+        extern "system" {
+            fn AttachConsole(process_id: u32) -> i32;
+        }
+        const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+        // Fails harmlessly when there is no parent console (e.g. started from Explorer)
+        unsafe { AttachConsole(ATTACH_PARENT_PROCESS) };
+    }
+    std::process::exit(run())
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     // Parse CLI arguments BEFORE starting Tauri (ensures they're captured)
-    let args: Vec<String> = std::env::args().collect();
-    
-    let pdf_paths: Vec<String> = args
-        .into_iter()
-        .skip(1) // Skip executable path
-        .filter(|arg| {
-            let lower = arg.to_lowercase();
-            lower.ends_with(".pdf") && std::path::Path::new(arg).exists()
-        })
-        .collect();
-    
+    // --help/--version exit with 0, usage errors with 2
+    let cli = Cli::parse();
+
+    let mut queue_source = None;
+    let open_args = match cli.command {
+        Some(Command::Open(args)) => args,
+        Some(Command::Queue(args)) => match args.into_queue() {
+            Ok(source) => {
+                queue_source = Some(source);
+                OpenArgs::default()
+            }
+            Err(e) => Cli::command()
+                .error(clap::error::ErrorKind::ValueValidation, e)
+                .exit(),
+        },
+        // Headless subcommands never start the Tauri builder
+        Some(Command::Diff(args)) => headless(|| diff::run(&args)),
+        Some(Command::Annotate(args)) => headless(|| annotate::run(&args)),
+        Some(Command::Annotations(args)) => headless(|| annotations::run(&args)),
+        None => cli.open,
+    };
+    let new_instance = open_args.new_instance;
+    let launch_options = match open_args.into_launch_options() {
+        Ok(options) => LaunchOptions { queue: queue_source.is_some(), ..options },
+        Err(e) => Cli::command()
+            .error(clap::error::ErrorKind::ValueValidation, e)
+            .exit(),
+    };
+
+    // Hand the files to a running instance and exit; queues always get their own window
+    let instance = if new_instance || launch_options.queue {
+        Instance::Standalone
+    } else {
+        single_instance::acquire(&launch_options)
+    };
+    if let Instance::Forwarded = instance {
+        return;
+    }
+
     // Store for later retrieval by frontend
-    let _ = CLI_PDF_PATHS.set(pdf_paths);
+    let _ = LAUNCH_OPTIONS.set(launch_options);
 
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
-        .setup(|app| {
+        .on_menu_event(|app, event| {
+            let id = event.id().as_ref();
+            match MenuAction::from_id(id) {
+                Some(MenuAction::Quit) => quit(app),
+                Some(MenuAction::ClearRecent) => match app.state::<Recent>().clear() {
+                    Ok(list) => recent_changed(app, &list),
+                    Err(e) => log::warn!("{}", e),
+                },
+                Some(action) => menu::dispatch(app, action),
+                None => {
+                    if let Some(item) = RecentMenuItem::from_id(id) {
+                        open_recent(app, item);
+                    }
+                }
+            }
+        })
+        .manage(OpenFiles::default())
+        .manage(Watchers::default())
+        .manage(FileScope::default())
+        .manage(UnlockedFiles::default())
+        .setup(move |app| {
             // Debug logging (dev only)
             if cfg!(debug_assertions) {
                 app.handle().plugin(
@@ -74,15 +944,139 @@ pub fn run() {
                 )?;
             }
             // DevTools enabled via "devtools" feature - use Ctrl+Shift+I to open
+
+            let data_dir = app.path().app_data_dir()?;
+            // Managed before the menu is built, so File ▸ Recent can read it even if the build fails
+            app.manage(Settings::load(&data_dir.join("settings.json")));
+            match menu::build(app.handle(), &app.state::<Settings>()) {
+                Ok(menu) => {
+                    app.set_menu(menu)?;
+                }
+                Err(e) => log::warn!("Failed to build the menu: {}", e),
+            }
+            app.manage(AnnotationStore::new(data_dir.join("annotations")));
+            app.manage(Journal::new(data_dir.join("journal.jsonl")));
+            let recent = Recent::load(data_dir.join("recent.json"));
+            match recent.list() {
+                Ok(list) => {
+                    if let Err(e) = menu::set_recent(app.handle(), &list) {
+                        log::warn!("Failed to fill the recent files menu: {}", e);
+                    }
+                }
+                Err(e) => log::warn!("{}", e),
+            }
+            app.manage(recent);
+            let primary = matches!(instance, Instance::Primary(_));
+            app.manage(ComparisonWindows::new(primary.then(|| data_dir.join("windows.json"))));
+            let review_queue = match queue_source {
+                Some(source) => ReviewQueue::load(source, &data_dir.join("queues")),
+                None => ReviewQueue::default(),
+            };
+
+            // Files named on the command line are granted up front
+            let scope = app.state::<FileScope>();
+            if let Some(options) = LAUNCH_OPTIONS.get() {
+                for path in options.files() {
+                    if let Err(e) = scope.grant_opened(path) {
+                        log::warn!("{}", e);
+                    }
+                }
+            }
+            if let Some(queue) = review_queue.get() {
+                grant_current_pair(&queue, &scope);
+            }
+            app.manage(review_queue);
+
+            // Later launches open their files here (`open-request`)
+            if let Instance::Primary(listener) = instance {
+                single_instance::listen(listener, app.handle().clone());
+            }
+            restore_windows(app.handle());
             
             Ok(())
         })
+        .on_window_event(|window, event| match event {
+            // Closing the main window quits, remembering the other windows for the next start
+            WindowEvent::CloseRequested { .. } if window.label() == windows::MAIN => quit(window.app_handle()),
+            WindowEvent::Destroyed => {
+                let documents = window.state::<ComparisonWindows>().remove(window.label());
+                release_unlocked(window.app_handle(), documents);
+                let _ = window.state::<Watchers>().unwatch_window(window.label());
+            }
+            _ => {}
+        })
+        .on_webview_event(|webview, event| {
+            // Dropped files are granted before the frontend sees the drop event
+            if let WebviewEvent::DragDrop(DragDropEvent::Drop { paths, .. }) = event {
+                let scope = webview.state::<FileScope>();
+                for path in paths.iter().filter(|p| p.is_file()) {
+                    let _ = scope.grant_opened(path);
+                }
+            }
+        })
         .invoke_handler(tauri::generate_handler![
-            get_cli_pdf_paths, 
-            read_pdf_file, 
+            get_launch_options,
+            read_pdf_file,
+            read_pdf_file_chunked,
+            pdf_file_info,
+            read_pdf_range,
+            close_pdf_file,
+            probe_file,
+            fetch_remote_pdf,
+            unlock_pdf_file,
+            watch_pdf_file,
+            unwatch_pdf_file,
+            pick_pdf_file,
+            pick_save_path,
+            pick_project_file,
+            read_project_file,
+            save_project_file,
             write_pdf_file,
+            export_annotated_pdf,
+            resolve_export_path,
+            list_comments,
+            add_comment,
+            save_comment,
+            delete_comment,
+            document_fingerprint,
+            get_bookmarks,
+            set_bookmarks,
+            export_bookmarks,
+            import_bookmarks,
+            journal_append,
+            journal_entries,
+            journal_compact,
+            open_comparison_window,
+            set_window_documents,
+            list_comparison_windows,
+            queue_state,
+            queue_goto,
+            queue_next,
+            queue_previous,
+            queue_set_status,
+            list_recent,
+            record_recent,
+            pin_recent_file,
+            pin_recent_pair,
+            clear_recent,
             show_in_folder
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_header_undoes_encode_uri_component() {
+        assert_eq!(
+            decode_path_header("C%3A%5CUsers%5CZo%C3%AB%5C100%25%20done%2Bfinal.pdf"),
+            "C:\\Users\\Zoë\\100% done+final.pdf"
+        );
+        assert_eq!(decode_path_header("%2Ftmp%2F%E6%97%A5%E6%9C%AC%2Fa.pdf"), "/tmp/日本/a.pdf");
+        // Unlike form encoding, `+` is not a space
+        assert_eq!(decode_path_header("/tmp/a+b (1).pdf"), "/tmp/a+b (1).pdf");
+    }
+}
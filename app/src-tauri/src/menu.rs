@@ -0,0 +1,467 @@
+//! Native application menu
+//!
+//! Built in Rust when the app starts. Choosing an item sends its
+//! [`MenuAction`] to the focused window as a `menu-action` event, whose
+//! payload is the action id (`"openLeft"`, `"nextPage"`, ...). Quit, Full
+//! Screen and Clear Recent are handled in the backend. File ▸ Recent lists
+//! the [`RecentList`] (see [`RecentMenuItem`]). Shortcuts come from
+//! [`MenuAction::default_accelerator`] unless `settings.json` overrides them;
+//! shortcuts that do not parse are logged and the default is used instead.
+
+use crate::recent::RecentList;
+use crate::settings::Settings;
+use crate::windows;
+use muda::accelerator::Accelerator;
+use serde::{Serialize, Serializer};
+use std::path::Path;
+use tauri::menu::{Menu, MenuBuilder, MenuItemBuilder, MenuItemKind, PredefinedMenuItem, Submenu, SubmenuBuilder};
+use tauri::{AppHandle, Emitter, Manager, WebviewWindow};
+
+pub const MENU_ACTION_EVENT: &str = "menu-action";
+
+const FILE_SUBMENU: &str = "file";
+const RECENT_SUBMENU: &str = "recent";
+
+/// File ▸ Recent shows the first entries of the recent list (pinned ones come first)
+const MAX_RECENT_FILES: usize = 10;
+const MAX_RECENT_PAIRS: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuAction {
+    OpenLeft,
+    OpenRight,
+    OpenProject,
+    SaveProject,
+    NewWindow,
+    ExportLeft,
+    ExportRight,
+    Quit,
+    ClearRecent,
+    ToggleSync,
+    SinglePage,
+    Continuous,
+    ZoomIn,
+    ZoomOut,
+    FitToPage,
+    Fullscreen,
+    NextPage,
+    PreviousPage,
+    FirstPage,
+    LastPage,
+    GoToPage,
+    NextComment,
+    PreviousComment,
+    NextPair,
+    PreviousPair,
+}
+
+use Entry::*;
+use MenuAction::*;
+
+enum Entry {
+    Item(MenuAction),
+    /// The File ▸ Recent submenu
+    Recent,
+    Separator,
+}
+
+const FILE: &[Entry] = &[
+    Item(OpenLeft),
+    Item(OpenRight),
+    Recent,
+    Separator,
+    Item(OpenProject),
+    Item(SaveProject),
+    Item(NewWindow),
+    Separator,
+    Item(ExportLeft),
+    Item(ExportRight),
+    Separator,
+    Item(Quit),
+];
+const VIEW: &[Entry] = &[
+    Item(ToggleSync),
+    Separator,
+    Item(SinglePage),
+    Item(Continuous),
+    Separator,
+    Item(ZoomIn),
+    Item(ZoomOut),
+    Item(FitToPage),
+    Separator,
+    Item(Fullscreen),
+];
+const GO: &[Entry] = &[
+    Item(NextPage),
+    Item(PreviousPage),
+    Item(FirstPage),
+    Item(LastPage),
+    Separator,
+    Item(GoToPage),
+];
+const REVIEW: &[Entry] = &[
+    Item(NextComment),
+    Item(PreviousComment),
+    Separator,
+    Item(NextPair),
+    Item(PreviousPair),
+];
+
+impl MenuAction {
+    const ALL: [MenuAction; 25] = [
+        OpenLeft, OpenRight, OpenProject, SaveProject, NewWindow, ExportLeft, ExportRight, Quit, ClearRecent,
+        ToggleSync, SinglePage, Continuous, ZoomIn, ZoomOut, FitToPage, Fullscreen,
+        NextPage, PreviousPage, FirstPage, LastPage, GoToPage,
+        NextComment, PreviousComment, NextPair, PreviousPair,
+    ];
+
+    /// Menu item id, event payload and key in `settings.json`
+    pub fn id(self) -> &'static str {
+        self.describe().0
+    }
+
+    pub fn from_id(id: &str) -> Option<MenuAction> {
+        Self::ALL.into_iter().find(|action| action.id() == id)
+    }
+
+    fn label(self) -> &'static str {
+        self.describe().1
+    }
+
+    fn default_accelerator(self) -> Option<&'static str> {
+        self.describe().2
+    }
+
+    fn describe(self) -> (&'static str, &'static str, Option<&'static str>) {
+        match self {
+            OpenLeft => ("openLeft", "Open Left…", Some("CmdOrCtrl+O")),
+            OpenRight => ("openRight", "Open Right…", Some("CmdOrCtrl+Shift+O")),
+            OpenProject => ("openProject", "Open Project…", None),
+            SaveProject => ("saveProject", "Save Project", Some("CmdOrCtrl+S")),
+            NewWindow => ("newWindow", "New Comparison Window", Some("CmdOrCtrl+N")),
+            ExportLeft => ("exportLeft", "Export Left…", Some("CmdOrCtrl+E")),
+            ExportRight => ("exportRight", "Export Right…", Some("CmdOrCtrl+Shift+E")),
+            Quit => ("quit", "Quit", Some("CmdOrCtrl+Q")),
+            ClearRecent => ("clearRecent", "Clear Recent", None),
+            ToggleSync => ("toggleSync", "Lock Scrolling", Some("CmdOrCtrl+L")),
+            SinglePage => ("singlePage", "Single Page", Some("CmdOrCtrl+1")),
+            Continuous => ("continuous", "Continuous", Some("CmdOrCtrl+2")),
+            ZoomIn => ("zoomIn", "Zoom In", Some("CmdOrCtrl+=")),
+            ZoomOut => ("zoomOut", "Zoom Out", Some("CmdOrCtrl+-")),
+            FitToPage => ("fitToPage", "Fit to Page", Some("CmdOrCtrl+0")),
+            Fullscreen => ("fullscreen", "Full Screen", Some("F11")),
+            NextPage => ("nextPage", "Next Page", Some("Alt+Right")),
+            PreviousPage => ("previousPage", "Previous Page", Some("Alt+Left")),
+            FirstPage => ("firstPage", "First Page", Some("Alt+Home")),
+            LastPage => ("lastPage", "Last Page", Some("Alt+End")),
+            GoToPage => ("goToPage", "Go to Page…", Some("CmdOrCtrl+G")),
+            NextComment => ("nextComment", "Next Comment", Some("F8")),
+            PreviousComment => ("previousComment", "Previous Comment", Some("Shift+F8")),
+            NextPair => ("nextPair", "Next Pair", Some("CmdOrCtrl+Alt+Right")),
+            PreviousPair => ("previousPair", "Previous Pair", Some("CmdOrCtrl+Alt+Left")),
+        }
+    }
+
+    /// The shortcut from the settings, else the default
+    fn accelerator(self, settings: &Settings) -> Option<String> {
+        let accelerator = match settings.accelerators.get(self.id()) {
+            Some(accelerator) => accelerator.clone().filter(|a| !a.trim().is_empty())?,
+            None => return self.default_accelerator().map(str::to_string),
+        };
+        // Tauri silently drops shortcuts it cannot parse
+        match accelerator.parse::<Accelerator>() {
+            Ok(_) => Some(accelerator),
+            Err(e) => {
+                log::warn!(
+                    "Ignoring shortcut \"{}\" for {} in settings.json, using the default: {}",
+                    accelerator,
+                    self.id(),
+                    e
+                );
+                self.default_accelerator().map(str::to_string)
+            }
+        }
+    }
+}
+
+impl Serialize for MenuAction {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.id())
+    }
+}
+
+/// The menu bar shared by all windows
+pub fn build(app: &AppHandle, settings: &Settings) -> tauri::Result<Menu> {
+    let menu = MenuBuilder::new(app);
+    // The first submenu is the application menu on macOS
+    #[cfg(target_os = "macos")]
+    let menu = {
+        let app_menu = SubmenuBuilder::new(app, "Twice PDF")
+            .about(None)
+            .separator()
+            .services()
+            .separator()
+            .hide()
+            .hide_others()
+            .show_all()
+            .build()?;
+        menu.item(&app_menu)
+    };
+
+    // Without an Edit menu macOS has no copy and paste shortcuts
+    let edit = SubmenuBuilder::new(app, "&Edit")
+        .undo()
+        .redo()
+        .separator()
+        .cut()
+        .copy()
+        .paste()
+        .select_all()
+        .build()?;
+
+    menu.item(&submenu(app, FILE_SUBMENU, "&File", FILE, settings)?)
+        .item(&edit)
+        .item(&submenu(app, "view", "&View", VIEW, settings)?)
+        .item(&submenu(app, "go", "&Go", GO, settings)?)
+        .item(&submenu(app, "review", "&Review", REVIEW, settings)?)
+        .build()
+}
+
+fn submenu(
+    app: &AppHandle,
+    id: &str,
+    text: &str,
+    entries: &[Entry],
+    settings: &Settings,
+) -> tauri::Result<Submenu> {
+    let mut submenu = SubmenuBuilder::with_id(app, id, text);
+    for entry in entries {
+        submenu = match entry {
+            Item(action) => submenu.item(&item(app, *action, settings)?),
+            // Filled by `set_recent`
+            Recent => submenu.item(&SubmenuBuilder::with_id(app, RECENT_SUBMENU, "Recent").enabled(false).build()?),
+            Separator => submenu.separator(),
+        };
+    }
+    submenu.build()
+}
+
+fn item(app: &AppHandle, action: MenuAction, settings: &Settings) -> tauri::Result<tauri::menu::MenuItem> {
+    let mut item = MenuItemBuilder::with_id(action.id(), action.label());
+    if let Some(accelerator) = action.accelerator(settings) {
+        item = item.accelerator(accelerator);
+    }
+    item.build(app)
+}
+
+/// A File ▸ Recent item, by its position in the recent list
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecentMenuItem {
+    File(usize),
+    Pair(usize),
+}
+
+impl RecentMenuItem {
+    fn id(self) -> String {
+        match self {
+            RecentMenuItem::File(index) => format!("recent-file-{}", index),
+            RecentMenuItem::Pair(index) => format!("recent-pair-{}", index),
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<RecentMenuItem> {
+        if let Some(index) = id.strip_prefix("recent-file-") {
+            index.parse().ok().map(RecentMenuItem::File)
+        } else {
+            id.strip_prefix("recent-pair-")?.parse().ok().map(RecentMenuItem::Pair)
+        }
+    }
+}
+
+/// Fill File ▸ Recent with the pairs, then the files
+pub fn set_recent(app: &AppHandle, recent: &RecentList) -> tauri::Result<()> {
+    let Some(file) = app.menu().and_then(|menu| menu.get(FILE_SUBMENU)) else {
+        return Ok(());
+    };
+    let Some(MenuItemKind::Submenu(submenu)) = file.as_submenu().and_then(|file| file.get(RECENT_SUBMENU)) else {
+        return Ok(());
+    };
+    for item in submenu.items()? {
+        submenu.remove(&item)?;
+    }
+
+    let (pairs, files) = (recent_pairs(recent), recent_files(recent));
+    for (item, text) in &pairs {
+        submenu.append(&MenuItemBuilder::with_id(item.id(), text).build(app)?)?;
+    }
+    if !pairs.is_empty() && !files.is_empty() {
+        submenu.append(&PredefinedMenuItem::separator(app)?)?;
+    }
+    for (item, text) in &files {
+        submenu.append(&MenuItemBuilder::with_id(item.id(), text).build(app)?)?;
+    }
+    let empty = recent.files.is_empty() && recent.pairs.is_empty();
+    if !empty {
+        submenu.append(&PredefinedMenuItem::separator(app)?)?;
+        submenu.append(&item(app, ClearRecent, &app.state::<Settings>())?)?;
+    }
+    submenu.set_enabled(!empty)
+}
+
+/// The first pairs of the recent list as File ▸ Recent items with their text
+fn recent_pairs(recent: &RecentList) -> Vec<(RecentMenuItem, String)> {
+    let pairs = recent.pairs.iter().take(MAX_RECENT_PAIRS).enumerate();
+    pairs
+        .map(|(index, pair)| {
+            let text = format!("{} ↔ {}", file_name(&pair.left.path), file_name(&pair.right.path));
+            (RecentMenuItem::Pair(index), text)
+        })
+        .collect()
+}
+
+/// The first files of the recent list as File ▸ Recent items with their text
+fn recent_files(recent: &RecentList) -> Vec<(RecentMenuItem, String)> {
+    let files = recent.files.iter().take(MAX_RECENT_FILES).enumerate();
+    files
+        .map(|(index, file)| (RecentMenuItem::File(index), file_name(&file.document.path)))
+        .collect()
+}
+
+fn file_name(path: &str) -> String {
+    let name = Path::new(path).file_name().map_or(path.into(), |name| name.to_string_lossy());
+    // `&` marks the mnemonic in menu text
+    name.replace('&', "&&")
+}
+
+/// Send `action` to the focused window, or toggle its full screen
+pub fn dispatch(app: &AppHandle, action: MenuAction) {
+    let Some(window) = target_window(app) else {
+        return;
+    };
+    if action == Fullscreen {
+        let fullscreen = window.is_fullscreen().unwrap_or(false);
+        if let Err(e) = window.set_fullscreen(!fullscreen) {
+            log::warn!("Failed to toggle full screen: {}", e);
+        }
+        return;
+    }
+    if let Err(e) = app.emit_to(window.label(), MENU_ACTION_EVENT, action) {
+        log::warn!("Failed to send menu action {}: {}", action.id(), e);
+    }
+}
+
+/// The focused window; the main one when none is (the menu is open on macOS)
+pub fn target_window(app: &AppHandle) -> Option<WebviewWindow> {
+    let open = app.webview_windows();
+    open.values()
+        .find(|window| window.is_focused().unwrap_or(false))
+        .or_else(|| open.get(windows::MAIN))
+        .cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recent::{RecentDocument, RecentFile, RecentPair};
+
+    fn settings(accelerators: &[(&str, Option<&str>)]) -> Settings {
+        Settings {
+            accelerators: accelerators
+                .iter()
+                .map(|(id, accelerator)| (id.to_string(), accelerator.map(str::to_string)))
+                .collect(),
+        }
+    }
+
+    fn document(path: &str) -> RecentDocument {
+        RecentDocument { path: path.to_string(), page: 1, fingerprint: String::new() }
+    }
+
+    #[test]
+    fn action_ids_round_trip() {
+        for action in MenuAction::ALL {
+            assert_eq!(MenuAction::from_id(action.id()), Some(action));
+            assert_eq!(serde_json::to_value(action).unwrap(), action.id());
+        }
+        let mut ids: Vec<_> = MenuAction::ALL.iter().map(|action| action.id()).collect();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), MenuAction::ALL.len());
+        assert_eq!(MenuAction::from_id("recent-file-0"), None);
+
+        for item in [RecentMenuItem::File(0), RecentMenuItem::Pair(12)] {
+            assert_eq!(RecentMenuItem::from_id(&item.id()), Some(item));
+        }
+        assert_eq!(RecentMenuItem::from_id("recent-file-x"), None);
+        assert_eq!(RecentMenuItem::from_id("openLeft"), None);
+    }
+
+    #[test]
+    fn default_accelerators_parse() {
+        for action in MenuAction::ALL {
+            if let Some(accelerator) = action.default_accelerator() {
+                assert!(accelerator.parse::<Accelerator>().is_ok(), "{}: {}", action.id(), accelerator);
+            }
+        }
+    }
+
+    #[test]
+    fn settings_override_accelerators() {
+        let settings = settings(&[
+            ("openLeft", Some("CmdOrCtrl+Shift+L")),
+            ("fullscreen", None),
+            ("zoomIn", Some(" ")),
+            ("openProject", Some("CmdOrCtrl+P")),
+        ]);
+        assert_eq!(OpenLeft.accelerator(&settings).as_deref(), Some("CmdOrCtrl+Shift+L"));
+        assert_eq!(Fullscreen.accelerator(&settings), None);
+        assert_eq!(ZoomIn.accelerator(&settings), None);
+        assert_eq!(OpenProject.accelerator(&settings).as_deref(), Some("CmdOrCtrl+P"));
+        assert_eq!(OpenRight.accelerator(&settings).as_deref(), Some("CmdOrCtrl+Shift+O"));
+    }
+
+    #[test]
+    fn invalid_accelerators_fall_back_to_the_default() {
+        let settings = settings(&[
+            ("nextPage", Some("Alt+Rigth")),
+            ("goToPage", Some("Ctrl+Shift")),
+            ("openProject", Some("Hyper+P")),
+        ]);
+        assert_eq!(NextPage.accelerator(&settings).as_deref(), Some("Alt+Right"));
+        assert_eq!(GoToPage.accelerator(&settings).as_deref(), Some("CmdOrCtrl+G"));
+        // No default to fall back to
+        assert_eq!(OpenProject.accelerator(&settings), None);
+    }
+
+    #[test]
+    fn recent_items_are_capped() {
+        let recent = RecentList {
+            files: (0..MAX_RECENT_FILES + 5)
+                .map(|i| RecentFile { document: document(&format!("/docs/{}.pdf", i)), opened: 0, pinned: false })
+                .collect(),
+            pairs: (0..MAX_RECENT_PAIRS + 3)
+                .map(|i| RecentPair {
+                    left: document(&format!("/old/{}.pdf", i)),
+                    right: document(&format!("/new/{}.pdf", i)),
+                    opened: 0,
+                    pinned: i == 0,
+                })
+                .collect(),
+        };
+        let (pairs, files) = (recent_pairs(&recent), recent_files(&recent));
+        assert_eq!(pairs.len(), MAX_RECENT_PAIRS);
+        assert_eq!(files.len(), MAX_RECENT_FILES);
+        assert_eq!(pairs[0], (RecentMenuItem::Pair(0), "0.pdf ↔ 0.pdf".to_string()));
+        assert_eq!(files.last().unwrap(), &(RecentMenuItem::File(MAX_RECENT_FILES - 1), format!("{}.pdf", MAX_RECENT_FILES - 1)));
+    }
+
+    #[test]
+    fn recent_items_escape_mnemonics() {
+        let recent = RecentList {
+            files: vec![RecentFile { document: document("/docs/Q&A.pdf"), opened: 0, pinned: true }],
+            pairs: Vec::new(),
+        };
+        assert!(recent_pairs(&recent).is_empty());
+        assert_eq!(recent_files(&recent), [(RecentMenuItem::File(0), "Q&&A.pdf".to_string())]);
+    }
+}
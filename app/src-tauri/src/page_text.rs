@@ -0,0 +1,349 @@
+//! Positioned text for a single page
+//!
+//! A small content-stream interpreter that tracks the text and graphics state
+//! well enough to place each decoded character on the page. Glyph widths come
+//! from the font's /Widths array when present, so positions are approximate
+//! for composite fonts, which is fine for matching text under annotations.
+
+use lopdf::content::Content;
+use lopdf::{Dictionary, Document, Encoding, Object, ObjectId};
+use std::collections::BTreeMap;
+
+/// Used when a font has no usable /Widths (1/1000 text space units)
+const DEFAULT_GLYPH_WIDTH: f32 = 500.0;
+
+/// A character placed in default user space (PDF points, origin bottom-left)
+#[derive(Debug, Clone, Copy)]
+pub struct Glyph {
+    pub ch: char,
+    /// Centre of the glyph box, used for hit-testing
+    pub x: f32,
+    pub y: f32,
+}
+
+type Matrix = [f32; 6];
+
+const IDENTITY: Matrix = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+/// `a` applied first, then `b` (PDF row-vector convention)
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    [
+        a[0] * b[0] + a[1] * b[2],
+        a[0] * b[1] + a[1] * b[3],
+        a[2] * b[0] + a[3] * b[2],
+        a[2] * b[1] + a[3] * b[3],
+        a[4] * b[0] + a[5] * b[2] + b[4],
+        a[4] * b[1] + a[5] * b[3] + b[5],
+    ]
+}
+
+fn transform(m: &Matrix, x: f32, y: f32) -> (f32, f32) {
+    (x * m[0] + y * m[2] + m[4], x * m[1] + y * m[3] + m[5])
+}
+
+struct Font<'a> {
+    encoding: Option<Encoding<'a>>,
+    first_char: i64,
+    widths: Vec<f32>,
+    missing_width: f32,
+    /// Composite fonts use multi-byte codes, so per-byte widths do not apply
+    composite: bool,
+}
+
+impl<'a> Font<'a> {
+    fn load(doc: &'a Document, dict: &'a Dictionary) -> Self {
+        let number = |obj: &Object| doc.dereference(obj).ok().and_then(|(_, o)| o.as_float().ok());
+        let widths = dict
+            .get_deref(b"Widths", doc)
+            .and_then(Object::as_array)
+            .map(|arr| arr.iter().map(|w| number(w).unwrap_or(DEFAULT_GLYPH_WIDTH)).collect())
+            .unwrap_or_default();
+        let missing_width = dict
+            .get_deref(b"FontDescriptor", doc)
+            .and_then(Object::as_dict)
+            .and_then(|d| d.get(b"MissingWidth"))
+            .ok()
+            .and_then(number)
+            .unwrap_or(DEFAULT_GLYPH_WIDTH);
+
+        Font {
+            encoding: dict.get_font_encoding(doc).ok(),
+            first_char: dict.get(b"FirstChar").and_then(Object::as_i64).unwrap_or(0),
+            widths,
+            missing_width,
+            composite: matches!(dict.get(b"Subtype").and_then(Object::as_name), Ok(b"Type0")),
+        }
+    }
+
+    fn width(&self, code: u8) -> f32 {
+        usize::try_from(code as i64 - self.first_char)
+            .ok()
+            .and_then(|i| self.widths.get(i).copied())
+            .unwrap_or(self.missing_width)
+    }
+}
+
+#[derive(Clone)]
+struct State {
+    ctm: Matrix,
+    char_spacing: f32,
+    word_spacing: f32,
+    horizontal_scale: f32,
+    leading: f32,
+    rise: f32,
+    font_size: f32,
+    font: Option<Vec<u8>>,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        State {
+            ctm: IDENTITY,
+            char_spacing: 0.0,
+            word_spacing: 0.0,
+            horizontal_scale: 1.0,
+            leading: 0.0,
+            rise: 0.0,
+            font_size: 0.0,
+            font: None,
+        }
+    }
+}
+
+/// Every character drawn on the page with its approximate position
+pub fn page_glyphs(doc: &Document, page_id: ObjectId) -> Result<Vec<Glyph>, String> {
+    let fonts: BTreeMap<Vec<u8>, Font> = doc
+        .get_page_fonts(page_id)
+        .map_err(|e| format!("Failed to read page fonts: {}", e))?
+        .into_iter()
+        .map(|(name, dict)| (name, Font::load(doc, dict)))
+        .collect();
+    let content = doc
+        .get_page_content(page_id)
+        .and_then(|data| Content::decode(&data))
+        .map_err(|e| format!("Failed to decode page content: {}", e))?;
+
+    let mut glyphs = Vec::new();
+    let mut state = State::default();
+    let mut stack: Vec<State> = Vec::new();
+    let mut text_matrix = IDENTITY;
+    let mut line_matrix = IDENTITY;
+
+    let num = |operands: &[Object], i: usize| operands.get(i).and_then(|o| o.as_float().ok()).unwrap_or(0.0);
+
+    for op in &content.operations {
+        let operands = op.operands.as_slice();
+        match op.operator.as_str() {
+            "q" => stack.push(state.clone()),
+            "Q" => state = stack.pop().unwrap_or_default(),
+            "cm" => {
+                let m: Matrix = std::array::from_fn(|i| num(operands, i));
+                state.ctm = multiply(&m, &state.ctm);
+            }
+            "BT" => {
+                text_matrix = IDENTITY;
+                line_matrix = IDENTITY;
+            }
+            "Tf" => {
+                state.font = operands.first().and_then(|o| o.as_name().ok()).map(<[u8]>::to_vec);
+                state.font_size = num(operands, 1);
+            }
+            "Tc" => state.char_spacing = num(operands, 0),
+            "Tw" => state.word_spacing = num(operands, 0),
+            "Tz" => state.horizontal_scale = num(operands, 0) / 100.0,
+            "TL" => state.leading = num(operands, 0),
+            "Ts" => state.rise = num(operands, 0),
+            "Td" | "TD" => {
+                let (tx, ty) = (num(operands, 0), num(operands, 1));
+                if op.operator == "TD" {
+                    state.leading = -ty;
+                }
+                line_matrix = multiply(&[1.0, 0.0, 0.0, 1.0, tx, ty], &line_matrix);
+                text_matrix = line_matrix;
+            }
+            "Tm" => {
+                line_matrix = std::array::from_fn(|i| num(operands, i));
+                text_matrix = line_matrix;
+            }
+            "T*" => {
+                line_matrix = multiply(&[1.0, 0.0, 0.0, 1.0, 0.0, -state.leading], &line_matrix);
+                text_matrix = line_matrix;
+            }
+            "Tj" | "'" | "\"" | "TJ" => {
+                if op.operator == "'" || op.operator == "\"" {
+                    if op.operator == "\"" {
+                        state.word_spacing = num(operands, 0);
+                        state.char_spacing = num(operands, 1);
+                    }
+                    line_matrix = multiply(&[1.0, 0.0, 0.0, 1.0, 0.0, -state.leading], &line_matrix);
+                    text_matrix = line_matrix;
+                }
+                let Some(font) = state.font.as_ref().and_then(|name| fonts.get(name)) else {
+                    continue;
+                };
+                let items: &[Object] = match op.operator.as_str() {
+                    "TJ" => operands.first().and_then(|o| o.as_array().ok()).map_or(&[], Vec::as_slice),
+                    _ => operands.last().map_or(&[], std::slice::from_ref),
+                };
+                for item in items {
+                    match item {
+                        Object::String(bytes, _) => {
+                            show_string(bytes, font, &state, &mut text_matrix, &mut glyphs);
+                        }
+                        other => {
+                            // TJ adjustment in thousandths of text space, moves against the writing direction
+                            let adjust = other.as_float().unwrap_or(0.0);
+                            let tx = -adjust / 1000.0 * state.font_size * state.horizontal_scale;
+                            text_matrix = multiply(&[1.0, 0.0, 0.0, 1.0, tx, 0.0], &text_matrix);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(glyphs)
+}
+
+fn show_string(bytes: &[u8], font: &Font, state: &State, text_matrix: &mut Matrix, glyphs: &mut Vec<Glyph>) {
+    let decoded: Vec<char> = font
+        .encoding
+        .as_ref()
+        .and_then(|enc| Document::decode_text(enc, bytes).ok())
+        .unwrap_or_default()
+        .chars()
+        .collect();
+
+    // Per-byte advances for simple fonts; composite fonts share the run evenly
+    let advances: Vec<f32> = if !font.composite && decoded.len() == bytes.len() {
+        bytes
+            .iter()
+            .map(|&b| {
+                let spacing = state.char_spacing + if b == b' ' { state.word_spacing } else { 0.0 };
+                (font.width(b) / 1000.0 * state.font_size + spacing) * state.horizontal_scale
+            })
+            .collect()
+    } else {
+        let each = (DEFAULT_GLYPH_WIDTH / 1000.0 * state.font_size + state.char_spacing) * state.horizontal_scale;
+        vec![each; decoded.len()]
+    };
+
+    for (ch, advance) in decoded.into_iter().zip(advances) {
+        let render = multiply(text_matrix, &state.ctm);
+        // Middle of the advance, about a third of the way up the em box
+        let (x, y) = transform(&render, advance / 2.0, state.rise + state.font_size * 0.3);
+        glyphs.push(Glyph { ch, x, y });
+        *text_matrix = multiply(&[1.0, 0.0, 0.0, 1.0, advance, 0.0], text_matrix);
+    }
+}
+
+/// Text whose glyph centres fall inside any of the given boxes ([left, bottom, right, top])
+pub fn text_in_boxes(glyphs: &[Glyph], boxes: &[[f32; 4]]) -> String {
+    let mut text = String::new();
+    let mut last_box = None;
+    for glyph in glyphs {
+        let hit = boxes
+            .iter()
+            .position(|b| glyph.x >= b[0] && glyph.x <= b[2] && glyph.y >= b[1] && glyph.y <= b[3]);
+        if let Some(index) = hit {
+            // Separate lines of a multi-line highlight
+            if last_box.is_some_and(|last| last != index) && !text.ends_with(' ') {
+                text.push(' ');
+            }
+            text.push(glyph.ch);
+            last_box = Some(index);
+        }
+    }
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{dictionary, Stream};
+
+    /// A page drawing `content` with /F1, a simple font whose glyphs are all 500 units wide
+    fn page(content: &str) -> (Document, ObjectId) {
+        let mut doc = Document::with_version("1.7");
+        let pages_id = doc.new_object_id();
+        let font_id = doc.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Courier",
+            "Encoding" => "WinAnsiEncoding",
+            "FirstChar" => 32,
+            "Widths" => vec![Object::Integer(500); 95],
+        });
+        let content_id = doc.add_object(Stream::new(dictionary! {}, content.as_bytes().to_vec()));
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+            "Resources" => dictionary! { "Font" => dictionary! { "F1" => font_id } },
+            "Contents" => content_id,
+        });
+        let pages = dictionary! { "Type" => "Pages", "Kids" => vec![page_id.into()], "Count" => 1 };
+        doc.objects.insert(pages_id, Object::Dictionary(pages));
+        let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        doc.trailer.set("Root", catalog_id);
+        (doc, page_id)
+    }
+
+    fn glyphs(content: &str) -> Vec<Glyph> {
+        let (doc, page_id) = page(content);
+        page_glyphs(&doc, page_id).unwrap()
+    }
+
+    /// Characters with their centres, rounded to a tenth of a point
+    fn placed(glyphs: &[Glyph]) -> Vec<(char, f32, f32)> {
+        let round = |v: f32| (v * 10.0).round() / 10.0;
+        glyphs.iter().map(|g| (g.ch, round(g.x), round(g.y))).collect()
+    }
+
+    #[test]
+    fn places_text_from_tm_and_td() {
+        // 10pt glyphs advance 5pt; centres sit 3pt above the baseline
+        let glyphs = glyphs("BT /F1 10 Tf 1 0 0 1 100 700 Tm (AB) Tj 20 -20 Td (C) Tj ET");
+        assert_eq!(
+            placed(&glyphs),
+            vec![('A', 102.5, 703.0), ('B', 107.5, 703.0), ('C', 122.5, 683.0)]
+        );
+    }
+
+    #[test]
+    fn tj_adjustments_move_against_the_writing_direction() {
+        let glyphs = glyphs("BT /F1 10 Tf 100 700 Td [(A) -1000 (B) 500 (C)] TJ ET");
+        assert_eq!(
+            placed(&glyphs),
+            vec![('A', 102.5, 703.0), ('B', 117.5, 703.0), ('C', 117.5, 703.0)]
+        );
+    }
+
+    #[test]
+    fn follows_leading_scaling_and_the_ctm() {
+        let glyphs = glyphs(
+            "q 2 0 0 2 10 10 cm BT /F1 10 Tf 12 TL 0 100 Td (A) Tj T* (B) Tj ET Q \
+             BT /F1 10 Tf 200 Tz 1 0 0 1 0 0 Tm (CD) Tj ET",
+        );
+        assert_eq!(
+            placed(&glyphs),
+            vec![('A', 15.0, 216.0), ('B', 15.0, 192.0), ('C', 5.0, 3.0), ('D', 15.0, 3.0)]
+        );
+    }
+
+    #[test]
+    fn text_without_a_font_is_skipped() {
+        assert!(glyphs("BT /F9 10 Tf (A) Tj ET").is_empty());
+        assert!(glyphs("BT (A) Tj ET").is_empty());
+    }
+
+    #[test]
+    fn collects_text_inside_boxes() {
+        let glyphs = glyphs("BT /F1 10 Tf 100 700 Td (Hello world) Tj 0 -20 Td (next line) Tj ET");
+        // Both words of the first line, then the start of the second
+        let boxes = [[100.0, 698.0, 155.0, 710.0], [100.0, 678.0, 120.0, 690.0]];
+        assert_eq!(text_in_boxes(&glyphs, &boxes), "Hello world next");
+        assert_eq!(text_in_boxes(&glyphs, &[[125.0, 698.0, 155.0, 710.0]]), "world");
+        assert_eq!(text_in_boxes(&glyphs, &[[0.0, 0.0, 50.0, 50.0]]), "");
+    }
+}
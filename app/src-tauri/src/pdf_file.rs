@@ -0,0 +1,247 @@
+//! Lazy access to large local PDFs
+//!
+//! The viewer asks for `pdf_file_info` once and then pulls byte ranges as
+//! PDF.js needs them, so a multi-GB document does not have to be read (or
+//! held in memory) before the first page renders.
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::UNIX_EPOCH;
+
+/// Bytes hashed from each end of the file for the change token
+const CHANGE_TOKEN_SAMPLE: u64 = 64 * 1024;
+
+/// Open handles kept around; older ones are dropped when this is exceeded
+const MAX_OPEN_FILES: usize = 16;
+
+/// Most bytes returned by one range read; the frontend asks again for the rest
+pub const MAX_RANGE_LENGTH: u64 = 16 * 1024 * 1024;
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FileInfo {
+    pub path: String,
+    pub size: u64,
+    /// Last modification time in milliseconds since the Unix epoch
+    pub modified: Option<u64>,
+    /// SHA-256 of the size plus the first and last 64 KiB, hex encoded. Cheap enough for
+    /// huge files, but misses same-size edits in the middle, so compare `modified` too.
+    /// Not a document identity, unlike `annotation_store::document_key`.
+    pub change_token: String,
+}
+
+/// File handles shared by range reads, keyed by path (managed Tauri state).
+/// Reads use positioned I/O on a shared handle, so the map is only locked to look it up.
+#[derive(Default)]
+pub struct OpenFiles {
+    files: Mutex<HashMap<PathBuf, Arc<File>>>,
+}
+
+impl OpenFiles {
+    /// Size, mtime and change token of a file. Also refreshes the cached handle,
+    /// so reads after a reload never see the previous version of the file.
+    pub fn info(&self, path: &Path) -> Result<FileInfo, String> {
+        let mut file = open(path)?;
+        let info = file_info(path, &mut file)?;
+
+        let mut files = self.files.lock().map_err(|_| "File cache is poisoned".to_string())?;
+        if files.len() >= MAX_OPEN_FILES && !files.contains_key(path) {
+            files.clear();
+        }
+        files.insert(path.to_path_buf(), Arc::new(file));
+        Ok(info)
+    }
+
+    /// Read up to `length` bytes at `offset`, at most `MAX_RANGE_LENGTH`;
+    /// shorter than asked only at the end of the file or past that limit
+    pub fn read_range(&self, path: &Path, offset: u64, length: u64) -> Result<Vec<u8>, String> {
+        let file = self.handle(path)?;
+        let read_error = |e: io::Error| format!("Failed to read file {}: {}", path.display(), e);
+        let size = file.metadata().map_err(read_error)?.len();
+        let length = length.min(MAX_RANGE_LENGTH).min(size.saturating_sub(offset));
+
+        let mut data = vec![0; length as usize];
+        let mut filled = 0;
+        while filled < data.len() {
+            match read_at(&file, &mut data[filled..], offset + filled as u64) {
+                Ok(0) => break,
+                Ok(read) => filled += read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(read_error(e)),
+            }
+        }
+        // The file may have shrunk since its size was read
+        data.truncate(filled);
+        Ok(data)
+    }
+
+    /// The cached handle for `path`, opened on first use
+    fn handle(&self, path: &Path) -> Result<Arc<File>, String> {
+        let mut files = self.files.lock().map_err(|_| "File cache is poisoned".to_string())?;
+        if let Some(file) = files.get(path) {
+            return Ok(Arc::clone(file));
+        }
+        if files.len() >= MAX_OPEN_FILES {
+            files.clear();
+        }
+        let file = Arc::new(open(path)?);
+        files.insert(path.to_path_buf(), Arc::clone(&file));
+        Ok(file)
+    }
+
+    /// Drop the cached handle for a file (e.g. when its viewer is closed)
+    pub fn close(&self, path: &Path) {
+        if let Ok(mut files) = self.files.lock() {
+            files.remove(path);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    std::os::unix::fs::FileExt::read_at(file, buf, offset)
+}
+
+#[cfg(windows)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    std::os::windows::fs::FileExt::seek_read(file, buf, offset)
+}
+
+fn open(path: &Path) -> Result<File, String> {
+    File::open(path).map_err(|e| format!("Failed to open file {}: {}", path.display(), e))
+}
+
+/// Metadata and change token of an open file
+pub fn file_info(path: &Path, file: &mut File) -> Result<FileInfo, String> {
+    let metadata = file
+        .metadata()
+        .map_err(|e| format!("Failed to read metadata of {}: {}", path.display(), e))?;
+    let size = metadata.len();
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_millis() as u64);
+
+    Ok(FileInfo {
+        path: path.to_string_lossy().into_owned(),
+        size,
+        modified,
+        change_token: change_token(path, file, size)?,
+    })
+}
+
+fn change_token(path: &Path, file: &mut File, size: u64) -> Result<String, String> {
+    let read_error = |e: std::io::Error| format!("Failed to read file {}: {}", path.display(), e);
+    let mut hasher = Sha256::new();
+    hasher.update(size.to_le_bytes());
+
+    let mut sample = Vec::new();
+    file.seek(SeekFrom::Start(0)).map_err(read_error)?;
+    (&mut *file).take(CHANGE_TOKEN_SAMPLE).read_to_end(&mut sample).map_err(read_error)?;
+    hasher.update(&sample);
+
+    if size > CHANGE_TOKEN_SAMPLE {
+        sample.clear();
+        let tail_start = size.saturating_sub(CHANGE_TOKEN_SAMPLE).max(CHANGE_TOKEN_SAMPLE);
+        file.seek(SeekFrom::Start(tail_start)).map_err(read_error)?;
+        (&mut *file).take(CHANGE_TOKEN_SAMPLE).read_to_end(&mut sample).map_err(read_error)?;
+        hasher.update(&sample);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// A file of `size` bytes counting up from 0, in a directory of its own
+    fn write_temp(name: &str, size: usize) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("twice-pdf-pdf-file-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, (0..size).map(|i| i as u8).collect::<Vec<_>>()).unwrap();
+        path
+    }
+
+    #[test]
+    fn reads_ranges_up_to_the_end() {
+        let path = write_temp("range.bin", 1000);
+        let files = OpenFiles::default();
+
+        assert_eq!(files.read_range(&path, 10, 3).unwrap(), [10, 11, 12]);
+        assert_eq!(files.read_range(&path, 998, 100).unwrap(), [230, 231]);
+        assert!(files.read_range(&path, 5000, 10).unwrap().is_empty());
+        // Lengths are clamped to the file before anything is allocated
+        assert_eq!(files.read_range(&path, 990, u64::MAX).unwrap().len(), 10);
+        assert!(files.read_range(&path, u64::MAX, u64::MAX).unwrap().is_empty());
+    }
+
+    #[test]
+    fn long_ranges_stop_at_the_limit() {
+        let path = write_temp("long.bin", MAX_RANGE_LENGTH as usize + 100);
+        let files = OpenFiles::default();
+        let data = files.read_range(&path, 50, MAX_RANGE_LENGTH + 50).unwrap();
+        assert_eq!(data.len() as u64, MAX_RANGE_LENGTH);
+        assert_eq!(data[..2], [50, 51]);
+        assert_eq!(files.read_range(&path, 50 + MAX_RANGE_LENGTH, 100).unwrap().len(), 50);
+    }
+
+    #[test]
+    fn concurrent_reads_share_one_handle() {
+        let path = write_temp("shared.bin", 4096);
+        let files = OpenFiles::default();
+        std::thread::scope(|scope| {
+            for start in 0..8u64 {
+                let (files, path) = (&files, &path);
+                scope.spawn(move || {
+                    for offset in (start..4000).step_by(97) {
+                        assert_eq!(files.read_range(path, offset, 2).unwrap(), [offset as u8, (offset + 1) as u8]);
+                    }
+                });
+            }
+        });
+        assert_eq!(files.files.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn info_refreshes_the_cached_handle() {
+        let path = write_temp("refresh.bin", 100);
+        let files = OpenFiles::default();
+        assert_eq!(files.read_range(&path, 0, 1).unwrap(), [0]);
+
+        // Replaced like an atomic save does, so the old handle still sees the old file
+        let replacement = path.with_extension("new");
+        fs::write(&replacement, [7u8; 50]).unwrap();
+        fs::rename(&replacement, &path).unwrap();
+
+        assert_eq!(files.info(&path).unwrap().size, 50);
+        assert_eq!(files.read_range(&path, 0, 1).unwrap(), [7]);
+    }
+
+    #[test]
+    fn change_token_samples_both_ends() {
+        let size = 3 * CHANGE_TOKEN_SAMPLE as usize;
+        let path = write_temp("token.bin", size);
+        let token = |path: &Path| file_info(path, &mut File::open(path).unwrap()).unwrap().change_token;
+        let original = token(&path);
+        assert_eq!(token(&path), original);
+
+        let mut data = fs::read(&path).unwrap();
+        data[size - 1] ^= 1;
+        fs::write(&path, &data).unwrap();
+        let tail_changed = token(&path);
+        assert_ne!(tail_changed, original);
+
+        // The middle is not sampled: same-size edits there only show in the mtime
+        data[size / 2] ^= 1;
+        fs::write(&path, &data).unwrap();
+        assert_eq!(token(&path), tail_changed);
+    }
+}
@@ -0,0 +1,277 @@
+//! Recognise PDFs by content rather than by file extension
+//!
+//! A file is a PDF when `%PDF-` appears in its first 1024 bytes (the same
+//! leniency Acrobat has for junk before the header). A missing `%%EOF` near
+//! the end only marks the file as possibly truncated, since pdf.js can often
+//! still show it. Version, encryption and linearization come from a scan of
+//! the head and tail; files up to `PARSE_LIMIT` are also loaded with lopdf for
+//! the page count and to find out whether they need a password.
+
+use lopdf::{Document, Object};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Where the `%PDF-` header has to start
+const HEADER_WINDOW: usize = 1024;
+
+/// Bytes scanned at each end of the file
+const SCAN_WINDOW: u64 = 16 * 1024;
+
+/// Larger files are not parsed for their page count or a user password
+const PARSE_LIMIT: u64 = 256 * 1024 * 1024;
+
+#[derive(Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ProbeResult {
+    pub path: String,
+    pub is_pdf: bool,
+    /// Header version, e.g. "1.7"
+    pub version: Option<String>,
+    pub encrypted: bool,
+    /// Encrypted with a non-empty user password (cannot be opened without it)
+    pub password_required: bool,
+    pub linearized: bool,
+    /// No `%%EOF` near the end of the file, which may have been cut off
+    pub truncated: bool,
+    /// From the page tree, or the linearization dictionary when it cannot be read
+    pub page_count: Option<u32>,
+    pub size: u64,
+}
+
+/// Sniff `path` for the PDF header and read basic document facts
+pub fn probe_file(path: &Path) -> Result<ProbeResult, String> {
+    let read_error = |e: std::io::Error| format!("Failed to read file {}: {}", path.display(), e);
+    let mut file = File::open(path).map_err(read_error)?;
+    let size = file.metadata().map_err(read_error)?.len();
+    let (head, tail) = head_and_tail(&mut file, size).map_err(read_error)?;
+
+    let mut result = ProbeResult {
+        path: path.to_string_lossy().into_owned(),
+        size,
+        ..Default::default()
+    };
+    let Some(start) = find(&head[..head.len().min(HEADER_WINDOW)], b"%PDF-") else {
+        return Ok(result);
+    };
+    result.is_pdf = true;
+    result.truncated = !ends_like_pdf(&tail);
+    result.version = Some(
+        head[start + 5..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit() || **b == b'.')
+            .map(|&b| b as char)
+            .collect(),
+    )
+    .filter(|v: &String| !v.is_empty());
+
+    // The linearization dictionary has to be the first object in the file
+    let first_object = &head[start..head.len().min(start + HEADER_WINDOW)];
+    result.linearized = find(first_object, b"/Linearized").is_some();
+    if result.linearized {
+        result.page_count = integer_after(first_object, b"/N");
+    }
+
+    // Trailers live at the end, or near the start when linearized
+    result.encrypted = find(&tail, b"/Encrypt").is_some() || find(&head, b"/Encrypt").is_some();
+    if size <= PARSE_LIMIT {
+        if let Ok(doc) = Document::load(path) {
+            result.password_required = doc.trailer.get(b"Encrypt").is_ok() && doc.encryption_state.is_none();
+            if !result.password_required {
+                result.page_count = Some(doc.get_pages().len() as u32);
+            }
+            if let Ok(Object::Name(version)) = doc.catalog().and_then(|c| c.get(b"Version")) {
+                // A catalog /Version overrides the header when it is newer
+                result.version = Some(String::from_utf8_lossy(version).into_owned());
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Only check for the `%PDF-` header, for command line arguments and scanning many files at once
+pub fn is_pdf_file(path: &Path) -> bool {
+    let mut head = Vec::with_capacity(HEADER_WINDOW);
+    File::open(path)
+        .and_then(|file| file.take(HEADER_WINDOW as u64).read_to_end(&mut head))
+        .is_ok_and(|_| starts_like_pdf(&head))
+}
+
+fn head_and_tail(file: &mut File, size: u64) -> std::io::Result<(Vec<u8>, Vec<u8>)> {
+    let mut head = Vec::with_capacity(SCAN_WINDOW as usize);
+    (&mut *file).take(SCAN_WINDOW).read_to_end(&mut head)?;
+    let mut tail = Vec::with_capacity(SCAN_WINDOW as usize);
+    file.seek(SeekFrom::Start(size.saturating_sub(SCAN_WINDOW)))?;
+    file.take(SCAN_WINDOW).read_to_end(&mut tail)?;
+    Ok((head, tail))
+}
+
+/// Whether `data` has the `%PDF-` header within the first 1024 bytes
+pub fn starts_like_pdf(data: &[u8]) -> bool {
+    find(&data[..data.len().min(HEADER_WINDOW)], b"%PDF-").is_some()
+}
+
+/// Whether the end of a file holds the `%%EOF` marker (scanners and mailers may append junk after it)
+fn ends_like_pdf(tail: &[u8]) -> bool {
+    find(tail, b"%%EOF").is_some()
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// Integer value of a `/Key 123` entry (not `/KeyOther`)
+fn integer_after(data: &[u8], key: &[u8]) -> Option<u32> {
+    let mut offset = 0;
+    while let Some(pos) = find(&data[offset..], key) {
+        let rest = &data[offset + pos + key.len()..];
+        if rest.first().is_some_and(|b| b.is_ascii_whitespace()) {
+            let digits: String = rest
+                .iter()
+                .skip_while(|b| b.is_ascii_whitespace())
+                .take_while(|b| b.is_ascii_digit())
+                .map(|&b| b as char)
+                .collect();
+            return digits.parse().ok();
+        }
+        offset += pos + key.len();
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::encryption::{EncryptionState, EncryptionVersion, Permissions};
+    use lopdf::{dictionary, StringFormat};
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// Write `data` to a file of its own and return the path
+    fn write_temp(name: &str, data: &[u8]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("twice-pdf-probe-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, data).unwrap();
+        path
+    }
+
+    /// A one-page document, encrypted with `user_password` when it is given
+    fn saved_pdf(user_password: Option<&str>) -> Vec<u8> {
+        let mut doc = Document::with_version("1.6");
+        let pages_id = doc.new_object_id();
+        let page_id = doc.add_object(dictionary! { "Type" => "Page", "Parent" => pages_id });
+        let pages = dictionary! { "Type" => "Pages", "Kids" => vec![page_id.into()], "Count" => 1 };
+        doc.objects.insert(pages_id, Object::Dictionary(pages));
+        let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        doc.trailer.set("Root", catalog_id);
+        let id = Object::String(b"0123456789abcdef".to_vec(), StringFormat::Hexadecimal);
+        doc.trailer.set("ID", vec![id.clone(), id]);
+
+        if let Some(user_password) = user_password {
+            let version = EncryptionVersion::V2 {
+                document: &doc,
+                owner_password: "owner",
+                user_password,
+                key_length: 128,
+                permissions: Permissions::all(),
+            };
+            let state = EncryptionState::try_from(version).unwrap();
+            doc.encrypt(&state).unwrap();
+        }
+        let mut bytes = Vec::new();
+        doc.save_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn recognises_pdfs_by_content() {
+        let path = write_temp("no-extension", &saved_pdf(None));
+        let result = probe_file(&path).unwrap();
+
+        assert!(result.is_pdf && is_pdf_file(&path));
+        assert_eq!(result.version.as_deref(), Some("1.6"));
+        assert!(!result.encrypted && !result.linearized && !result.truncated);
+        assert_eq!(result.page_count, Some(1));
+        assert_eq!(result.size, fs::metadata(&path).unwrap().len());
+    }
+
+    #[test]
+    fn allows_junk_around_the_markers() {
+        let mut data = vec![b' '; 1000];
+        data.extend(saved_pdf(None));
+        data.extend(b"\r\n-- appended by a mail gateway --\r\n");
+        let path = write_temp("junk.pdf", &data);
+
+        assert!(probe_file(&path).unwrap().is_pdf);
+        assert!(is_pdf_file(&path));
+    }
+
+    #[test]
+    fn rejects_late_headers() {
+        let mut late = vec![b' '; HEADER_WINDOW];
+        late.extend(saved_pdf(None));
+        let late = write_temp("late.pdf", &late);
+        assert!(!probe_file(&late).unwrap().is_pdf && !is_pdf_file(&late));
+
+        let text = write_temp("notes.pdf", b"just some text\n%%EOF");
+        assert!(!is_pdf_file(&text));
+        assert!(!is_pdf_file(Path::new("/definitely/not/here.pdf")));
+    }
+
+    #[test]
+    fn truncated_files_are_still_pdfs() {
+        let full = saved_pdf(None);
+        let path = write_temp("truncated.pdf", &full[..full.len() / 2]);
+        let result = probe_file(&path).unwrap();
+        assert!(result.is_pdf && result.truncated && is_pdf_file(&path));
+        assert_eq!(result.version.as_deref(), Some("1.6"));
+    }
+
+    #[test]
+    fn counts_the_pages_of_ordinary_files() {
+        let mut doc = Document::load_mem(&saved_pdf(None)).unwrap();
+        let pages_id = doc.catalog().unwrap().get(b"Pages").unwrap().as_reference().unwrap();
+        let kids: Vec<Object> = (0..3)
+            .map(|_| doc.add_object(dictionary! { "Type" => "Page", "Parent" => pages_id }).into())
+            .collect();
+        let pages = doc.get_dictionary_mut(pages_id).unwrap();
+        pages.set("Kids", kids);
+        pages.set("Count", 3);
+        let mut data = Vec::new();
+        doc.save_to(&mut data).unwrap();
+
+        assert_eq!(probe_file(&write_temp("three.pdf", &data)).unwrap().page_count, Some(3));
+    }
+
+    #[test]
+    fn reads_the_linearization_dictionary() {
+        let mut data = b"%PDF-1.5\n%\xe2\xe3\xcf\xd3\n1 0 obj\n<< /Linearized 1 /L 5000 /NX 7 /N 42 /T 4000 >>\nendobj\n"
+            .to_vec();
+        data.extend(b"trailer\n<< /Size 2 >>\nstartxref\n0\n%%EOF\n");
+        let result = probe_file(&write_temp("linearized.pdf", &data)).unwrap();
+
+        assert!(result.is_pdf && result.linearized);
+        assert_eq!(result.page_count, Some(42));
+    }
+
+    #[test]
+    fn tells_whether_a_password_is_needed() {
+        let locked = probe_file(&write_temp("locked.pdf", &saved_pdf(Some("secret")))).unwrap();
+        assert!(locked.is_pdf && locked.encrypted && locked.password_required);
+        assert_eq!(locked.page_count, None);
+
+        // Encrypted with only an owner password: opens without asking
+        let restricted = probe_file(&write_temp("restricted.pdf", &saved_pdf(Some("")))).unwrap();
+        assert!(restricted.encrypted && !restricted.password_required);
+        assert_eq!(restricted.page_count, Some(1));
+    }
+
+    #[test]
+    fn integers_need_the_exact_key() {
+        assert_eq!(integer_after(b"/NX 7 /N 42", b"/N"), Some(42));
+        assert_eq!(integer_after(b"/N\n 3", b"/N"), Some(3));
+        assert_eq!(integer_after(b"/Names 3", b"/N"), None);
+    }
+}
@@ -0,0 +1,471 @@
+//! Review queues for working through many document pairs
+//!
+//! `twice-pdf queue` pairs the PDFs of two folders (by file stem, or by a key
+//! taken from each file name with `--pattern`) or reads explicit pairs from a
+//! CSV manifest. The reviewer steps through the pairs and marks each one
+//! reviewed or flagged. Progress is saved to `<app data>/queues/<id>.json`
+//! after every change, `id` being a hash of the queue's folders or manifest,
+//! so running the same command again resumes where the last session stopped.
+
+use crate::atomic_write::{self, FileError};
+use crate::probe;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PairStatus {
+    #[default]
+    Pending,
+    Reviewed,
+    Flagged,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct QueuePair {
+    pub left: String,
+    pub right: String,
+    #[serde(default)]
+    pub status: PairStatus,
+}
+
+/// Queue state sent to the frontend, also the format of the progress file
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Queue {
+    /// Folder or manifest names, for display
+    pub name: String,
+    /// Index of the pair on screen
+    pub current: usize,
+    pub pairs: Vec<QueuePair>,
+    /// Files in either folder that found no partner
+    #[serde(default)]
+    pub unmatched: Vec<String>,
+}
+
+impl Queue {
+    pub fn current_pair(&self) -> Option<&QueuePair> {
+        self.pairs.get(self.current)
+    }
+}
+
+/// A queue as built from the command line, before saved progress is applied
+#[derive(Debug, Clone)]
+pub struct QueueSource {
+    /// Names the progress file
+    pub id: String,
+    pub queue: Queue,
+}
+
+/// Pair the PDFs of two folders whose names give the same key: the file stem,
+/// or with `pattern` the `key` group, first capture group or whole match
+pub fn from_folders(left: &Path, right: &Path, pattern: Option<&str>) -> Result<QueueSource, String> {
+    let regex = pattern
+        .map(Regex::new)
+        .transpose()
+        .map_err(|e| format!("invalid --pattern: {}", e))?;
+    let left = canonical_dir(left)?;
+    let right = canonical_dir(right)?;
+    let left_files = keyed_files(&left, regex.as_ref())?;
+    let right_files = keyed_files(&right, regex.as_ref())?;
+
+    let mut pairs = Vec::new();
+    let mut unmatched = Vec::new();
+    for (key, path) in &left_files {
+        match right_files.get(key) {
+            Some(partner) => pairs.push(QueuePair {
+                left: path_string(path),
+                right: path_string(partner),
+                status: PairStatus::Pending,
+            }),
+            None => unmatched.push(path_string(path)),
+        }
+    }
+    unmatched.extend(
+        right_files
+            .iter()
+            .filter(|(key, _)| !left_files.contains_key(*key))
+            .map(|(_, path)| path_string(path)),
+    );
+    if pairs.is_empty() {
+        return Err(format!("no PDFs in {} and {} pair up", left.display(), right.display()));
+    }
+
+    Ok(QueueSource {
+        id: queue_id(&["folders", &path_string(&left), &path_string(&right), pattern.unwrap_or("")]),
+        queue: Queue {
+            name: format!("{} ↔ {}", display_name(&left), display_name(&right)),
+            current: 0,
+            pairs,
+            unmatched,
+        },
+    })
+}
+
+/// Read `left,right` pairs from a CSV file; relative paths are resolved against its folder.
+/// An optional `left,right` header row is skipped.
+pub fn from_manifest(path: &Path) -> Result<QueueSource, String> {
+    let manifest = fs::canonicalize(path).map_err(|e| format!("Failed to read manifest {}: {}", path.display(), e))?;
+    let base = manifest.parent().unwrap_or(Path::new("")).to_path_buf();
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_path(&manifest)
+        .map_err(|e| format!("Failed to read manifest {}: {}", manifest.display(), e))?;
+
+    let mut pairs = Vec::new();
+    for (index, record) in reader.records().enumerate() {
+        let record = record.map_err(|e| format!("Invalid manifest {}: {}", manifest.display(), e))?;
+        let row = index + 1;
+        if index == 0 && record.get(0).is_some_and(|f| f.eq_ignore_ascii_case("left")) {
+            continue;
+        }
+        let file = |column: usize| -> Result<String, String> {
+            let field = record
+                .get(column)
+                .filter(|f| !f.is_empty())
+                .ok_or_else(|| format!("{} row {}: expected `left,right`", manifest.display(), row))?;
+            let file = base.join(field);
+            if !file.is_file() {
+                return Err(format!("{} row {}: no such file: {}", manifest.display(), row, file.display()));
+            }
+            if !probe::is_pdf_file(&file) {
+                return Err(format!("{} row {}: not a PDF file: {}", manifest.display(), row, file.display()));
+            }
+            Ok(path_string(&file))
+        };
+        pairs.push(QueuePair { left: file(0)?, right: file(1)?, status: PairStatus::Pending });
+    }
+    if pairs.is_empty() {
+        return Err(format!("manifest {} lists no pairs", manifest.display()));
+    }
+
+    Ok(QueueSource {
+        id: queue_id(&["manifest", &path_string(&manifest)]),
+        queue: Queue {
+            name: display_name(&manifest),
+            current: 0,
+            pairs,
+            unmatched: Vec::new(),
+        },
+    })
+}
+
+fn canonical_dir(dir: &Path) -> Result<PathBuf, String> {
+    match fs::canonicalize(dir) {
+        Ok(dir) if dir.is_dir() => Ok(dir),
+        Ok(_) => Err(format!("not a folder: {}", dir.display())),
+        Err(e) => Err(format!("Failed to read folder {}: {}", dir.display(), e)),
+    }
+}
+
+/// PDFs directly inside `dir` by pairing key; files the pattern does not match are left out
+fn keyed_files(dir: &Path, pattern: Option<&Regex>) -> Result<BTreeMap<String, PathBuf>, String> {
+    let read_error = |e: std::io::Error| format!("Failed to read folder {}: {}", dir.display(), e);
+    let mut files: BTreeMap<String, PathBuf> = BTreeMap::new();
+    for entry in fs::read_dir(dir).map_err(read_error)? {
+        let path = entry.map_err(read_error)?.path();
+        if !path.is_file() || !probe::is_pdf_file(&path) {
+            continue;
+        }
+        let key = match pattern {
+            Some(pattern) => {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                let Some(captures) = pattern.captures(&name) else {
+                    continue;
+                };
+                let key = captures.name("key").or_else(|| captures.get(1)).or_else(|| captures.get(0));
+                key.map_or(String::new(), |m| m.as_str().to_string())
+            }
+            None => path.file_stem().unwrap_or_default().to_string_lossy().into_owned(),
+        };
+        if let Some(other) = files.get(&key) {
+            return Err(format!(
+                "{} and {} both pair as `{}`",
+                path_string(other),
+                path_string(&path),
+                key
+            ));
+        }
+        files.insert(key, path);
+    }
+    Ok(files)
+}
+
+fn queue_id(parts: &[&str]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    hex::encode(&hasher.finalize()[..16])
+}
+
+fn display_name(path: &Path) -> String {
+    path.file_name()
+        .map_or_else(|| path_string(path), |name| name.to_string_lossy().into_owned())
+}
+
+fn path_string(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+struct Loaded {
+    progress: PathBuf,
+    queue: Queue,
+}
+
+/// The queue opened on the command line, if any (managed Tauri state)
+#[derive(Default)]
+pub struct ReviewQueue {
+    loaded: Mutex<Option<Loaded>>,
+}
+
+impl ReviewQueue {
+    /// Start `source`, carrying over statuses and position saved in `dir` by an earlier session
+    pub fn load(source: QueueSource, dir: &Path) -> Self {
+        let progress = dir.join(format!("{}.json", source.id));
+        let mut queue = source.queue;
+        match read_progress(&progress) {
+            Ok(Some(saved)) => {
+                let statuses: HashMap<_, _> = saved
+                    .pairs
+                    .iter()
+                    .map(|pair| ((pair.left.as_str(), pair.right.as_str()), pair.status))
+                    .collect();
+                for pair in &mut queue.pairs {
+                    if let Some(status) = statuses.get(&(pair.left.as_str(), pair.right.as_str())) {
+                        pair.status = *status;
+                    }
+                }
+                // Pairs may have been added to the folders since, so find the pair rather than the index
+                if let Some(current) = saved.current_pair() {
+                    queue.current = queue
+                        .pairs
+                        .iter()
+                        .position(|pair| pair.left == current.left && pair.right == current.right)
+                        .unwrap_or(0);
+                }
+            }
+            Ok(None) => {}
+            Err(e) => log::warn!("Ignoring queue progress: {}", e),
+        }
+        ReviewQueue { loaded: Mutex::new(Some(Loaded { progress, queue })) }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Option<Loaded>> {
+        self.loaded.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn get(&self) -> Option<Queue> {
+        self.lock().as_ref().map(|loaded| loaded.queue.clone())
+    }
+
+    /// Apply `change`, save the progress and return the new state.
+    /// Nothing changes when the progress cannot be written.
+    pub fn update(&self, change: impl FnOnce(&mut Queue) -> Result<(), String>) -> Result<Queue, FileError> {
+        let mut guard = self.lock();
+        let loaded = guard
+            .as_mut()
+            .ok_or_else(|| FileError::other(Path::new(""), "No review queue is open".to_string()))?;
+        let mut queue = loaded.queue.clone();
+        change(&mut queue).map_err(|e| FileError::other(&loaded.progress, e))?;
+
+        let json = serde_json::to_vec_pretty(&queue)
+            .map_err(|e| FileError::other(&loaded.progress, format!("Failed to serialise queue: {}", e)))?;
+        if let Some(dir) = loaded.progress.parent() {
+            fs::create_dir_all(dir).map_err(|e| FileError::io("write", dir, e))?;
+        }
+        atomic_write::write_file(&loaded.progress, &json, false)?;
+        loaded.queue = queue.clone();
+        Ok(queue)
+    }
+}
+
+fn read_progress(path: &Path) -> Result<Option<Queue>, String> {
+    match fs::read(path) {
+        Ok(json) => serde_json::from_slice(&json)
+            .map(Some)
+            .map_err(|e| format!("{}: {}", path.display(), e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PDF: &[u8] = b"%PDF-1.7\n%%EOF\n";
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("twice-pdf-queue-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        // Canonical, so paths compare equal with the queue's
+        fs::canonicalize(dir).unwrap()
+    }
+
+    /// `dir/<folder>/<name>` for each name, all PDFs
+    fn folder(dir: &Path, folder: &str, names: &[&str]) -> PathBuf {
+        let folder = dir.join(folder);
+        fs::create_dir_all(&folder).unwrap();
+        for name in names {
+            fs::write(folder.join(name), PDF).unwrap();
+        }
+        folder
+    }
+
+    fn file_names(paths: &[String]) -> Vec<String> {
+        paths.iter().map(|p| display_name(Path::new(p))).collect()
+    }
+
+    fn pair_names(queue: &Queue) -> Vec<(String, String)> {
+        queue
+            .pairs
+            .iter()
+            .map(|p| (display_name(Path::new(&p.left)), display_name(Path::new(&p.right))))
+            .collect()
+    }
+
+    #[test]
+    fn pairs_folders_by_stem() {
+        let dir = test_dir("stem");
+        let left = folder(&dir, "v1", &["a.pdf", "b.pdf", "only-left.pdf"]);
+        let right = folder(&dir, "v2", &["a.pdf", "b.pdf", "only-right.pdf"]);
+        fs::write(left.join("notes.txt"), "not a pdf").unwrap();
+
+        let source = from_folders(&left, &right, None).unwrap();
+        assert_eq!(source.queue.name, "v1 ↔ v2");
+        assert_eq!(pair_names(&source.queue), [("a.pdf".into(), "a.pdf".into()), ("b.pdf".into(), "b.pdf".into())]);
+        assert_eq!(file_names(&source.queue.unmatched), ["only-left.pdf", "only-right.pdf"]);
+        assert!(source.queue.pairs.iter().all(|p| p.status == PairStatus::Pending));
+    }
+
+    #[test]
+    fn pairs_folders_by_pattern_key() {
+        let dir = test_dir("pattern");
+        let left = folder(&dir, "left", &["INV-001_draft.pdf", "INV-002_draft.pdf", "readme.pdf"]);
+        let right = folder(&dir, "right", &["INV-002_final.pdf", "INV-001_final.pdf", "INV-003_final.pdf"]);
+
+        let source = from_folders(&left, &right, Some(r"^(?<key>INV-\d+)_")).unwrap();
+        assert_eq!(
+            pair_names(&source.queue),
+            [
+                ("INV-001_draft.pdf".into(), "INV-001_final.pdf".into()),
+                ("INV-002_draft.pdf".into(), "INV-002_final.pdf".into()),
+            ]
+        );
+        // Files the pattern does not match are left out entirely
+        assert_eq!(file_names(&source.queue.unmatched), ["INV-003_final.pdf"]);
+
+        // Without a named group the first group is the key
+        let by_group = from_folders(&left, &right, Some(r"(INV-\d+)")).unwrap();
+        assert_eq!(by_group.queue.pairs, source.queue.pairs);
+        // The pattern is part of the queue's identity
+        assert_ne!(by_group.id, source.id);
+    }
+
+    #[test]
+    fn rejects_duplicate_keys_and_bad_patterns() {
+        let dir = test_dir("duplicate");
+        let left = folder(&dir, "left", &["a_v1.pdf", "a_v2.pdf"]);
+        let right = folder(&dir, "right", &["a.pdf"]);
+
+        let error = from_folders(&left, &right, Some(r"^([a-z]+)_")).unwrap_err();
+        assert!(error.contains("both pair as `a`"), "{}", error);
+        assert!(from_folders(&left, &right, Some("(")).unwrap_err().starts_with("invalid --pattern"));
+        assert!(from_folders(&left, &right, None).unwrap_err().starts_with("no PDFs"));
+        assert!(from_folders(&left, &dir.join("missing"), None).is_err());
+    }
+
+    #[test]
+    fn reads_manifests_with_or_without_a_header() {
+        let dir = test_dir("manifest");
+        folder(&dir, "docs", &["a.pdf", "b.pdf", "with, comma.pdf"]);
+        let manifest = dir.join("pairs.csv");
+
+        fs::write(&manifest, "Left,Right\ndocs/a.pdf, docs/b.pdf\n\"docs/with, comma.pdf\",docs/a.pdf\n").unwrap();
+        let source = from_manifest(&manifest).unwrap();
+        assert_eq!(source.queue.name, "pairs.csv");
+        assert_eq!(
+            pair_names(&source.queue),
+            [("a.pdf".into(), "b.pdf".into()), ("with, comma.pdf".into(), "a.pdf".into())]
+        );
+        assert_eq!(source.queue.pairs[0].left, path_string(&dir.join("docs").join("a.pdf")));
+
+        fs::write(&manifest, "docs/b.pdf,docs/a.pdf\n").unwrap();
+        assert_eq!(pair_names(&from_manifest(&manifest).unwrap().queue), [("b.pdf".into(), "a.pdf".into())]);
+    }
+
+    #[test]
+    fn rejects_bad_manifest_rows() {
+        let dir = test_dir("bad-manifest");
+        let docs = folder(&dir, "docs", &["a.pdf"]);
+        fs::write(docs.join("text.pdf"), "plain text").unwrap();
+        let manifest = dir.join("pairs.csv");
+
+        for (contents, expected) in [
+            ("docs/a.pdf\n", "row 1: expected `left,right`"),
+            ("docs/a.pdf,docs/missing.pdf\n", "row 1: no such file"),
+            ("left,right\ndocs/a.pdf,docs/text.pdf\n", "row 2: not a PDF file"),
+            ("left,right\n", "lists no pairs"),
+        ] {
+            fs::write(&manifest, contents).unwrap();
+            let error = from_manifest(&manifest).unwrap_err();
+            assert!(error.contains(expected), "{:?}: {}", contents, error);
+        }
+    }
+
+    #[test]
+    fn progress_survives_a_restart() {
+        let dir = test_dir("progress");
+        let left = folder(&dir, "left", &["a.pdf", "b.pdf"]);
+        let right = folder(&dir, "right", &["a.pdf", "b.pdf"]);
+        let store = dir.join("queues");
+
+        let queue = ReviewQueue::load(from_folders(&left, &right, None).unwrap(), &store);
+        let updated = queue
+            .update(|q| {
+                q.pairs[0].status = PairStatus::Flagged;
+                q.current = 1;
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(updated.current, 1);
+        // A failed change leaves the queue as it was
+        assert!(queue.update(|_| Err("nope".into())).is_err());
+        assert_eq!(queue.get().unwrap().current, 1);
+
+        // A new pair sorted in front: statuses and the current pair follow the files
+        fs::write(left.join("0.pdf"), PDF).unwrap();
+        fs::write(right.join("0.pdf"), PDF).unwrap();
+        let queue = ReviewQueue::load(from_folders(&left, &right, None).unwrap(), &store);
+        let resumed = queue.get().unwrap();
+        let statuses: Vec<_> = resumed.pairs.iter().map(|p| p.status).collect();
+        assert_eq!(statuses, [PairStatus::Pending, PairStatus::Flagged, PairStatus::Pending]);
+        assert_eq!(display_name(Path::new(&resumed.current_pair().unwrap().left)), "b.pdf");
+    }
+
+    #[test]
+    fn corrupt_progress_is_ignored() {
+        let dir = test_dir("corrupt");
+        let left = folder(&dir, "left", &["a.pdf"]);
+        let right = folder(&dir, "right", &["a.pdf"]);
+        let source = from_folders(&left, &right, None).unwrap();
+        let store = dir.join("queues");
+        fs::create_dir_all(&store).unwrap();
+        fs::write(store.join(format!("{}.json", source.id)), "{ truncated").unwrap();
+
+        let queue = ReviewQueue::load(source, &store);
+        assert_eq!(queue.get().unwrap().pairs[0].status, PairStatus::Pending);
+        assert!(ReviewQueue::default().get().is_none());
+        assert!(ReviewQueue::default().update(|_| Ok(())).is_err());
+    }
+}
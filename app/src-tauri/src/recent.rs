@@ -0,0 +1,346 @@
+//! Recently opened files and comparison pairs
+//!
+//! Every local document a window shows is recorded in `<app data>/recent.json`
+//! with the time, the page it was left on and its fingerprint, and so is every
+//! left/right pair. Pinned entries stay on top and survive clearing the list.
+//! Entries whose files no longer exist are dropped whenever the list changes
+//! or is read.
+
+use crate::atomic_write::{self, FileError};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Sent to every window with the new [`RecentList`]
+pub const RECENT_CHANGED_EVENT: &str = "recent-changed";
+/// Sent to the focused window with the [`RecentEntry`] picked in File ▸ Recent
+pub const OPEN_RECENT_EVENT: &str = "open-recent";
+
+/// Unpinned entries kept; older ones fall off the end
+const MAX_FILES: usize = 20;
+const MAX_PAIRS: usize = 10;
+
+/// A document a window shows, as the frontend reports it
+#[derive(Deserialize, Debug)]
+pub struct RecentInput {
+    pub path: String,
+    pub page: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RecentDocument {
+    pub path: String,
+    /// 1-based page shown last
+    pub page: u32,
+    /// See `annotation_store::document_key`
+    pub fingerprint: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RecentFile {
+    #[serde(flatten)]
+    pub document: RecentDocument,
+    /// Milliseconds since the Unix epoch
+    pub opened: u64,
+    #[serde(default)]
+    pub pinned: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RecentPair {
+    pub left: RecentDocument,
+    pub right: RecentDocument,
+    /// Milliseconds since the Unix epoch
+    pub opened: u64,
+    #[serde(default)]
+    pub pinned: bool,
+}
+
+/// Pinned entries first, then the most recent
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RecentList {
+    pub files: Vec<RecentFile>,
+    pub pairs: Vec<RecentPair>,
+}
+
+/// One entry, as sent to the frontend when it is picked from the menu
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum RecentEntry {
+    File(RecentFile),
+    Pair(RecentPair),
+}
+
+impl RecentEntry {
+    pub fn paths(&self) -> Vec<&str> {
+        match self {
+            RecentEntry::File(file) => vec![file.document.path.as_str()],
+            RecentEntry::Pair(pair) => vec![pair.left.path.as_str(), pair.right.path.as_str()],
+        }
+    }
+}
+
+impl RecentList {
+    /// Local paths of every entry
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.files
+            .iter()
+            .map(|file| &file.document)
+            .chain(self.pairs.iter().flat_map(|pair| [&pair.left, &pair.right]))
+            .map(|document| document.path.as_str())
+    }
+
+    fn tidy(&mut self) {
+        let exists = |document: &RecentDocument| Path::new(&document.path).is_file();
+        self.files.retain(|file| exists(&file.document));
+        self.pairs.retain(|pair| exists(&pair.left) && exists(&pair.right));
+
+        self.files.sort_by_key(|file| (Reverse(file.pinned), Reverse(file.opened)));
+        self.pairs.sort_by_key(|pair| (Reverse(pair.pinned), Reverse(pair.opened)));
+        let mut unpinned = 0;
+        self.files.retain(|file| {
+            unpinned += usize::from(!file.pinned);
+            file.pinned || unpinned <= MAX_FILES
+        });
+        let mut unpinned = 0;
+        self.pairs.retain(|pair| {
+            unpinned += usize::from(!pair.pinned);
+            pair.pinned || unpinned <= MAX_PAIRS
+        });
+    }
+}
+
+/// The recent list (managed Tauri state)
+pub struct Recent {
+    path: PathBuf,
+    list: Mutex<RecentList>,
+}
+
+impl Recent {
+    /// Read `path`; a missing or unreadable file starts an empty list
+    pub fn load(path: PathBuf) -> Self {
+        let list = match fs::read(&path) {
+            Ok(json) => serde_json::from_slice(&json).unwrap_or_else(|e| {
+                log::warn!("Ignoring invalid recent files list {}: {}", path.display(), e);
+                RecentList::default()
+            }),
+            Err(e) if e.kind() == ErrorKind::NotFound => RecentList::default(),
+            Err(e) => {
+                log::warn!("Cannot read recent files list {}: {}", path.display(), e);
+                RecentList::default()
+            }
+        };
+        Recent { path, list: Mutex::new(list) }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, RecentList> {
+        self.list.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// The list as last saved (and shown in the menu)
+    pub fn current(&self) -> RecentList {
+        self.lock().clone()
+    }
+
+    /// The entries whose files still exist
+    pub fn list(&self) -> Result<RecentList, FileError> {
+        self.update(|_| {})
+    }
+
+    /// Move the documents (and their pair when both are given) to the top, with their current page
+    pub fn record(&self, left: Option<RecentDocument>, right: Option<RecentDocument>) -> Result<RecentList, FileError> {
+        let opened = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        // New entries go in front so they stay ahead of entries from the same millisecond
+        self.update(|list| {
+            let mut recorded = Vec::new();
+            for document in [&left, &right].into_iter().flatten() {
+                let pinned = list.files.iter().any(|file| file.document.path == document.path && file.pinned);
+                list.files.retain(|file| file.document.path != document.path);
+                recorded.push(RecentFile { document: document.clone(), opened, pinned });
+            }
+            list.files.splice(0..0, recorded);
+            if let (Some(left), Some(right)) = (left, right) {
+                let same = |pair: &RecentPair| pair.left.path == left.path && pair.right.path == right.path;
+                let pinned = list.pairs.iter().any(|pair| same(pair) && pair.pinned);
+                list.pairs.retain(|pair| !same(pair));
+                list.pairs.insert(0, RecentPair { left, right, opened, pinned });
+            }
+        })
+    }
+
+    pub fn pin_file(&self, path: &str, pinned: bool) -> Result<RecentList, FileError> {
+        self.update(|list| {
+            for file in list.files.iter_mut().filter(|file| file.document.path == path) {
+                file.pinned = pinned;
+            }
+        })
+    }
+
+    pub fn pin_pair(&self, left: &str, right: &str, pinned: bool) -> Result<RecentList, FileError> {
+        self.update(|list| {
+            for pair in list.pairs.iter_mut().filter(|pair| pair.left.path == left && pair.right.path == right) {
+                pair.pinned = pinned;
+            }
+        })
+    }
+
+    /// Forget everything except the pinned entries
+    pub fn clear(&self) -> Result<RecentList, FileError> {
+        self.update(|list| {
+            list.files.retain(|file| file.pinned);
+            list.pairs.retain(|pair| pair.pinned);
+        })
+    }
+
+    /// Apply `change`, prune and save when anything changed
+    fn update(&self, change: impl FnOnce(&mut RecentList)) -> Result<RecentList, FileError> {
+        let mut list = self.lock();
+        let mut updated = list.clone();
+        change(&mut updated);
+        updated.tidy();
+        if updated != *list {
+            let json = serde_json::to_vec_pretty(&updated)
+                .map_err(|e| FileError::other(&self.path, format!("Failed to serialise recent files: {}", e)))?;
+            if let Some(dir) = self.path.parent() {
+                fs::create_dir_all(dir).map_err(|e| FileError::io("write", dir, e))?;
+            }
+            atomic_write::write_file(&self.path, &json, false)?;
+            *list = updated;
+        }
+        Ok(list.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("twice-pdf-recent-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Documents for files `0.pdf`, `1.pdf`, ... created in `dir`
+    fn documents(dir: &Path, count: usize) -> Vec<RecentDocument> {
+        (0..count)
+            .map(|i| {
+                let path = dir.join(format!("{}.pdf", i));
+                fs::write(&path, b"%PDF-1.7\n").unwrap();
+                RecentDocument { path: path_string(&path), page: 1, fingerprint: format!("id-{:02x}", i) }
+            })
+            .collect()
+    }
+
+    fn path_string(path: &Path) -> String {
+        path.to_string_lossy().into_owned()
+    }
+
+    fn file_paths(list: &RecentList) -> Vec<&str> {
+        list.files.iter().map(|file| file.document.path.as_str()).collect()
+    }
+
+    #[test]
+    fn record_moves_documents_to_the_front() {
+        let dir = test_dir("record");
+        let docs = documents(&dir, 3);
+        let recent = Recent::load(dir.join("recent.json"));
+
+        recent.record(Some(docs[0].clone()), None).unwrap();
+        recent.record(Some(docs[1].clone()), Some(docs[2].clone())).unwrap();
+        let mut revisited = docs[0].clone();
+        revisited.page = 7;
+        let list = recent.record(Some(revisited), None).unwrap();
+
+        assert_eq!(file_paths(&list), [&docs[0].path, &docs[1].path, &docs[2].path]);
+        assert_eq!(list.files[0].document.page, 7);
+        assert_eq!(list.pairs.len(), 1);
+        assert_eq!((&list.pairs[0].left, &list.pairs[0].right), (&docs[1], &docs[2]));
+
+        // Saved and read back
+        assert_eq!(Recent::load(dir.join("recent.json")).current(), list);
+    }
+
+    #[test]
+    fn pinned_entries_stay_on_top_and_survive_clearing() {
+        let dir = test_dir("pin");
+        let docs = documents(&dir, 3);
+        let recent = Recent::load(dir.join("recent.json"));
+        recent.record(Some(docs[0].clone()), Some(docs[1].clone())).unwrap();
+        recent.record(Some(docs[2].clone()), None).unwrap();
+
+        let list = recent.pin_file(&docs[1].path, true).unwrap();
+        assert_eq!(file_paths(&list), [&docs[1].path, &docs[2].path, &docs[0].path]);
+        recent.pin_pair(&docs[0].path, &docs[1].path, true).unwrap();
+
+        // Recording a pinned document keeps the pin
+        let list = recent.record(Some(docs[1].clone()), None).unwrap();
+        assert!(list.files[0].pinned);
+
+        let list = recent.clear().unwrap();
+        assert_eq!(file_paths(&list), [&docs[1].path]);
+        assert_eq!(list.pairs.len(), 1);
+
+        recent.pin_file(&docs[1].path, false).unwrap();
+        let list = recent.pin_pair(&docs[0].path, &docs[1].path, false).unwrap();
+        assert!(!list.files[0].pinned && !list.pairs[0].pinned);
+        assert_eq!(recent.clear().unwrap(), RecentList::default());
+    }
+
+    #[test]
+    fn tidy_drops_missing_files() {
+        let dir = test_dir("missing");
+        let docs = documents(&dir, 3);
+        let recent = Recent::load(dir.join("recent.json"));
+        recent.record(Some(docs[0].clone()), Some(docs[1].clone())).unwrap();
+        recent.record(Some(docs[2].clone()), None).unwrap();
+        recent.pin_file(&docs[1].path, true).unwrap();
+
+        // Pinned or not, and with the pairs they belong to
+        fs::remove_file(&docs[1].path).unwrap();
+        let list = recent.list().unwrap();
+        assert_eq!(file_paths(&list), [&docs[2].path, &docs[0].path]);
+        assert!(list.pairs.is_empty());
+    }
+
+    #[test]
+    fn tidy_caps_unpinned_entries() {
+        let dir = test_dir("cap");
+        let docs = documents(&dir, MAX_FILES + 5);
+        let recent = Recent::load(dir.join("recent.json"));
+        recent.record(Some(docs[0].clone()), None).unwrap();
+        recent.pin_file(&docs[0].path, true).unwrap();
+        for pair in docs[1..].chunks(2) {
+            recent.record(Some(pair[0].clone()), pair.get(1).cloned()).unwrap();
+        }
+
+        let list = recent.current();
+        assert_eq!(list.files.len(), MAX_FILES + 1);
+        assert_eq!(list.files[0].document.path, docs[0].path);
+        assert_eq!(list.files.iter().filter(|file| !file.pinned).count(), MAX_FILES);
+        // The oldest unpinned entries fell off
+        assert!(!file_paths(&list).contains(&docs[1].path.as_str()));
+        assert_eq!(list.pairs.len(), MAX_PAIRS);
+        assert_eq!(list.pairs[0].left.path, docs[MAX_FILES + 3].path);
+    }
+
+    #[test]
+    fn invalid_lists_start_empty() {
+        let dir = test_dir("invalid");
+        fs::write(dir.join("recent.json"), "[not json").unwrap();
+        assert_eq!(Recent::load(dir.join("recent.json")).current(), RecentList::default());
+        assert_eq!(Recent::load(dir.join("missing.json")).current(), RecentList::default());
+    }
+}
@@ -0,0 +1,482 @@
+//! Download remote PDFs in the desktop app
+//!
+//! A port of `protectedFetch` from the Vite dev bridge (`vite.config.js`), so
+//! the desktop app no longer sends document URLs through public CORS proxies.
+//! Only http(s) URLs whose host resolves to public addresses are fetched, and
+//! the connection goes to exactly the addresses that were checked (DNS
+//! pinning), so a rebinding DNS server cannot swap in an internal one.
+//! Redirects are followed by hand and every hop is checked the same way.
+//! Downloads are capped in size and time and must be PDFs.
+
+use crate::probe;
+use reqwest::{header, redirect, Client, Response, StatusCode, Url};
+use serde::Serialize;
+use std::error::Error;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::{Duration, Instant};
+use url::Host;
+
+const USER_AGENT: &str = concat!("Twice-PDF/", env!("CARGO_PKG_VERSION"));
+
+/// Content types servers use for PDFs; anything else (HTML error pages, images) is refused
+const PDF_CONTENT_TYPES: &[&str] = &[
+    "application/pdf",
+    "application/x-pdf",
+    "application/acrobat",
+    "application/octet-stream",
+    "binary/octet-stream",
+    "application/download",
+    "application/x-download",
+    "application/force-download",
+];
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum FetchErrorKind {
+    /// Not an absolute http(s) URL
+    InvalidUrl,
+    /// The host is or resolves to a loopback, private, link-local or otherwise internal address
+    Blocked,
+    /// DNS, connection or TLS failure
+    Network,
+    Timeout,
+    /// The server answered with something other than 200 OK, or redirected too often
+    Http,
+    TooLarge,
+    /// Neither the content type nor the content is a PDF
+    NotPdf,
+}
+
+/// Download error returned to the frontend as `{ kind, url, message, status }`
+#[derive(Serialize, Debug, Clone)]
+pub struct FetchError {
+    pub kind: FetchErrorKind,
+    pub url: String,
+    pub message: String,
+    /// HTTP status, for `Http` errors
+    pub status: Option<u16>,
+}
+
+impl FetchError {
+    fn new(kind: FetchErrorKind, url: &str, message: String) -> Self {
+        FetchError { kind, url: url.to_string(), message, status: None }
+    }
+
+    fn request(url: &Url, err: reqwest::Error) -> Self {
+        let kind = if err.is_timeout() { FetchErrorKind::Timeout } else { FetchErrorKind::Network };
+        // reqwest's own message leaves out the cause ("connection refused", "invalid certificate")
+        let mut message = format!("Failed to download {}", url);
+        let mut source = err.source();
+        while let Some(cause) = source {
+            message.push_str(&format!(": {}", cause));
+            source = cause.source();
+        }
+        FetchError::new(kind, url.as_str(), message)
+    }
+}
+
+/// Limits of a download and the addresses it may connect to
+pub struct Fetcher {
+    pub max_size: u64,
+    pub max_redirects: usize,
+    pub connect_timeout: Duration,
+    /// For the whole download, redirects included
+    pub timeout: Duration,
+    pub allow_address: fn(IpAddr) -> bool,
+}
+
+impl Default for Fetcher {
+    fn default() -> Self {
+        Fetcher {
+            max_size: 200 * 1024 * 1024,
+            max_redirects: 5,
+            connect_timeout: Duration::from_secs(10),
+            timeout: Duration::from_secs(120),
+            allow_address: is_public,
+        }
+    }
+}
+
+impl Fetcher {
+    /// Download the PDF at `url`
+    pub async fn fetch(&self, url: &str) -> Result<Vec<u8>, FetchError> {
+        let deadline = Instant::now() + self.timeout;
+        let mut url = parse_url(url)?;
+        for _ in 0..=self.max_redirects {
+            let response = self.get(&url, deadline).await?;
+            if !response.status().is_redirection() {
+                return self.read_pdf(&url, response).await;
+            }
+            let location = response
+                .headers()
+                .get(header::LOCATION)
+                .and_then(|location| location.to_str().ok())
+                .and_then(|location| url.join(location).ok())
+                .ok_or_else(|| {
+                    FetchError::new(
+                        FetchErrorKind::Http,
+                        url.as_str(),
+                        format!("{} redirected without a valid location", url),
+                    )
+                })?;
+            log::info!("Following redirect to {}", location);
+            url = parse_url(location.as_str())?;
+        }
+        Err(FetchError::new(
+            FetchErrorKind::Http,
+            url.as_str(),
+            format!("Too many redirects (more than {})", self.max_redirects),
+        ))
+    }
+
+    /// One request, without following redirects, to the checked addresses of the host
+    async fn get(&self, url: &Url, deadline: Instant) -> Result<Response, FetchError> {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let mut client = Client::builder()
+            .redirect(redirect::Policy::none())
+            // A proxy would resolve the host itself
+            .no_proxy()
+            .connect_timeout(self.connect_timeout)
+            .timeout(remaining)
+            .user_agent(USER_AGENT);
+
+        let blocked = |ip: IpAddr| {
+            FetchError::new(
+                FetchErrorKind::Blocked,
+                url.as_str(),
+                format!("Blocked: {} is a restricted address ({})", url.host_str().unwrap_or_default(), ip),
+            )
+        };
+        match url.host() {
+            Some(Host::Ipv4(ip)) if !(self.allow_address)(ip.into()) => return Err(blocked(ip.into())),
+            Some(Host::Ipv6(ip)) if !(self.allow_address)(ip.into()) => return Err(blocked(ip.into())),
+            Some(Host::Domain(domain)) => {
+                let addresses = resolve(url, domain, remaining).await?;
+                if let Some(ip) = addresses.iter().map(SocketAddr::ip).find(|ip| !(self.allow_address)(*ip)) {
+                    return Err(blocked(ip));
+                }
+                client = client.resolve_to_addrs(domain, &addresses);
+            }
+            _ => {}
+        }
+
+        let client = client.build().map_err(|e| FetchError::request(url, e))?;
+        client.get(url.clone()).send().await.map_err(|e| FetchError::request(url, e))
+    }
+
+    async fn read_pdf(&self, url: &Url, mut response: Response) -> Result<Vec<u8>, FetchError> {
+        let status = response.status();
+        if status != StatusCode::OK {
+            return Err(FetchError {
+                status: Some(status.as_u16()),
+                ..FetchError::new(FetchErrorKind::Http, url.as_str(), format!("{} answered HTTP {}", url, status))
+            });
+        }
+
+        let content_type = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.split(';').next().unwrap_or_default().trim().to_ascii_lowercase());
+        if let Some(content_type) = content_type.filter(|t| !t.is_empty() && !PDF_CONTENT_TYPES.contains(&t.as_str())) {
+            return Err(FetchError::new(
+                FetchErrorKind::NotPdf,
+                url.as_str(),
+                format!("{} is not a PDF (content type {})", url, content_type),
+            ));
+        }
+
+        let too_large = || {
+            FetchError::new(
+                FetchErrorKind::TooLarge,
+                url.as_str(),
+                format!("{} is larger than {} MB", url, self.max_size / (1024 * 1024)),
+            )
+        };
+        if response.content_length().is_some_and(|length| length > self.max_size) {
+            return Err(too_large());
+        }
+        let mut data = Vec::new();
+        while let Some(chunk) = response.chunk().await.map_err(|e| FetchError::request(url, e))? {
+            if (data.len() + chunk.len()) as u64 > self.max_size {
+                return Err(too_large());
+            }
+            data.extend_from_slice(&chunk);
+        }
+
+        if !probe::starts_like_pdf(&data) {
+            return Err(FetchError::new(
+                FetchErrorKind::NotPdf,
+                url.as_str(),
+                format!("{} is not a PDF (no %PDF- header)", url),
+            ));
+        }
+        Ok(data)
+    }
+}
+
+fn parse_url(url: &str) -> Result<Url, FetchError> {
+    let invalid = |message: String| FetchError::new(FetchErrorKind::InvalidUrl, url, message);
+    let parsed = Url::parse(url.trim()).map_err(|e| invalid(format!("Invalid URL {}: {}", url, e)))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(invalid(format!("Invalid protocol: {}:", parsed.scheme())));
+    }
+    if parsed.host().is_none() {
+        return Err(invalid(format!("Invalid URL {}: no host", url)));
+    }
+    Ok(parsed)
+}
+
+async fn resolve(url: &Url, domain: &str, timeout: Duration) -> Result<Vec<SocketAddr>, FetchError> {
+    let failed = |message: String| FetchError::new(FetchErrorKind::Network, url.as_str(), message);
+    let port = url.port_or_known_default().unwrap_or(80);
+    let addresses = tokio::time::timeout(timeout, tokio::net::lookup_host((domain, port)))
+        .await
+        .map_err(|_| FetchError::new(FetchErrorKind::Timeout, url.as_str(), format!("Looking up {} timed out", domain)))?
+        .map_err(|e| failed(format!("Failed to look up {}: {}", domain, e)))?
+        .collect::<Vec<_>>();
+    if addresses.is_empty() {
+        return Err(failed(format!("{} has no addresses", domain)));
+    }
+    Ok(addresses)
+}
+
+/// Whether `ip` is on the public internet (not loopback, private, link-local, CGNAT, reserved, ...)
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => is_public_v6(ip),
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local() // Includes 169.254.169.254, the cloud metadata service
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        || a == 0
+        || (a == 100 && (64..128).contains(&b)) // Carrier-grade NAT
+        || (a == 192 && b == 0 && c == 0) // IETF protocol assignments
+        || (a == 198 && (18..20).contains(&b)) // Benchmarking
+        || a >= 240)
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    let segments = ip.segments();
+    // IPv4-mapped and -compatible (::ffff:a.b.c.d, ::a.b.c.d, also ::1 as 0.0.0.1)
+    if let Some(v4) = ip.to_ipv4() {
+        return is_public_v4(v4);
+    }
+    // NAT64 (64:ff9b::/96) and 6to4 (2002::/16) carry an IPv4 address
+    let embedded = |high: u16, low: u16| Ipv4Addr::from((u32::from(high) << 16) | u32::from(low));
+    if segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] {
+        return is_public_v4(embedded(segments[6], segments[7]));
+    }
+    if segments[0] == 0x2002 {
+        return is_public_v4(embedded(segments[1], segments[2]));
+    }
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        || (segments[0] & 0xfe00) == 0xfc00 // Unique local
+        || (segments[0] & 0xffc0) == 0xfe80 // Link-local
+        || (segments[0] & 0xffc0) == 0xfec0 // Site-local
+        || (segments[0] == 0x2001 && segments[1] == 0x0db8) // Documentation
+        || (segments[0] == 0x2001 && segments[1] == 0)) // Teredo tunnels to any IPv4 address
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::Future;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::thread;
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    /// Stands in for the public internet: only the test server's own address is allowed
+    fn test_server_only(ip: IpAddr) -> bool {
+        ip == IpAddr::V4(Ipv4Addr::LOCALHOST)
+    }
+
+    fn fetcher() -> Fetcher {
+        Fetcher { allow_address: test_server_only, timeout: Duration::from_secs(10), ..Default::default() }
+    }
+
+    fn response(status: &str, headers: &[(&str, &str)], body: &[u8]) -> Vec<u8> {
+        let mut response = format!("HTTP/1.1 {}\r\nConnection: close\r\nContent-Length: {}\r\n", status, body.len());
+        for (name, value) in headers {
+            response.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        response.push_str("\r\n");
+        let mut response = response.into_bytes();
+        response.extend_from_slice(body);
+        response
+    }
+
+    /// Answer one request per response on 127.0.0.1 in a background thread; request paths are sent back
+    fn serve(listener: TcpListener, responses: Vec<Vec<u8>>) -> mpsc::Receiver<String> {
+        let (paths, received) = mpsc::channel();
+        thread::spawn(move || {
+            for response in responses {
+                let Ok((mut stream, _)) = listener.accept() else { return };
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut line = String::new();
+                while reader.read_line(&mut line).is_ok_and(|n| n > 2) {
+                    line.clear();
+                }
+                let _ = paths.send(request_line.split_whitespace().nth(1).unwrap_or_default().to_string());
+                let _ = stream.write_all(&response);
+            }
+        });
+        received
+    }
+
+    fn listener() -> (TcpListener, u16) {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        (listener, port)
+    }
+
+    const PDF: &[u8] = b"%PDF-1.7\n1 0 obj\n<< >>\nendobj\ntrailer\n<< >>\n%%EOF\n";
+
+    #[test]
+    fn downloads_pdfs() {
+        let (listener, port) = listener();
+        let paths = serve(listener, vec![response("200 OK", &[("Content-Type", "application/pdf")], PDF)]);
+
+        let data = block_on(fetcher().fetch(&format!("http://127.0.0.1:{}/a.pdf", port))).unwrap();
+        assert_eq!(data, PDF);
+        assert_eq!(paths.recv().unwrap(), "/a.pdf");
+    }
+
+    #[test]
+    fn redirects_to_internal_addresses_are_blocked() {
+        let targets = [
+            "http://127.0.0.2:{port}/internal.pdf",
+            "http://[::1]:{port}/internal.pdf",
+            "http://0x7f000002:{port}/internal.pdf",
+            "http://169.254.169.254/latest/meta-data/",
+        ];
+        for target in targets {
+            let (listener, port) = listener();
+            let location = target.replace("{port}", &port.to_string());
+            let paths = serve(listener, vec![response("302 Found", &[("Location", &location)], b"")]);
+
+            let error = block_on(fetcher().fetch(&format!("http://127.0.0.1:{}/a.pdf", port))).unwrap_err();
+            assert_eq!(error.kind, FetchErrorKind::Blocked, "{}: {}", location, error.message);
+            assert_ne!(error.url, format!("http://127.0.0.1:{}/a.pdf", port));
+            // Only the first hop reached the server
+            assert_eq!(paths.recv().unwrap(), "/a.pdf");
+            assert!(paths.recv_timeout(Duration::from_millis(100)).is_err());
+        }
+    }
+
+    #[test]
+    fn redirects_are_followed_up_to_the_limit() {
+        let (listener, port) = listener();
+        let redirect = |path: &str| response("301 Moved Permanently", &[("Location", path)], b"");
+        let paths = serve(
+            listener,
+            vec![redirect("/b.pdf"), response("200 OK", &[], PDF), redirect("/b.pdf"), redirect("/a.pdf")],
+        );
+        let url = format!("http://127.0.0.1:{}/a.pdf", port);
+
+        assert_eq!(block_on(fetcher().fetch(&url)).unwrap(), PDF);
+        assert_eq!((paths.recv().unwrap(), paths.recv().unwrap()), ("/a.pdf".into(), "/b.pdf".into()));
+
+        let limited = Fetcher { max_redirects: 1, ..fetcher() };
+        let error = block_on(limited.fetch(&url)).unwrap_err();
+        assert_eq!(error.kind, FetchErrorKind::Http);
+        assert!(error.message.starts_with("Too many redirects"), "{}", error.message);
+    }
+
+    #[test]
+    fn default_fetcher_blocks_loopback_before_connecting() {
+        let (listener, port) = listener();
+        let paths = serve(listener, vec![response("200 OK", &[], PDF)]);
+
+        for url in [format!("http://127.0.0.1:{}/a.pdf", port), format!("http://localhost:{}/a.pdf", port)] {
+            let error = block_on(Fetcher::default().fetch(&url)).unwrap_err();
+            assert_eq!(error.kind, FetchErrorKind::Blocked, "{}: {}", url, error.message);
+        }
+        assert!(paths.recv_timeout(Duration::from_millis(100)).is_err());
+    }
+
+    #[test]
+    fn refuses_non_pdf_responses() {
+        let (listener, port) = listener();
+        let big = vec![b'%'; 64];
+        serve(
+            listener,
+            vec![
+                response("404 Not Found", &[], b"gone"),
+                response("200 OK", &[("Content-Type", "text/html; charset=utf-8")], b"<html>"),
+                response("200 OK", &[("Content-Type", "application/octet-stream")], b"GIF89a"),
+                response("200 OK", &[], &big),
+            ],
+        );
+        let url = format!("http://127.0.0.1:{}/a.pdf", port);
+        let fetch = |fetcher: Fetcher| block_on(fetcher.fetch(&url)).unwrap_err();
+
+        let not_found = fetch(fetcher());
+        assert_eq!((not_found.kind, not_found.status), (FetchErrorKind::Http, Some(404)));
+        assert_eq!(fetch(fetcher()).kind, FetchErrorKind::NotPdf);
+        assert_eq!(fetch(fetcher()).kind, FetchErrorKind::NotPdf);
+        assert_eq!(fetch(Fetcher { max_size: 32, ..fetcher() }).kind, FetchErrorKind::TooLarge);
+    }
+
+    #[test]
+    fn only_http_urls_are_fetched() {
+        for url in ["file:///etc/passwd", "ftp://example.com/a.pdf", "example.com/a.pdf", "http://"] {
+            let error = block_on(fetcher().fetch(url)).unwrap_err();
+            assert_eq!(error.kind, FetchErrorKind::InvalidUrl, "{}", url);
+        }
+    }
+
+    #[test]
+    fn classifies_addresses() {
+        let public = ["8.8.8.8", "1.1.1.1", "2606:4700:4700::1111", "::ffff:8.8.8.8", "64:ff9b::808:808"];
+        for ip in public {
+            assert!(is_public(ip.parse().unwrap()), "{} should be public", ip);
+        }
+        let internal = [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "255.255.255.255",
+            "240.0.0.1",
+            "::1",
+            "::",
+            "fc00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "::ffff:10.0.0.1",
+            "64:ff9b::a00:1",
+            "2002:7f00:1::",
+            "2001::1",
+            "2001:db8::1",
+        ];
+        for ip in internal {
+            assert!(!is_public(ip.parse().unwrap()), "{} should be blocked", ip);
+        }
+    }
+}
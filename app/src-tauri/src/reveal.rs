@@ -0,0 +1,275 @@
+//! "Show in folder" for each desktop platform
+//!
+//! Linux has no single file manager, so we ask whichever one implements the
+//! `org.freedesktop.FileManager1` D-Bus interface to select the file, and fall
+//! back to opening the parent directory with `xdg-open`.
+
+use crate::atomic_write::FileError;
+use serde::Serialize;
+use std::path::Path;
+use std::process::Command;
+
+/// One way of revealing a file that was tried and failed
+#[derive(Serialize, Debug, Clone)]
+pub struct RevealAttempt {
+    /// e.g. "explorer", "dbus:FileManager1", "xdg-open"
+    pub mechanism: String,
+    pub error: String,
+}
+
+/// Returned to the frontend as `{ message, attempts: [{ mechanism, error }] }`
+#[derive(Serialize, Debug, Clone)]
+pub struct RevealError {
+    pub message: String,
+    pub attempts: Vec<RevealAttempt>,
+}
+
+impl RevealError {
+    fn new(path: &Path, attempts: Vec<RevealAttempt>) -> Self {
+        let tried = attempts
+            .iter()
+            .map(|a| format!("{}: {}", a.mechanism, a.error))
+            .collect::<Vec<_>>()
+            .join("; ");
+        RevealError {
+            message: format!("Failed to show {} in folder ({})", path.display(), tried),
+            attempts,
+        }
+    }
+}
+
+/// Paths outside the file scope are refused before anything is tried
+impl From<FileError> for RevealError {
+    fn from(e: FileError) -> Self {
+        RevealError {
+            attempts: vec![attempt("scope", &e.message)],
+            message: e.message,
+        }
+    }
+}
+
+fn attempt(mechanism: &str, error: impl ToString) -> RevealAttempt {
+    RevealAttempt {
+        mechanism: mechanism.to_string(),
+        error: error.to_string(),
+    }
+}
+
+/// Open the system file manager with `path` selected (or at least its folder open)
+pub fn show_in_folder(path: &Path) -> Result<(), RevealError> {
+    if !path.exists() {
+        return Err(RevealError::new(path, vec![attempt("check", "file does not exist")]));
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        // Comma is important for explorer /select; explorer exits with 1 even when it worked
+        spawn("explorer", Command::new("explorer").args([std::ffi::OsStr::new("/select,"), path.as_os_str()]))
+            .map_err(|e| RevealError::new(path, vec![e]))
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        run("open -R", Command::new("open").arg("-R").arg(path)).map_err(|e| RevealError::new(path, vec![e]))
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        reveal_linux(path, show_items_dbus, |dir| spawn("xdg-open", Command::new("xdg-open").arg(dir)))
+    }
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+    {
+        let _ = Command::new;
+        Err(RevealError::new(path, vec![attempt("none", "not supported on this OS")]))
+    }
+}
+
+/// Ask the file manager to select `path`, and failing that open its folder
+#[cfg(target_os = "linux")]
+fn reveal_linux(
+    path: &Path,
+    show_items: impl FnOnce(&Path) -> Result<(), String>,
+    open_dir: impl FnOnce(&Path) -> Result<(), RevealAttempt>,
+) -> Result<(), RevealError> {
+    let mut attempts = Vec::new();
+    match show_items(path) {
+        Ok(()) => return Ok(()),
+        Err(e) => attempts.push(attempt("dbus:FileManager1", e)),
+    }
+    open_dir(path.parent().unwrap_or(path)).map_err(|e| {
+        attempts.push(e);
+        RevealError::new(path, attempts)
+    })
+}
+
+/// Start a launcher without waiting for it; it is reaped on a background thread.
+/// `xdg-open` may stay around as long as the program it opened does.
+#[cfg(any(target_os = "windows", target_os = "linux"))]
+fn spawn(mechanism: &str, command: &mut Command) -> Result<(), RevealAttempt> {
+    let mut child = command.spawn().map_err(|e| attempt(mechanism, e))?;
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+/// Run a launcher to completion (leaving no zombie behind); a non-zero exit is a failure.
+/// `open -R` returns as soon as Finder is up.
+#[cfg(target_os = "macos")]
+fn run(mechanism: &str, command: &mut Command) -> Result<(), RevealAttempt> {
+    match command.status() {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(attempt(mechanism, format!("exited with {}", status))),
+        Err(e) => Err(attempt(mechanism, e)),
+    }
+}
+
+/// `org.freedesktop.FileManager1.ShowItems` on the session bus
+#[cfg(target_os = "linux")]
+fn show_items_dbus(path: &Path) -> Result<(), String> {
+    let absolute = std::fs::canonicalize(path).map_err(|e| e.to_string())?;
+    let uri = url::Url::from_file_path(&absolute)
+        .map_err(|_| format!("cannot build a file URI for {}", absolute.display()))?;
+
+    let connection = zbus::blocking::Connection::session().map_err(|e| e.to_string())?;
+    show_items(&connection, uri.as_str())
+}
+
+/// Select `uri` through whichever file manager answers on `connection`
+#[cfg(target_os = "linux")]
+fn show_items(connection: &zbus::blocking::Connection, uri: &str) -> Result<(), String> {
+    connection
+        .call_method(
+            Some("org.freedesktop.FileManager1"),
+            "/org/freedesktop/FileManager1",
+            Some("org.freedesktop.FileManager1"),
+            "ShowItems",
+            // URIs to select, and a startup notification id (none)
+            &(vec![uri], ""),
+        )
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_files_are_not_revealed() {
+        let error = show_in_folder(Path::new("/definitely/not/here.pdf")).unwrap_err();
+        assert_eq!(error.attempts.len(), 1);
+        assert_eq!(error.attempts[0].mechanism, "check");
+        assert!(error.message.contains("not/here.pdf"), "{}", error.message);
+    }
+
+    #[test]
+    fn message_lists_every_attempt() {
+        let error = RevealError::new(
+            Path::new("a.pdf"),
+            vec![attempt("dbus:FileManager1", "no bus"), attempt("xdg-open", "exited with 3")],
+        );
+        assert_eq!(
+            error.message,
+            "Failed to show a.pdf in folder (dbus:FileManager1: no bus; xdg-open: exited with 3)"
+        );
+    }
+
+    #[test]
+    fn scope_errors_become_an_attempt() {
+        let error = RevealError::from(FileError::other(Path::new("a.pdf"), "Not granted".to_string()));
+        assert_eq!(error.message, "Not granted");
+        assert_eq!(error.attempts[0].mechanism, "scope");
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn launchers_fail_on_non_zero_exit() {
+        assert!(run("true", &mut Command::new("true")).is_ok());
+
+        let failed = run("false", &mut Command::new("false")).unwrap_err();
+        assert_eq!(failed.mechanism, "false");
+        assert!(failed.error.starts_with("exited with"), "{}", failed.error);
+
+        let missing = run("missing", &mut Command::new("/definitely/not/a/launcher")).unwrap_err();
+        assert_eq!(missing.mechanism, "missing");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn launchers_are_not_waited_for() {
+        // A launcher that outlives the call, or exits non-zero later, still counts as started
+        assert!(spawn("sleep", Command::new("sleep").arg("5")).is_ok());
+        assert!(spawn("false", &mut Command::new("false")).is_ok());
+
+        let missing = spawn("missing", &mut Command::new("/definitely/not/a/launcher")).unwrap_err();
+        assert_eq!(missing.mechanism, "missing");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn dbus_is_tried_before_xdg_open() {
+        let path = Path::new("/docs/a.pdf");
+
+        let shown = reveal_linux(path, |_| Ok(()), |_| panic!("xdg-open must not run after D-Bus worked"));
+        assert!(shown.is_ok());
+
+        let mut opened = None;
+        let fell_back = reveal_linux(
+            path,
+            |_| Err("no bus".to_string()),
+            |dir| {
+                opened = Some(dir.to_path_buf());
+                Ok(())
+            },
+        );
+        assert!(fell_back.is_ok());
+        assert_eq!(opened.as_deref(), Some(Path::new("/docs")));
+
+        let error = reveal_linux(path, |_| Err("no bus".to_string()), |_| Err(attempt("xdg-open", "exited with 3")))
+            .unwrap_err();
+        let mechanisms: Vec<_> = error.attempts.iter().map(|a| a.mechanism.as_str()).collect();
+        assert_eq!(mechanisms, ["dbus:FileManager1", "xdg-open"]);
+    }
+
+    /// Stands in for a file manager and records what it was asked to select
+    #[cfg(target_os = "linux")]
+    struct FileManager {
+        shown: std::sync::mpsc::Sender<(Vec<String>, String)>,
+    }
+
+    #[cfg(target_os = "linux")]
+    #[zbus::interface(name = "org.freedesktop.FileManager1")]
+    impl FileManager {
+        fn show_items(&self, uris: Vec<String>, startup_id: String) {
+            let _ = self.shown.send((uris, startup_id));
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn show_items_calls_the_file_manager() {
+        use zbus::blocking::connection::Builder;
+
+        let (server_stream, client_stream) = std::os::unix::net::UnixStream::pair().unwrap();
+        let (sender, shown) = std::sync::mpsc::channel();
+        // Both ends of a peer-to-peer connection authenticate together, so the server is built on its own thread
+        let server = std::thread::spawn(move || {
+            Builder::unix_stream(server_stream)
+                .server(zbus::Guid::generate())
+                .unwrap()
+                .p2p()
+                .serve_at("/org/freedesktop/FileManager1", FileManager { shown: sender })
+                .unwrap()
+                .build()
+                .unwrap()
+        });
+        let client = Builder::unix_stream(client_stream).p2p().build().unwrap();
+        let _server = server.join().unwrap();
+
+        show_items(&client, "file:///docs/a%20b.pdf").unwrap();
+        assert_eq!(
+            shown.recv_timeout(std::time::Duration::from_secs(5)).unwrap(),
+            (vec!["file:///docs/a%20b.pdf".to_string()], String::new())
+        );
+    }
+}
@@ -0,0 +1,37 @@
+//! Desktop settings kept in `<app data>/settings.json`
+//!
+//! The file is edited by hand and read on start. Everything is optional:
+//!
+//! ```json
+//! { "accelerators": { "openLeft": "CmdOrCtrl+O", "fullscreen": null } }
+//! ```
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Settings {
+    /// Menu shortcuts by action id; `null` or `""` removes the default one
+    pub accelerators: BTreeMap<String, Option<String>>,
+}
+
+impl Settings {
+    /// Read the settings file; a missing or invalid file gives the defaults
+    pub fn load(path: &Path) -> Settings {
+        match fs::read(path) {
+            Ok(json) => serde_json::from_slice(&json).unwrap_or_else(|e| {
+                log::warn!("Ignoring invalid settings {}: {}", path.display(), e);
+                Settings::default()
+            }),
+            Err(e) if e.kind() == ErrorKind::NotFound => Settings::default(),
+            Err(e) => {
+                log::warn!("Cannot read settings {}: {}", path.display(), e);
+                Settings::default()
+            }
+        }
+    }
+}
@@ -0,0 +1,458 @@
+//! Hand launches over to the instance that is already running
+//!
+//! The first process listens on a local socket (a Unix domain socket, which
+//! Windows supports since 10 1803) in a folder only the user can enter. Later
+//! launches connect to it, send their parsed launch options as one JSON line
+//! and exit as soon as the running app confirms. Both ends check that the
+//! other runs as the same user. The running app grants the files that really
+//! are PDFs or projects, brings its main window to the front and emits
+//! `open-request` to it.
+//! A socket file left behind by a crashed instance is replaced; a lock file
+//! next to it keeps two launches at the same time from both doing so.
+
+use crate::cli::LaunchOptions;
+use crate::file_scope::FileScope;
+use crate::{probe, project, windows};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(windows)]
+use uds_windows::{UnixListener, UnixStream};
+
+pub const OPEN_REQUEST_EVENT: &str = "open-request";
+
+const ACK: &[u8; 3] = b"ok\n";
+
+/// How long a launch waits for the running instance before starting its own window
+const TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Requests are a few paths and options; anything longer is not ours
+const MAX_REQUEST: u64 = 64 * 1024;
+
+/// `identifier` in tauri.conf.json, which names the app data folder
+const IDENTIFIER: &str = "com.pluskits.twice-pdf";
+
+/// Outcome of looking for a running instance
+pub enum Instance {
+    /// The running instance took over; this process should exit
+    Forwarded,
+    /// This is the first instance; `listen` once the app is set up
+    Primary(UnixListener),
+    /// No running instance could be reached and none can listen; run on our own
+    Standalone,
+}
+
+fn socket_path() -> io::Result<PathBuf> {
+    // The runtime dir is private to the user on Linux; elsewhere use a private folder in the app data
+    let dir = match dirs::runtime_dir() {
+        Some(dir) => dir,
+        None => {
+            let dir = dirs::data_dir()
+                .ok_or_else(|| io::Error::other("no app data folder"))?
+                .join(IDENTIFIER)
+                .join("run");
+            create_private_dir(&dir)?;
+            dir
+        }
+    };
+    Ok(dir.join("twice-pdf.sock"))
+}
+
+/// Held from looking for a running instance until the socket is bound
+struct StartupLock {
+    _file: fs::File,
+}
+
+/// Wait for the exclusive lock on `path`; closing the file releases it
+#[cfg(unix)]
+fn lock_startup(path: &Path) -> io::Result<StartupLock> {
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::os::unix::io::AsRawFd;
+    let file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .mode(0o600)
+        .open(path)?;
+    // SAFETY: flock only takes the descriptor, which is open
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(StartupLock { _file: file })
+}
+
+/// A file opened without sharing cannot be opened again until it is closed
+#[cfg(windows)]
+fn lock_startup(path: &Path) -> io::Result<StartupLock> {
+    use std::os::windows::fs::OpenOptionsExt;
+    const ERROR_SHARING_VIOLATION: i32 = 32;
+    let started = std::time::Instant::now();
+    loop {
+        match fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .share_mode(0)
+            .open(path)
+        {
+            Ok(file) => return Ok(StartupLock { _file: file }),
+            Err(e) if e.raw_os_error() == Some(ERROR_SHARING_VIOLATION) && started.elapsed() < TIMEOUT => {
+                thread::sleep(Duration::from_millis(20))
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+    fs::DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+    let metadata = fs::symlink_metadata(dir)?;
+    if !metadata.is_dir() || metadata.uid() != peer::current_uid() {
+        return Err(io::Error::other(format!("{} is not a folder of this user", dir.display())));
+    }
+    if metadata.mode() & 0o077 != 0 {
+        fs::set_permissions(dir, fs::Permissions::from_mode(0o700))?;
+    }
+    Ok(())
+}
+
+/// The app data folder is in the user's profile, which other users cannot open
+#[cfg(windows)]
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)
+}
+
+/// Forward `options` to a running instance, or become the one that later launches forward to
+pub fn acquire(options: &LaunchOptions) -> Instance {
+    let path = match socket_path() {
+        Ok(path) => path,
+        Err(e) => {
+            eprintln!("twice-pdf: no private folder for the instance socket ({}), files will open in new windows", e);
+            return Instance::Standalone;
+        }
+    };
+    acquire_at(&path, options)
+}
+
+fn acquire_at(path: &Path, options: &LaunchOptions) -> Instance {
+    // Otherwise two launches could both find no listener, and the second would remove the first one's socket
+    let _lock = match lock_startup(&path.with_extension("lock")) {
+        Ok(lock) => lock,
+        Err(e) => {
+            eprintln!("twice-pdf: cannot lock the instance socket ({}), files will open in new windows", e);
+            return Instance::Standalone;
+        }
+    };
+    if let Ok(stream) = UnixStream::connect(path) {
+        return match forward(stream, options) {
+            Ok(()) => Instance::Forwarded,
+            Err(e) => {
+                eprintln!("twice-pdf: the running instance did not respond ({}), opening a new window", e);
+                Instance::Standalone
+            }
+        };
+    }
+
+    // Nobody is listening: the file (if any) was left behind by a crash
+    let _ = fs::remove_file(path);
+    match UnixListener::bind(path) {
+        Ok(listener) => {
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                let _ = fs::set_permissions(path, fs::Permissions::from_mode(0o600));
+            }
+            Instance::Primary(listener)
+        }
+        Err(e) => {
+            eprintln!("twice-pdf: cannot listen on {} ({}), files will open in new windows", path.display(), e);
+            Instance::Standalone
+        }
+    }
+}
+
+fn forward(stream: UnixStream, options: &LaunchOptions) -> io::Result<()> {
+    peer::check_same_user(&stream)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    let mut request = serde_json::to_vec(options)?;
+    request.push(b'\n');
+    (&stream).write_all(&request)?;
+
+    let mut reply = [0u8; 3];
+    (&stream).read_exact(&mut reply)?;
+    if &reply != ACK {
+        return Err(io::Error::other("unexpected reply"));
+    }
+    Ok(())
+}
+
+/// Serve launches forwarded by later processes until the app exits
+pub fn listen(listener: UnixListener, app: AppHandle) {
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            if let Err(e) = stream.and_then(|stream| receive(&app, stream)) {
+                log::warn!("Ignoring forwarded launch: {}", e);
+            }
+        }
+    });
+}
+
+fn receive(app: &AppHandle, stream: UnixStream) -> io::Result<()> {
+    let mut options = read_request(&stream)?;
+    let scope = app.state::<FileScope>();
+    for file in [&mut options.left, &mut options.right, &mut options.project] {
+        *file = file.take().filter(|path| grant(&scope, Path::new(path)));
+    }
+    if let Some(window) = app.get_webview_window(windows::MAIN) {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+    app.emit_to(windows::MAIN, OPEN_REQUEST_EVENT, &options).map_err(io::Error::other)?;
+    (&stream).write_all(ACK)
+}
+
+/// The launch options a later process sent, once it is known to run as this user
+fn read_request(stream: &UnixStream) -> io::Result<LaunchOptions> {
+    peer::check_same_user(stream)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    let mut request = String::new();
+    BufReader::new(stream.take(MAX_REQUEST)).read_line(&mut request)?;
+    Ok(serde_json::from_str(&request)?)
+}
+
+/// Grant a forwarded file when it really is a PDF or a project; anything else is dropped
+fn grant(scope: &FileScope, path: &Path) -> bool {
+    if !project::is_project_file(path) && !probe::is_pdf_file(path) {
+        log::warn!("Ignoring forwarded file {}: not a PDF or project", path.display());
+        return false;
+    }
+    match scope.grant_opened(path) {
+        Ok(_) => true,
+        Err(e) => {
+            log::warn!("{}", e);
+            false
+        }
+    }
+}
+
+/// Who is at the other end of the socket
+#[cfg(unix)]
+mod peer {
+    use super::UnixStream;
+    use std::io;
+    use std::os::unix::io::AsRawFd;
+
+    pub fn current_uid() -> libc::uid_t {
+        // SAFETY: geteuid has no preconditions and cannot fail
+        unsafe { libc::geteuid() }
+    }
+
+    pub fn check_same_user(stream: &UnixStream) -> io::Result<()> {
+        check_uid(peer_uid(stream)?)
+    }
+
+    pub fn check_uid(uid: libc::uid_t) -> io::Result<()> {
+        if uid != current_uid() {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("the other end of the socket runs as user {}", uid),
+            ));
+        }
+        Ok(())
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn peer_uid(stream: &UnixStream) -> io::Result<libc::uid_t> {
+        let mut credentials = libc::ucred { pid: 0, uid: 0, gid: 0 };
+        let mut length = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+        // SAFETY: the buffer and its length describe a valid ucred for SO_PEERCRED
+        let result = unsafe {
+            libc::getsockopt(
+                stream.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_PEERCRED,
+                (&mut credentials as *mut libc::ucred).cast(),
+                &mut length,
+            )
+        };
+        if result != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(credentials.uid)
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    pub fn peer_uid(stream: &UnixStream) -> io::Result<libc::uid_t> {
+        let (mut uid, mut gid) = (0, 0);
+        // SAFETY: both out pointers are valid for writes
+        if unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(uid)
+    }
+}
+
+/// Only the user can reach the socket in their profile folder
+#[cfg(windows)]
+mod peer {
+    use super::UnixStream;
+    use std::io;
+
+    pub fn check_same_user(_stream: &UnixStream) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::cli::Zoom;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("twice-pdf-single-instance-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Answer forwarded launches like `listen` does, passing on what was received
+    fn serve(listener: UnixListener, received: std::sync::mpsc::Sender<LaunchOptions>) {
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = stream.unwrap();
+                if let Ok(options) = read_request(&stream) {
+                    let _ = received.send(options);
+                    let _ = (&stream).write_all(ACK);
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn peers_must_run_as_this_user() {
+        let (one, _other) = UnixStream::pair().unwrap();
+        assert_eq!(peer::peer_uid(&one).unwrap(), peer::current_uid());
+        assert!(peer::check_same_user(&one).is_ok());
+
+        let error = peer::check_uid(peer::current_uid().wrapping_add(1)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+    }
+
+    #[test]
+    fn options_are_forwarded_and_acknowledged() {
+        let (client, server) = UnixStream::pair().unwrap();
+        let options = LaunchOptions {
+            left: Some("/docs/a.pdf".to_string()),
+            sync_offset: Some(-2),
+            zoom: Some(Zoom::Level { level: 150 }),
+            ..Default::default()
+        };
+        let receiver = thread::spawn(move || {
+            let options = read_request(&server).unwrap();
+            (&server).write_all(ACK).unwrap();
+            options
+        });
+
+        forward(client, &options).unwrap();
+        let received = receiver.join().unwrap();
+        assert_eq!(received.left.as_deref(), Some("/docs/a.pdf"));
+        assert_eq!(received.sync_offset, Some(-2));
+        assert_eq!(received.zoom, Some(Zoom::Level { level: 150 }));
+    }
+
+    #[test]
+    fn forwarding_needs_the_acknowledgement() {
+        let (client, server) = UnixStream::pair().unwrap();
+        let receiver = thread::spawn(move || {
+            read_request(&server).unwrap();
+            (&server).write_all(b"no\n").unwrap();
+        });
+        assert!(forward(client, &LaunchOptions::default()).is_err());
+        receiver.join().unwrap();
+    }
+
+    #[test]
+    fn requests_must_be_launch_options() {
+        let (client, server) = UnixStream::pair().unwrap();
+        (&client).write_all(b"{\"page\": \"two\"}\n").unwrap();
+        assert!(read_request(&server).is_err());
+    }
+
+    #[test]
+    fn later_launches_forward_to_the_first() {
+        let path = test_dir("forward").join("twice-pdf.sock");
+        let (sender, received) = std::sync::mpsc::channel();
+        match acquire_at(&path, &LaunchOptions::default()) {
+            Instance::Primary(listener) => serve(listener, sender),
+            _ => panic!("the first launch should listen"),
+        }
+
+        let options = LaunchOptions { right: Some("/docs/b.pdf".to_string()), ..Default::default() };
+        assert!(matches!(acquire_at(&path, &options), Instance::Forwarded));
+        assert_eq!(received.recv().unwrap().right.as_deref(), Some("/docs/b.pdf"));
+    }
+
+    #[test]
+    fn stale_sockets_are_replaced() {
+        let path = test_dir("stale").join("twice-pdf.sock");
+        drop(UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+        assert!(matches!(acquire_at(&path, &LaunchOptions::default()), Instance::Primary(_)));
+    }
+
+    #[test]
+    fn startup_lock_is_exclusive() {
+        let path = test_dir("lock").join("twice-pdf.lock");
+        let held = lock_startup(&path).unwrap();
+        let (sender, locked) = std::sync::mpsc::channel();
+        let waiting = {
+            let path = path.clone();
+            thread::spawn(move || {
+                let lock = lock_startup(&path).unwrap();
+                sender.send(()).unwrap();
+                lock
+            })
+        };
+        assert!(locked.recv_timeout(Duration::from_millis(200)).is_err());
+        drop(held);
+        assert!(locked.recv_timeout(TIMEOUT).is_ok());
+        waiting.join().unwrap();
+    }
+
+    #[test]
+    fn simultaneous_launches_elect_one_instance() {
+        let path = test_dir("race").join("twice-pdf.sock");
+        let (sender, received) = std::sync::mpsc::channel();
+        let start = std::sync::Arc::new(std::sync::Barrier::new(8));
+        let launches: Vec<_> = (0..8)
+            .map(|_| {
+                let (path, sender, start) = (path.clone(), sender.clone(), start.clone());
+                thread::spawn(move || {
+                    start.wait();
+                    match acquire_at(&path, &LaunchOptions::default()) {
+                        Instance::Primary(listener) => {
+                            serve(listener, sender);
+                            "primary"
+                        }
+                        Instance::Forwarded => "forwarded",
+                        Instance::Standalone => "standalone",
+                    }
+                })
+            })
+            .collect();
+        let mut outcomes: Vec<_> = launches.into_iter().map(|launch| launch.join().unwrap()).collect();
+        outcomes.sort();
+        assert_eq!(outcomes, [["forwarded"; 7].as_slice(), &["primary"]].concat());
+        assert_eq!(received.try_iter().count(), 7);
+    }
+}
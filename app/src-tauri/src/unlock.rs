@@ -0,0 +1,420 @@
+//! Password-protected PDFs
+//!
+//! lopdf only tries the empty password while parsing, so a file with a user
+//! password comes back holding nothing but its Encrypt dictionary. Here the
+//! objects are re-read from the raw bytes and decrypted with the user or owner
+//! password, leaving a plain in-memory document. Only the Standard security
+//! handler (RC4, AES-128 and AES-256) is supported.
+//!
+//! lopdf derives the file key as if the password were always the user
+//! password. That holds for AES-256, but for the older revisions an owner
+//! password first has to be turned into the user password it protects.
+
+use crate::atomic_write::{FileError, FileErrorKind};
+use lopdf::encryption::PasswordAlgorithm;
+use lopdf::xref::XrefEntry;
+use lopdf::{Dictionary, Document, EncryptionState, Object, Reader};
+use md5::{Digest, Md5};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::SystemTime;
+
+/// A decrypted document plus what is needed to encrypt it the same way again
+#[derive(Clone)]
+pub struct Unlocked {
+    pub document: Document,
+    /// `None` for files that were not encrypted
+    pub encryption: Option<EncryptionState>,
+}
+
+#[derive(Debug)]
+pub enum UnlockError {
+    Invalid(lopdf::Error),
+    WrongPassword,
+    UnsupportedHandler(String),
+}
+
+impl fmt::Display for UnlockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnlockError::Invalid(e) => write!(f, "Invalid PDF: {}", e),
+            UnlockError::WrongPassword => f.write_str("the password is missing or incorrect"),
+            UnlockError::UnsupportedHandler(name) => write!(f, "unsupported security handler {}", name),
+        }
+    }
+}
+
+impl UnlockError {
+    /// As a `passwordRequired` / `unsupportedEncryption` file error for the frontend
+    pub fn file_error(&self, path: &Path) -> FileError {
+        let kind = match self {
+            UnlockError::Invalid(_) => FileErrorKind::Other,
+            UnlockError::WrongPassword => FileErrorKind::PasswordRequired,
+            UnlockError::UnsupportedHandler(_) => FileErrorKind::UnsupportedEncryption,
+        };
+        FileError {
+            kind,
+            path: path.to_string_lossy().into_owned(),
+            message: format!("Failed to open {}: {}", path.display(), self),
+        }
+    }
+}
+
+/// Parse `bytes` and decrypt it with `password` (either the user or the owner password).
+/// Plain files are returned as they are, whatever the password.
+pub fn unlock(bytes: &[u8], password: &str) -> Result<Unlocked, UnlockError> {
+    let mut doc = Document::load_mem(bytes).map_err(UnlockError::Invalid)?;
+    let Ok(encrypt) = doc.get_encrypted() else {
+        return Ok(Unlocked { document: doc, encryption: None });
+    };
+    let filter = encrypt.get(b"Filter").and_then(Object::as_name).unwrap_or(b"");
+    if filter != b"Standard" {
+        return Err(UnlockError::UnsupportedHandler(String::from_utf8_lossy(filter).into_owned()));
+    }
+    let user_password = user_password(&doc, encrypt, password).ok_or(UnlockError::WrongPassword)?;
+
+    let encryption = match doc.encryption_state.take() {
+        // Empty user password: lopdf already decrypted the objects, only the Encrypt entry is left
+        Some(state) => {
+            if let Some(Object::Reference(id)) = doc.trailer.remove(b"Encrypt") {
+                doc.objects.remove(&id);
+            }
+            state
+        }
+        None => {
+            doc = read_raw_objects(bytes, doc);
+            doc.decrypt_raw(&user_password).map_err(UnlockError::Invalid)?;
+            doc.encryption_state.take().ok_or(UnlockError::WrongPassword)?
+        }
+    };
+    Ok(Unlocked { document: doc, encryption: Some(encryption) })
+}
+
+/// The sanitized user password for `password`, which may be the user or the owner password
+fn user_password(doc: &Document, encrypt: &Dictionary, password: &str) -> Option<Vec<u8>> {
+    let algorithm = PasswordAlgorithm::try_from(doc).ok()?;
+    let password = algorithm.sanitize_password(password).ok()?;
+    if algorithm.authenticate_user_password(doc, &password).is_ok() {
+        return Some(password);
+    }
+    algorithm.authenticate_owner_password(doc, &password).ok()?;
+
+    let revision = encrypt.get(b"R").and_then(Object::as_i64).ok()?;
+    if revision >= 5 {
+        // AES-256 keys can be unwrapped with either password
+        return Some(password);
+    }
+    let owner_value = encrypt.get(b"O").and_then(Object::as_str).ok()?;
+    let key_length = if revision >= 3 {
+        encrypt.get(b"Length").and_then(Object::as_i64).unwrap_or(40) as usize / 8
+    } else {
+        5
+    };
+    Some(decrypt_owner_value(&password, owner_value, revision, key_length.min(16)))
+}
+
+/// Algorithm 7 of ISO 32000-2: the owner password is the RC4 key that encrypted
+/// the (padded) user password into the O entry
+fn decrypt_owner_value(owner_password: &[u8], owner_value: &[u8], revision: i64, key_length: usize) -> Vec<u8> {
+    const PAD_BYTES: [u8; 32] = [
+        0x28, 0xBF, 0x4E, 0x5E, 0x4E, 0x75, 0x8A, 0x41, 0x64, 0x00, 0x4E, 0x56, 0xFF, 0xFA, 0x01, 0x08, 0x2E, 0x2E,
+        0x00, 0xB6, 0xD0, 0x68, 0x3E, 0x80, 0x2F, 0x0C, 0xA9, 0xFE, 0x64, 0x53, 0x69, 0x7A,
+    ];
+    let len = owner_password.len().min(32);
+    let mut hash = Md5::new()
+        .chain_update(&owner_password[..len])
+        .chain_update(&PAD_BYTES[..32 - len])
+        .finalize();
+    if revision >= 3 {
+        for _ in 0..50 {
+            hash = Md5::digest(hash);
+        }
+    }
+    let key = &hash[..key_length];
+
+    let mut result = owner_value.to_vec();
+    if revision >= 3 {
+        for i in (1..=19u8).rev() {
+            let round_key: Vec<u8> = key.iter().map(|b| b ^ i).collect();
+            result = rc4(&round_key, &result);
+        }
+    }
+    rc4(key, &result)
+}
+
+fn rc4(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut state: [u8; 256] = std::array::from_fn(|i| i as u8);
+    let mut j = 0u8;
+    for i in 0..256 {
+        j = j.wrapping_add(state[i]).wrapping_add(key[i % key.len()]);
+        state.swap(i, j as usize);
+    }
+    let (mut i, mut j) = (0u8, 0u8);
+    data.iter()
+        .map(|byte| {
+            i = i.wrapping_add(1);
+            j = j.wrapping_add(state[i as usize]);
+            state.swap(i as usize, j as usize);
+            byte ^ state[state[i as usize].wrapping_add(state[j as usize]) as usize]
+        })
+        .collect()
+}
+
+/// Fill in the still-encrypted objects lopdf skipped for lack of a password
+fn read_raw_objects(bytes: &[u8], doc: Document) -> Document {
+    let reader = Reader {
+        buffer: bytes,
+        document: doc,
+        encryption_state: None,
+        raw_objects: BTreeMap::new(),
+    };
+    let objects: Vec<_> = reader
+        .document
+        .reference_table
+        .entries
+        .iter()
+        .filter_map(|(&number, entry)| match *entry {
+            XrefEntry::Normal { generation, .. } => {
+                let id = (number, generation);
+                reader.get_object(id, &mut HashSet::new()).ok().map(|obj| (id, obj))
+            }
+            // Compressed objects are unpacked from their object streams by `decrypt`
+            _ => None,
+        })
+        .collect();
+
+    let mut doc = reader.document;
+    for (id, obj) in objects {
+        doc.objects.entry(id).or_insert(obj);
+    }
+    doc
+}
+
+/// Read and decrypt a file
+pub fn unlock_file(path: &Path, password: &str) -> Result<Unlocked, FileError> {
+    let bytes = fs::read(path).map_err(|e| FileError::io("read", path, e))?;
+    unlock(&bytes, password).map_err(|e| e.file_error(path))
+}
+
+/// Size and mtime of a file when it was decrypted
+type Stamp = Option<(u64, Option<SystemTime>)>;
+
+fn stamp(path: &Path) -> Stamp {
+    fs::metadata(path).ok().map(|metadata| (metadata.len(), metadata.modified().ok()))
+}
+
+/// Documents decrypted for viewing, reused by the annotated export (managed Tauri state).
+/// An entry is only used while the file on disk is unchanged.
+#[derive(Default)]
+pub struct UnlockedFiles {
+    docs: Mutex<HashMap<PathBuf, (Stamp, Unlocked)>>,
+}
+
+impl UnlockedFiles {
+    fn lock(&self) -> MutexGuard<'_, HashMap<PathBuf, (Stamp, Unlocked)>> {
+        self.docs.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Decrypt `path` and keep the result, replacing any earlier copy
+    pub fn unlock(&self, path: &Path, password: &str) -> Result<Unlocked, FileError> {
+        self.remove(path);
+        // Taken before reading, so a rewrite during the read invalidates the copy
+        let stamp = stamp(path);
+        let unlocked = unlock_file(path, password)?;
+        self.lock().insert(path.to_path_buf(), (stamp, unlocked.clone()));
+        Ok(unlocked)
+    }
+
+    /// The decrypted copy of `path`, unless the file changed since it was decrypted
+    pub fn get(&self, path: &Path) -> Option<Unlocked> {
+        let mut docs = self.lock();
+        let current = stamp(path);
+        match docs.get(path) {
+            Some((stamp, unlocked)) if stamp.is_some() && *stamp == current => Some(unlocked.clone()),
+            Some(_) => {
+                docs.remove(path);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub fn remove(&self, path: &Path) {
+        self.lock().remove(path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::encryption::crypt_filters::{Aes128CryptFilter, Aes256CryptFilter, CryptFilter};
+    use lopdf::encryption::{EncryptionVersion, Permissions};
+    use lopdf::{dictionary, Stream, StringFormat};
+    use std::sync::Arc;
+
+    const CONTENT: &[u8] = b"BT /F1 12 Tf 72 720 Td (Secret text) Tj ET";
+
+    #[derive(Clone, Copy, Debug)]
+    enum Cipher {
+        Rc4_40,
+        Rc4_128,
+        Aes128,
+        Aes256,
+    }
+
+    const CIPHERS: [Cipher; 4] = [Cipher::Rc4_40, Cipher::Rc4_128, Cipher::Aes128, Cipher::Aes256];
+
+    fn plain_document() -> Document {
+        let mut doc = Document::with_version("1.7");
+        let pages_id = doc.new_object_id();
+        let content_id = doc.add_object(Stream::new(dictionary! {}, CONTENT.to_vec()));
+        let page_id = doc.add_object(dictionary! { "Type" => "Page", "Parent" => pages_id, "Contents" => content_id });
+        let pages = dictionary! { "Type" => "Pages", "Kids" => vec![page_id.into()], "Count" => 1 };
+        doc.objects.insert(pages_id, Object::Dictionary(pages));
+        let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        doc.trailer.set("Root", catalog_id);
+        let id = Object::String(b"0123456789abcdef".to_vec(), StringFormat::Hexadecimal);
+        doc.trailer.set("ID", vec![id.clone(), id]);
+        doc
+    }
+
+    /// A one-page document encrypted by lopdf with the owner password "owner"
+    fn encrypted(cipher: Cipher, user_password: &str) -> Vec<u8> {
+        let mut doc = plain_document();
+        let filters = |filter: Arc<dyn CryptFilter>| BTreeMap::from([(b"StdCF".to_vec(), filter)]);
+        let key = [7u8; 32];
+        let version = match cipher {
+            Cipher::Rc4_40 => EncryptionVersion::V1 {
+                document: &doc,
+                owner_password: "owner",
+                user_password,
+                permissions: Permissions::all(),
+            },
+            Cipher::Rc4_128 => EncryptionVersion::V2 {
+                document: &doc,
+                owner_password: "owner",
+                user_password,
+                key_length: 128,
+                permissions: Permissions::all(),
+            },
+            Cipher::Aes128 => EncryptionVersion::V4 {
+                document: &doc,
+                encrypt_metadata: true,
+                crypt_filters: filters(Arc::new(Aes128CryptFilter)),
+                stream_filter: b"StdCF".to_vec(),
+                string_filter: b"StdCF".to_vec(),
+                owner_password: "owner",
+                user_password,
+                permissions: Permissions::all(),
+            },
+            Cipher::Aes256 => EncryptionVersion::V5 {
+                encrypt_metadata: true,
+                crypt_filters: filters(Arc::new(Aes256CryptFilter)),
+                file_encryption_key: &key,
+                stream_filter: b"StdCF".to_vec(),
+                string_filter: b"StdCF".to_vec(),
+                owner_password: "owner",
+                user_password,
+                permissions: Permissions::all(),
+            },
+        };
+        let state = EncryptionState::try_from(version).unwrap();
+        doc.encrypt(&state).unwrap();
+        let mut bytes = Vec::new();
+        doc.save_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    fn page_content(doc: &Document) -> Vec<u8> {
+        let page_id = *doc.get_pages().get(&1).unwrap();
+        doc.get_page_content(page_id).unwrap()
+    }
+
+    #[test]
+    fn unlocks_with_the_user_or_owner_password() {
+        for cipher in CIPHERS {
+            let bytes = encrypted(cipher, "user");
+            for password in ["user", "owner"] {
+                let unlocked = unlock(&bytes, password).unwrap_or_else(|e| panic!("{:?} {}: {}", cipher, password, e));
+                assert_eq!(page_content(&unlocked.document), CONTENT, "{:?} {}", cipher, password);
+                assert!(unlocked.encryption.is_some());
+                assert!(unlocked.document.trailer.get(b"Encrypt").is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn refuses_wrong_passwords() {
+        for cipher in CIPHERS {
+            let bytes = encrypted(cipher, "user");
+            for password in ["", "User", "ownerx"] {
+                let error = unlock(&bytes, password).err();
+                assert!(matches!(error, Some(UnlockError::WrongPassword)), "{:?} {:?}", cipher, password);
+            }
+        }
+    }
+
+    #[test]
+    fn opens_owner_only_files_without_a_password() {
+        for cipher in CIPHERS {
+            let unlocked = unlock(&encrypted(cipher, ""), "").unwrap();
+            assert_eq!(page_content(&unlocked.document), CONTENT, "{:?}", cipher);
+            assert!(unlocked.encryption.is_some());
+        }
+    }
+
+    #[test]
+    fn plain_files_ignore_the_password() {
+        let mut bytes = Vec::new();
+        plain_document().save_to(&mut bytes).unwrap();
+        let unlocked = unlock(&bytes, "anything").unwrap();
+        assert!(unlocked.encryption.is_none());
+        assert_eq!(page_content(&unlocked.document), CONTENT);
+    }
+
+    #[test]
+    fn owner_value_holds_the_padded_user_password() {
+        for (cipher, revision, key_length) in [(Cipher::Rc4_40, 2, 5), (Cipher::Rc4_128, 3, 16)] {
+            let doc = Document::load_mem(&encrypted(cipher, "user")).unwrap();
+            let encrypt = doc.get_encrypted().unwrap();
+            let owner_value = encrypt.get(b"O").and_then(Object::as_str).unwrap();
+            let user_password = decrypt_owner_value(b"owner", owner_value, revision, key_length);
+            assert_eq!(&user_password[..6], b"user\x28\xBF", "{:?}", cipher);
+        }
+    }
+
+    #[test]
+    fn rc4_matches_known_answers() {
+        assert_eq!(hex::encode(rc4(b"Key", b"Plaintext")), "bbf316e8d940af0ad3");
+        assert_eq!(hex::encode(rc4(b"Wiki", b"pedia")), "1021bf0420");
+        assert_eq!(hex::encode(rc4(b"Secret", b"Attack at dawn")), "45a01f645fc35b383552544b9bf5");
+        // Symmetric
+        assert_eq!(rc4(b"Key", &rc4(b"Key", b"Plaintext")), b"Plaintext");
+    }
+
+    #[test]
+    fn stale_copies_are_dropped() {
+        let dir = std::env::temp_dir().join(format!("twice-pdf-unlock-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("locked.pdf");
+        fs::write(&path, encrypted(Cipher::Aes128, "user")).unwrap();
+
+        let files = UnlockedFiles::default();
+        assert_eq!(files.unlock(&path, "nope").err().unwrap().kind, FileErrorKind::PasswordRequired);
+        assert!(files.get(&path).is_none());
+        files.unlock(&path, "user").unwrap();
+        assert!(files.get(&path).is_some());
+
+        // Rewritten on disk: the copy no longer matches
+        fs::write(&path, encrypted(Cipher::Rc4_128, "user")).unwrap();
+        assert!(files.get(&path).is_none());
+        assert!(files.lock().is_empty());
+
+        files.unlock(&path, "user").unwrap();
+        files.remove(&path);
+        assert!(files.get(&path).is_none());
+    }
+}
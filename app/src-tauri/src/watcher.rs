@@ -0,0 +1,325 @@
+//! Watch the file shown on each side of each window and tell that window when it changes
+//!
+//! Polls size and mtime rather than using OS notifications: build tools often
+//! replace the file (write + rename), which inotify-style watches on the file
+//! itself lose track of. Events are debounced until the file stops changing,
+//! so a half-written PDF is never reloaded, and are not sent when the new file
+//! has the same contents (a touch, or a build that wrote identical output).
+
+use crate::cli::Side;
+use crate::pdf_file::{self, FileInfo};
+use crate::unlock::UnlockedFiles;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use tauri::{AppHandle, Emitter, Manager};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long the file must stay unchanged before the event is sent
+const DEBOUNCE: Duration = Duration::from_millis(1000);
+
+/// Files up to this size are hashed whole, to tell a touch from an edit the change token misses.
+/// The viewer reads files this small in one go anyway.
+const FULL_DIGEST_LIMIT: u64 = 64 * 1024 * 1024;
+
+pub const FILE_CHANGED_EVENT: &str = "pdf-file-changed";
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FileChanged {
+    pub side: Side,
+    pub path: String,
+    pub size: u64,
+    pub modified: Option<u64>,
+    pub change_token: String,
+}
+
+/// Stop flag of the watcher thread for each window label and side
+type StopFlags = HashMap<(String, Side), Arc<AtomicBool>>;
+
+/// One watcher thread per window and side (managed Tauri state)
+#[derive(Default)]
+pub struct Watchers {
+    sides: Mutex<StopFlags>,
+}
+
+impl Watchers {
+    /// Start watching `path` for `side` of `window`, replacing whatever that side watched before.
+    /// Events name the file as `source`, the path the frontend opened it by.
+    pub fn watch(&self, app: AppHandle, window: &str, side: Side, path: PathBuf, source: String) -> Result<(), String> {
+        let (_, version) = read_version(&path)?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        if let Some(previous) = self.lock()?.insert((window.to_string(), side), stop.clone()) {
+            previous.store(true, Ordering::Relaxed);
+        }
+
+        let window = window.to_string();
+        thread::spawn(move || poll(app, window, side, path, source, version, stop));
+        Ok(())
+    }
+
+    pub fn unwatch(&self, window: &str, side: Side) -> Result<(), String> {
+        if let Some(stop) = self.lock()?.remove(&(window.to_string(), side)) {
+            stop.store(true, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    /// Stop both sides of a closed window
+    pub fn unwatch_window(&self, window: &str) -> Result<(), String> {
+        self.lock()?.retain(|(label, _), stop| {
+            let keep = label != window;
+            if !keep {
+                stop.store(true, Ordering::Relaxed);
+            }
+            keep
+        });
+        Ok(())
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, StopFlags>, String> {
+        self.sides.lock().map_err(|_| "Watcher state is poisoned".to_string())
+    }
+}
+
+/// Size and mtime, `None` while the file is missing
+type Stamp = Option<(u64, Option<SystemTime>)>;
+
+fn stamp(path: &Path) -> Stamp {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.len(), metadata.modified().ok()))
+}
+
+/// Waits for a file to stop changing
+struct Debounce {
+    last: Stamp,
+    /// Set while the file is changing; cleared once the change has been reported
+    changed_at: Option<Instant>,
+}
+
+impl Debounce {
+    fn new(stamp: Stamp) -> Self {
+        Debounce { last: stamp, changed_at: None }
+    }
+
+    /// Feed the latest stamp; true once per change, when it has been stable for `DEBOUNCE`
+    fn settled(&mut self, current: Stamp, now: Instant) -> bool {
+        if current != self.last {
+            self.last = current;
+            self.changed_at = Some(now);
+            return false;
+        }
+        // Missing or empty files are mid-rewrite, keep waiting
+        let settled = self.changed_at.is_some_and(|t| now.duration_since(t) >= DEBOUNCE)
+            && current.is_some_and(|(len, _)| len > 0);
+        if settled {
+            self.changed_at = None;
+        }
+        settled
+    }
+}
+
+/// What is known about the contents of one version of the file
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Version {
+    change_token: String,
+    modified: Option<u64>,
+    /// SHA-256 of the whole file, for files up to `FULL_DIGEST_LIMIT`
+    digest: Option<String>,
+}
+
+impl Version {
+    /// Whether this version may hold different contents than `previous`
+    fn differs_from(&self, previous: &Version) -> bool {
+        if self.change_token != previous.change_token {
+            return true;
+        }
+        if self.modified == previous.modified {
+            return false;
+        }
+        // Same ends but written again: only the whole file tells a touch from an edit in the middle.
+        // Without it, assume an edit.
+        match (&self.digest, &previous.digest) {
+            (Some(digest), Some(previous)) => digest != previous,
+            _ => true,
+        }
+    }
+}
+
+fn read_version(path: &Path) -> Result<(FileInfo, Version), String> {
+    let mut file = File::open(path).map_err(|e| format!("Failed to open file {}: {}", path.display(), e))?;
+    let info = pdf_file::file_info(path, &mut file)?;
+    let digest = if info.size <= FULL_DIGEST_LIMIT {
+        Some(full_digest(&mut file).map_err(|e| format!("Failed to read file {}: {}", path.display(), e))?)
+    } else {
+        None
+    };
+    let version = Version { change_token: info.change_token.clone(), modified: info.modified, digest };
+    Ok((info, version))
+}
+
+fn full_digest(file: &mut File) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    file.seek(SeekFrom::Start(0))?;
+    io::copy(file, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+fn poll(
+    app: AppHandle,
+    window: String,
+    side: Side,
+    path: PathBuf,
+    source: String,
+    mut version: Version,
+    stop: Arc<AtomicBool>,
+) {
+    let mut debounce = Debounce::new(stamp(&path));
+
+    while !stop.load(Ordering::Relaxed) {
+        thread::sleep(POLL_INTERVAL);
+        if !debounce.settled(stamp(&path), Instant::now()) {
+            continue;
+        }
+
+        let Ok((info, current)) = read_version(&path) else { continue };
+        let changed = current.differs_from(&version);
+        // Remembered either way, so a later touch compares against the newest mtime
+        version = current;
+        if !changed || stop.load(Ordering::Relaxed) {
+            continue;
+        }
+        // The decrypted copy of an encrypted file is stale now; the reload unlocks it again
+        app.state::<UnlockedFiles>().remove(&path);
+
+        let event = FileChanged {
+            side,
+            path: source.clone(),
+            size: info.size,
+            modified: info.modified,
+            change_token: info.change_token,
+        };
+        if let Err(e) = app.emit_to(window.as_str(), FILE_CHANGED_EVENT, event) {
+            log::warn!("Failed to emit {}: {}", FILE_CHANGED_EVENT, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("twice-pdf-watcher-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// `size` bytes of a sample PDF-ish body
+    fn contents(size: usize) -> Vec<u8> {
+        (0..size).map(|i| (i % 251) as u8).collect()
+    }
+
+    fn version(path: &Path) -> Version {
+        read_version(path).unwrap().1
+    }
+
+    /// Move the mtime forward like `touch` does
+    fn touch(path: &Path, seconds: u64) {
+        let file = File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(seconds)).unwrap();
+    }
+
+    #[test]
+    fn debounce_waits_for_the_file_to_settle() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let size = |len| Some((len, Some(SystemTime::UNIX_EPOCH)));
+        let mut debounce = Debounce::new(size(10));
+
+        assert!(!debounce.settled(size(10), at(5000)));
+        // Growing while it is written
+        assert!(!debounce.settled(size(20), at(0)));
+        assert!(!debounce.settled(size(30), at(500)));
+        assert!(!debounce.settled(size(30), at(1000)));
+        assert!(debounce.settled(size(30), at(1500)));
+        // Reported once
+        assert!(!debounce.settled(size(30), at(3000)));
+
+        // Missing or empty in the middle of a rewrite
+        assert!(!debounce.settled(None, at(4000)));
+        assert!(!debounce.settled(None, at(6000)));
+        assert!(!debounce.settled(size(0), at(6500)));
+        assert!(!debounce.settled(size(0), at(8000)));
+        assert!(!debounce.settled(size(40), at(8500)));
+        assert!(debounce.settled(size(40), at(9500)));
+    }
+
+    #[test]
+    fn a_touch_is_not_a_change() {
+        let dir = test_dir("touch");
+        let path = dir.join("doc.pdf");
+        fs::write(&path, contents(1000)).unwrap();
+        let before = version(&path);
+
+        touch(&path, 10);
+        let after = version(&path);
+        assert_ne!(after.modified, before.modified);
+        assert!(!after.differs_from(&before));
+        assert!(!before.differs_from(&before));
+    }
+
+    #[test]
+    fn same_size_edits_in_the_middle_are_changes() {
+        let dir = test_dir("middle");
+        let path = dir.join("doc.pdf");
+        // Large enough that the change token does not sample the middle
+        let mut data = contents(3 * 64 * 1024);
+        fs::write(&path, &data).unwrap();
+        let before = version(&path);
+
+        let middle = data.len() / 2;
+        data[middle] ^= 0xff;
+        fs::write(&path, &data).unwrap();
+        touch(&path, 10);
+        let after = version(&path);
+        assert_eq!(after.change_token, before.change_token);
+        assert!(after.differs_from(&before));
+
+        // Too large to hash whole: a new mtime has to count as a change
+        let huge = |v: &Version| Version { digest: None, ..v.clone() };
+        assert!(huge(&after).differs_from(&huge(&before)));
+    }
+
+    #[test]
+    fn atomic_replacements_compare_contents() {
+        let dir = test_dir("rename");
+        let path = dir.join("doc.pdf");
+        let temp = dir.join("doc.pdf.tmp");
+        fs::write(&path, contents(1000)).unwrap();
+        let before = version(&path);
+
+        // A rebuild that wrote the same output
+        fs::write(&temp, contents(1000)).unwrap();
+        touch(&temp, 10);
+        fs::rename(&temp, &path).unwrap();
+        let same = version(&path);
+        assert!(!same.differs_from(&before));
+
+        let mut data = contents(1000);
+        data[0] = b'%';
+        fs::write(&temp, &data).unwrap();
+        fs::rename(&temp, &path).unwrap();
+        assert!(version(&path).differs_from(&same));
+    }
+}
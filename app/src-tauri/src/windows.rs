@@ -0,0 +1,399 @@
+//! Comparison windows
+//!
+//! Besides `main`, any number of `comparison-<n>` windows can be opened, each
+//! running its own copy of the frontend with its own documents. The backend
+//! keeps track of the documents every window shows. Closing the main window
+//! quits the app and saves the open windows with their documents, size and
+//! position to `<app data>/windows.json`; the next start puts them back.
+
+use crate::atomic_write::{self, FileError};
+use crate::cli::LaunchOptions;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use tauri::{AppHandle, LogicalPosition, LogicalSize, Manager, WebviewUrl, WebviewWindow, WebviewWindowBuilder};
+
+pub const MAIN: &str = "main";
+
+/// Labels of additional windows; `capabilities/default.json` grants `comparison-*`
+const LABEL_PREFIX: &str = "comparison-";
+
+const TITLE: &str = "Twice PDF";
+
+/// Smallest inner size of every window
+const MIN_WIDTH: f64 = 800.0;
+const MIN_HEIGHT: f64 = 600.0;
+
+/// Outer position and inner size in logical pixels
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Geometry {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    #[serde(default)]
+    pub maximized: bool,
+}
+
+/// Local documents shown on each side
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct WindowDocuments {
+    #[serde(default)]
+    pub left: Option<String>,
+    #[serde(default)]
+    pub right: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WindowInfo {
+    pub label: String,
+    #[serde(flatten)]
+    pub documents: WindowDocuments,
+    #[serde(default)]
+    pub geometry: Option<Geometry>,
+}
+
+/// Read back as JSON values first, so one corrupt entry does not cost the others
+#[derive(Serialize, Deserialize, Default)]
+struct SavedWindows<W = WindowInfo> {
+    windows: Vec<W>,
+}
+
+/// Position and size of a monitor in logical pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Area {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+#[derive(Default)]
+struct Tracked {
+    documents: BTreeMap<String, WindowDocuments>,
+    next_id: usize,
+    /// Set once the main window closed; the other windows closing then is not a user choice
+    quitting: bool,
+}
+
+/// Open windows and their documents (managed Tauri state)
+pub struct ComparisonWindows {
+    /// Where the windows are saved; `None` for extra instances (`--new-instance`, queues),
+    /// which leave the session of the main instance alone
+    path: Option<PathBuf>,
+    tracked: Mutex<Tracked>,
+}
+
+impl ComparisonWindows {
+    pub fn new(path: Option<PathBuf>) -> Self {
+        ComparisonWindows { path, tracked: Mutex::new(Tracked::default()) }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Tracked> {
+        self.tracked.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Create a window showing `documents`, at `geometry` when given; returns its label
+    pub fn open(
+        &self,
+        app: &AppHandle,
+        documents: WindowDocuments,
+        geometry: Option<Geometry>,
+    ) -> Result<String, String> {
+        let label = {
+            let mut tracked = self.lock();
+            let label = loop {
+                tracked.next_id += 1;
+                let label = format!("{}{}", LABEL_PREFIX, tracked.next_id);
+                if app.get_webview_window(&label).is_none() {
+                    break label;
+                }
+            };
+            tracked.documents.insert(label.clone(), documents.clone());
+            label
+        };
+
+        let mut builder = WebviewWindowBuilder::new(app, &label, WebviewUrl::App("index.html".into()))
+            .title(title(&documents))
+            .inner_size(1400.0, 900.0)
+            .min_inner_size(MIN_WIDTH, MIN_HEIGHT)
+            .background_color(tauri::window::Color(0xf3, 0xf4, 0xf6, 0xff));
+        if let Some(geometry) = geometry.and_then(|g| fit(&g, &monitors(app))) {
+            builder = builder
+                .position(geometry.x, geometry.y)
+                .inner_size(geometry.width, geometry.height)
+                .maximized(geometry.maximized);
+        }
+        if let Err(e) = builder.build() {
+            self.lock().documents.remove(&label);
+            return Err(format!("Failed to open a window: {}", e));
+        }
+        Ok(label)
+    }
+
+    /// What the frontend of `label` should load on start (the main window gets the command line)
+    pub fn launch_options(&self, label: &str) -> LaunchOptions {
+        let documents = self.lock().documents.get(label).cloned().unwrap_or_default();
+        LaunchOptions {
+            left: documents.left,
+            right: documents.right,
+            ..Default::default()
+        }
+    }
+
+    /// Record what `window` shows now and put it in the title; returns what it showed before
+    pub fn set_documents(&self, window: &WebviewWindow, documents: WindowDocuments) -> Option<WindowDocuments> {
+        let _ = window.set_title(&title(&documents));
+        self.lock().documents.insert(window.label().to_string(), documents)
+    }
+
+    /// Whether any window shows the document at `path`
+    pub fn shows(&self, path: &str) -> bool {
+        self.lock()
+            .documents
+            .values()
+            .any(|documents| documents.left.as_deref() == Some(path) || documents.right.as_deref() == Some(path))
+    }
+
+    /// All windows with their documents
+    pub fn list(&self, app: &AppHandle) -> Vec<WindowInfo> {
+        // Not locked while asking the windows, which may wait for the main thread
+        let mut documents = self.lock().documents.clone();
+        app.webview_windows()
+            .into_iter()
+            .map(|(label, window)| WindowInfo {
+                documents: documents.remove(&label).unwrap_or_default(),
+                geometry: geometry(&window),
+                label,
+            })
+            .collect()
+    }
+
+    /// Forget a closed window, returns the documents it showed
+    pub fn remove(&self, label: &str) -> Option<WindowDocuments> {
+        let mut tracked = self.lock();
+        if tracked.quitting {
+            return None;
+        }
+        tracked.documents.remove(label)
+    }
+
+    /// Save every open window for the next start; later closes are no longer recorded
+    pub fn save(&self, app: &AppHandle) -> Result<(), FileError> {
+        let windows = self.list(app);
+        self.lock().quitting = true;
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let json = serde_json::to_vec_pretty(&SavedWindows { windows })
+            .map_err(|e| FileError::other(path, format!("Failed to serialise windows: {}", e)))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| FileError::io("write", dir, e))?;
+        }
+        atomic_write::write_file(path, &json, false)
+    }
+
+    /// Windows saved when the app last quit
+    pub fn saved(&self) -> Result<Vec<WindowInfo>, FileError> {
+        let Some(path) = &self.path else {
+            return Ok(Vec::new());
+        };
+        match fs::read(path) {
+            Ok(json) => serde_json::from_slice::<SavedWindows<serde_json::Value>>(&json)
+                .map(|saved| {
+                    saved
+                        .windows
+                        .into_iter()
+                        .filter_map(|window| match serde_json::from_value(window) {
+                            Ok(window) => Some(window),
+                            Err(e) => {
+                                log::warn!("Ignoring a saved window in {}: {}", path.display(), e);
+                                None
+                            }
+                        })
+                        .collect()
+                })
+                .map_err(|e| FileError::other(path, format!("Invalid window state {}: {}", path.display(), e))),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(FileError::io("read", path, e)),
+        }
+    }
+}
+
+/// Move and size an existing window (the main one) to where it was
+pub fn restore_geometry(window: &WebviewWindow, geometry: &Geometry) {
+    let Some(geometry) = fit(geometry, &monitors(window.app_handle())) else {
+        return;
+    };
+    let _ = window.unmaximize();
+    let _ = window.set_position(LogicalPosition::new(geometry.x, geometry.y));
+    let _ = window.set_size(LogicalSize::new(geometry.width, geometry.height));
+    if geometry.maximized {
+        let _ = window.maximize();
+    }
+}
+
+fn geometry(window: &WebviewWindow) -> Option<Geometry> {
+    let scale = window.scale_factor().ok()?;
+    let position = window.outer_position().ok()?.to_logical::<f64>(scale);
+    let size = window.inner_size().ok()?.to_logical::<f64>(scale);
+    Some(Geometry {
+        x: position.x,
+        y: position.y,
+        width: size.width,
+        height: size.height,
+        maximized: window.is_maximized().unwrap_or(false),
+    })
+}
+
+fn monitors(app: &AppHandle) -> Vec<Area> {
+    let monitors = app.available_monitors().unwrap_or_default();
+    monitors
+        .iter()
+        .map(|monitor| {
+            let scale = monitor.scale_factor();
+            let position = monitor.position().to_logical::<f64>(scale);
+            let size = monitor.size().to_logical::<f64>(scale);
+            Area { x: position.x, y: position.y, width: size.width, height: size.height }
+        })
+        .collect()
+}
+
+/// Put a saved geometry entirely on the monitor its top-left corner is on (or the nearest one),
+/// at least the minimum size and at most the monitor's. `None` without monitors.
+fn fit(geometry: &Geometry, monitors: &[Area]) -> Option<Geometry> {
+    let values = [geometry.x, geometry.y, geometry.width, geometry.height];
+    if !values.iter().all(|value| value.is_finite()) {
+        return None;
+    }
+    let distance = |area: &&Area| {
+        let dx = (area.x - geometry.x).max(geometry.x - (area.x + area.width)).max(0.0);
+        let dy = (area.y - geometry.y).max(geometry.y - (area.y + area.height)).max(0.0);
+        dx * dx + dy * dy
+    };
+    let area = monitors.iter().min_by(|a, b| distance(a).total_cmp(&distance(b)))?;
+
+    let width = geometry.width.max(MIN_WIDTH).min(area.width);
+    let height = geometry.height.max(MIN_HEIGHT).min(area.height);
+    Some(Geometry {
+        x: geometry.x.clamp(area.x, area.x + area.width - width),
+        y: geometry.y.clamp(area.y, area.y + area.height - height),
+        width,
+        height,
+        maximized: geometry.maximized,
+    })
+}
+
+fn title(documents: &WindowDocuments) -> String {
+    let name = |path: &Option<String>| {
+        path.as_deref()
+            .map(|p| Path::new(p).file_name().map_or(p.to_string(), |n| n.to_string_lossy().into_owned()))
+    };
+    match (name(&documents.left), name(&documents.right)) {
+        (Some(left), Some(right)) => format!("{} — {} ↔ {}", TITLE, left, right),
+        (Some(one), None) | (None, Some(one)) => format!("{} — {}", TITLE, one),
+        (None, None) => TITLE.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("twice-pdf-windows-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn geometry(x: f64, y: f64, width: f64, height: f64) -> Geometry {
+        Geometry { x, y, width, height, maximized: false }
+    }
+
+    /// A 1920x1080 monitor with a 1280x1024 one to its right
+    const MONITORS: [Area; 2] = [
+        Area { x: 0.0, y: 0.0, width: 1920.0, height: 1080.0 },
+        Area { x: 1920.0, y: 0.0, width: 1280.0, height: 1024.0 },
+    ];
+
+    #[test]
+    fn visible_geometry_is_kept() {
+        let saved = Geometry { maximized: true, ..geometry(100.0, 50.0, 1400.0, 900.0) };
+        assert_eq!(fit(&saved, &MONITORS), Some(saved));
+        let right = geometry(2000.0, 20.0, 1000.0, 800.0);
+        assert_eq!(fit(&right, &MONITORS), Some(right));
+    }
+
+    #[test]
+    fn off_screen_geometry_is_moved_onto_the_nearest_monitor() {
+        // A monitor that was unplugged further right
+        assert_eq!(fit(&geometry(4000.0, 100.0, 1000.0, 800.0), &MONITORS), Some(geometry(2200.0, 100.0, 1000.0, 800.0)));
+        // Above and left of everything
+        assert_eq!(fit(&geometry(-3000.0, -500.0, 1400.0, 900.0), &MONITORS), Some(geometry(0.0, 0.0, 1400.0, 900.0)));
+        // Hanging off the bottom of the monitor its corner is on
+        assert_eq!(fit(&geometry(1000.0, 700.0, 1400.0, 900.0), &MONITORS), Some(geometry(520.0, 180.0, 1400.0, 900.0)));
+    }
+
+    #[test]
+    fn sizes_are_clamped() {
+        assert_eq!(fit(&geometry(10.0, 10.0, 0.0, 0.0), &MONITORS), Some(geometry(10.0, 10.0, MIN_WIDTH, MIN_HEIGHT)));
+        assert_eq!(fit(&geometry(10.0, 10.0, -50.0, 700.0), &MONITORS), Some(geometry(10.0, 10.0, MIN_WIDTH, 700.0)));
+        assert_eq!(fit(&geometry(2000.0, 10.0, 5000.0, 3000.0), &MONITORS), Some(geometry(1920.0, 0.0, 1280.0, 1024.0)));
+    }
+
+    #[test]
+    fn nothing_is_restored_without_monitors_or_numbers() {
+        assert_eq!(fit(&geometry(10.0, 10.0, 1400.0, 900.0), &[]), None);
+        assert_eq!(fit(&geometry(f64::NAN, 10.0, 1400.0, 900.0), &MONITORS), None);
+        assert_eq!(fit(&geometry(10.0, 10.0, f64::INFINITY, 900.0), &MONITORS), None);
+    }
+
+    #[test]
+    fn corrupt_entries_are_ignored() {
+        let path = test_dir("corrupt").join("windows.json");
+        fs::write(
+            &path,
+            r#"{ "windows": [
+                { "label": "main", "geometry": { "x": 10, "y": 20, "width": 1400, "height": 900 } },
+                { "label": "comparison-1", "geometry": { "x": "left" } },
+                42,
+                { "left": "/docs/a.pdf" },
+                { "label": "comparison-2", "left": "/docs/a.pdf", "right": "/docs/b.pdf" }
+            ] }"#,
+        )
+        .unwrap();
+
+        let saved = ComparisonWindows::new(Some(path)).saved().unwrap();
+        let labels: Vec<_> = saved.iter().map(|window| window.label.as_str()).collect();
+        assert_eq!(labels, ["main", "comparison-2"]);
+        assert_eq!(saved[0].geometry, Some(geometry(10.0, 20.0, 1400.0, 900.0)));
+        assert_eq!(saved[1].documents.right.as_deref(), Some("/docs/b.pdf"));
+        assert_eq!(saved[1].geometry, None);
+    }
+
+    #[test]
+    fn unreadable_state_files() {
+        let dir = test_dir("state");
+        assert!(ComparisonWindows::new(Some(dir.join("missing.json"))).saved().unwrap().is_empty());
+        assert!(ComparisonWindows::new(None).saved().unwrap().is_empty());
+
+        let path = dir.join("windows.json");
+        fs::write(&path, "{ \"windows\": [").unwrap();
+        assert!(ComparisonWindows::new(Some(path)).saved().is_err());
+    }
+
+    #[test]
+    fn titles_name_the_documents() {
+        let documents = |left: Option<&str>, right: Option<&str>| WindowDocuments {
+            left: left.map(str::to_string),
+            right: right.map(str::to_string),
+        };
+        assert_eq!(title(&documents(None, None)), "Twice PDF");
+        assert_eq!(title(&documents(None, Some("/docs/b.pdf"))), "Twice PDF — b.pdf");
+        assert_eq!(title(&documents(Some("/docs/a.pdf"), Some("/x/b.pdf"))), "Twice PDF — a.pdf ↔ b.pdf");
+    }
+}
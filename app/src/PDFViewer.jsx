@@ -144,6 +144,9 @@ const PDFViewer = forwardRef(({
     onScroll,
     onUpload,
     onExport,
+    onOpenProject,      // Tauri: open a .twice project
+    onSaveProject,      // Tauri: save the session as a project (`saveAs` picks a new file)
+    onNewWindow,        // Tauri: open another comparison window
     comments,
     deleteComment,
     activeComment,
@@ -163,6 +166,10 @@ const PDFViewer = forwardRef(({
     onClose,
     onLoadFromUrl,
     onLoadFromPath, // Tauri: load from file path (native dialog)
+    recent,         // Tauri: recent files and pairs `{ files, pairs }`, shown in the upload zone
+    onOpenRecent,
+    onPinRecent,
+    onClearRecent,
     onFitToPage,
     isLoading = false,
     viewMode = 'single', // 'single' | 'continuous'
@@ -230,9 +237,26 @@ const PDFViewer = forwardRef(({
     const [rightPanelWidth, setRightPanelWidth] = useState(168);
     const [showPanelsMenu, setShowPanelsMenu] = useState(false);
 
+    // Panels saved in a project file
+    useEffect(() => {
+        const panels = pdf?.viewState?.panels;
+        if (!panels) return;
+        // eslint-disable-next-line react-hooks/set-state-in-effect -- Restoring state from the loaded project
+        setShowBookmarks(!!panels.bookmarks);
+        setShowAnnotations(!!panels.annotations);
+    }, [pdf]);
+
     // Bookmarks hook (user-defined bookmarks)
-    const pdfId = pdf?.name ? pdf.name.replace(/[^a-zA-Z0-9]/g, '_').toLowerCase() : null;
-    const { bookmarks, addBookmark, removeBookmark, renameBookmark } = useBookmarks(pdfId, onBookmarksChange);
+    const {
+        bookmarks, addBookmark, removeBookmark, renameBookmark, exportBookmarks, importBookmarks,
+    } = useBookmarks({
+        name: pdf?.name,
+        sourcePath: pdf?.sourcePath,
+        fingerprint: pdf?.doc?.fingerprints?.[0],
+        initialBookmarks: pdf?.viewState?.bookmarks,
+        side,
+        sourceUrl: pdf?.sourceUrl,
+    }, onBookmarksChange);
 
     const [hoveredLink, setHoveredLink] = useState(null);
 
@@ -346,8 +370,15 @@ const PDFViewer = forwardRef(({
             }
             return (page - 1);
         },
+        getPanels: () => ({ bookmarks: showBookmarks, annotations: showAnnotations }),
         allowRemote: () => import.meta.env.VITE_ENABLE_REMOTE_PDFS !== 'false',
-        triggerFitToPage: () => handleFitToPage(true)
+        triggerFitToPage: () => handleFitToPage(true),
+        goToPage: (targetPage) => scrollToPage(targetPage),
+        showComment: (comment) => {
+            scrollToPage(comment.page);
+            setCommentText(comment.text || '');
+            setActiveComment(comment);
+        }
     }));
 
     // Sync input value with prop page
@@ -2330,6 +2361,9 @@ const PDFViewer = forwardRef(({
                                         setDefaultScaleLevel={setDefaultScaleLevel}
                                         altTextSettings={altTextSettings}
                                         setAltTextSettings={setAltTextSettings}
+                                        onOpenProject={onOpenProject}
+                                        onSaveProject={onSaveProject}
+                                        onNewWindow={onNewWindow}
                                     />
                                 </div>
                             )}
@@ -2401,6 +2435,10 @@ const PDFViewer = forwardRef(({
                         onUpload={onUpload}
                         onLoadFromPath={onLoadFromPath}
                         onLoadFromUrl={onLoadFromUrl}
+                        recent={recent}
+                        onOpenRecent={onOpenRecent}
+                        onPinRecent={onPinRecent}
+                        onClearRecent={onClearRecent}
                         isLoading={isLoading}
                         isDragging={isDragging}
                         onDragOver={(e) => {
@@ -2470,6 +2508,20 @@ const PDFViewer = forwardRef(({
                                 onAddBookmark={addBookmark}
                                 onRemoveBookmark={removeBookmark}
                                 onRenameBookmark={renameBookmark}
+                                onImportBookmarks={async () => {
+                                    try {
+                                        await importBookmarks();
+                                    } catch (err) {
+                                        alert(`Failed to import bookmarks: ${err?.message || err}`);
+                                    }
+                                }}
+                                onExportBookmarks={async () => {
+                                    try {
+                                        await exportBookmarks();
+                                    } catch (err) {
+                                        alert(`Failed to export bookmarks: ${err?.message || err}`);
+                                    }
+                                }}
                                 currentPage={page}
                                 numPages={numPages}
                             />
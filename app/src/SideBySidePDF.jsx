@@ -1,10 +1,19 @@
 /* global __REVEAL_ABSOLUTE_PATH__, __PDF_SAMPLES_ROOT__ */
-import React, { useState, useRef, useEffect, useCallback } from 'react';
+import React, { useState, useRef, useEffect, useCallback, useMemo } from 'react';
 import { AlertTriangle, X, Check } from 'lucide-react';
 import * as pdfjsLib from 'pdfjs-dist';
 import 'pdfjs-dist/web/pdf_viewer.css';
 import PDFViewer from './PDFViewer';
-import { exportPDFWithAnnotations, downloadPDF, generateExportFilename, savePDFToPath } from './utils/pdfExport';
+import QueueBar from './components/QueueBar';
+import { exportPDFWithAnnotations, downloadPDF, generateExportFilename, exportPDFToPath, resolveExportPath, pickSavePath } from './utils/pdfExport';
+import { readPDFFromPath, getPDFFileInfo, readPDFRange, createRangeTransport, probeFile, unlockPDF, pickPDFFile, fetchRemotePDF } from './utils/tauriFiles';
+import { listStoredComments, addStoredComment, saveStoredComment, deleteStoredComment } from './utils/annotationStore';
+import { pickProjectFile, readProjectFile, saveProjectFile } from './utils/projectFile';
+import { appendJournal, readJournal, compactJournal, summarizeJournal } from './utils/journal';
+import { getQueue, queueNext, queuePrevious, queueGoto, setPairStatus } from './utils/reviewQueue';
+import { openComparisonWindow, setWindowDocuments, isMainWindow, listenToWindow } from './utils/comparisonWindows';
+import { listenToMenu } from './utils/appMenu';
+import { listRecent, recordRecent, pinRecent, clearRecent } from './utils/recentFiles';
 import { PDFDocument, PDFName, PDFArray, PDFNumber } from 'pdf-lib';
 import useAnnotations from './hooks/useAnnotations';
 
@@ -14,7 +23,50 @@ pdfjsLib.GlobalWorkerOptions.workerSrc = new URL(
     import.meta.url
 ).toString();
 
+// Local files bigger than this are loaded lazily through byte-range reads (Tauri only)
+const RANGE_LOAD_THRESHOLD = 64 * 1024 * 1024;
+const RANGE_CHUNK_SIZE = 1024 * 1024;
+
+// Ask for the password of an encrypted file until the backend accepts it
+const promptAndUnlock = async (filePath) => {
+    const fileName = filePath.split(/[\\/]/).pop();
+    let message = `"${fileName}" is password protected. Enter the password:`;
+    for (;;) {
+        const password = window.prompt(message);
+        if (password === null) throw new Error('A password is required to open this file');
+        try {
+            return await unlockPDF(filePath, password);
+        } catch (err) {
+            if (err?.kind !== 'passwordRequired') throw err;
+            message = `Wrong password for "${fileName}". Try again:`;
+        }
+    }
+};
+
+// Comments restored from a project file (or the journal) take the side they are loaded on
+const restoredComments = (viewState, side) =>
+    Object.fromEntries((viewState?.comments || []).map(comment => [comment.id, { ...comment, side }]));
+
+// Scroll position saved in a project, applied once the restored zoom has been laid out
+const restoreScroll = (viewerRef, viewState) => {
+    if (!viewState?.scrollTop && !viewState?.scrollLeft) return;
+    setTimeout(() => {
+        const viewer = viewerRef.current;
+        if (!viewer) return;
+        viewer.scrollTop = viewState.scrollTop || 0;
+        viewer.scrollLeft = viewState.scrollLeft || 0;
+    }, 200);
+};
+
+// The change token misses same-size edits in the middle of a file, so the mtime must match too
+const isSameVersion = (fileInfo, modified, changeToken) =>
+    !!fileInfo && fileInfo.changeToken === changeToken && fileInfo.modified === modified;
 
+// Identifies a document in the autosave journal: local path or URL, plus the pdf.js fingerprint
+const journalDoc = (pdf) => pdf ? {
+    source: pdf.sourcePath || (/^https?:\/\//i.test(pdf.sourceUrl || '') ? pdf.sourceUrl : null),
+    fingerprint: pdf.doc?.fingerprints?.[0] || null
+} : {};
 
 const fetchPDF = async (targetUrl, proxyType = null) => {
     const allowRemote = import.meta.env.VITE_ENABLE_REMOTE_PDFS !== 'false';
@@ -78,11 +130,52 @@ export default function SideBySidePDF() {
         const saved = localStorage.getItem('pdftwice_export_settings');
         return saved ? JSON.parse(saved) : {
             autoSaveToSource: false,
+            incrementalSave: false,
+            keepBackup: true,
+            keepEncryption: true,
+            collisionPolicy: 'overwrite',
+            reloadOnChange: 'ask',
             filenamePrefix: '',
             filenameSuffix: '_commented'
         };
     });
 
+    // The backend tracks which documents each window shows, and titles the window after them
+    const leftSourcePath = leftPDF?.sourcePath ?? null;
+    const rightSourcePath = rightPDF?.sourcePath ?? null;
+    useEffect(() => {
+        if (!isTauri) return;
+        setWindowDocuments(leftSourcePath, rightSourcePath)
+            .catch(err => console.warn('Failed to report window documents:', err));
+    }, [isTauri, leftSourcePath, rightSourcePath]);
+
+    // Comments on files opened from disk are persisted by the backend as they change (Tauri only)
+    // Every change is also journaled, so a crash before the next export can be recovered
+    const sourcePathsRef = useRef({ left: null, right: null });
+    const journalDocsRef = useRef({ left: {}, right: {} });
+    useEffect(() => {
+        sourcePathsRef.current = { left: leftPDF?.sourcePath, right: rightPDF?.sourcePath };
+        journalDocsRef.current = { left: journalDoc(leftPDF), right: journalDoc(rightPDF) };
+    }, [leftPDF, rightPDF]);
+    const commentStore = useMemo(() => {
+        if (!isTauri) return undefined;
+        const persist = (op, action) => (comment) => {
+            appendJournal({
+                op,
+                side: comment.side,
+                ...journalDocsRef.current[comment.side],
+                data: op === 'deleteComment' ? { id: comment.id } : comment
+            });
+            const path = sourcePathsRef.current[comment.side];
+            if (path) action(path, comment).catch(err => console.warn('Failed to store comment:', err));
+        };
+        return {
+            add: persist('addComment', addStoredComment),
+            save: persist('saveComment', saveStoredComment),
+            remove: persist('deleteComment', (path, comment) => deleteStoredComment(path, comment.id))
+        };
+    }, [isTauri]);
+
     // Annotations (comments/highlights) from hook
     const {
         comments,
@@ -103,7 +196,25 @@ export default function SideBySidePDF() {
         addHighlight,
         saveComment,
         deleteComment,
-    } = useAnnotations({ authorName });
+    } = useAnnotations({ authorName, store: commentStore });
+
+    // Comment drafts are journaled too (debounced), so a crash mid-edit keeps the text
+    const lastDraftRef = useRef({ left: 'null', right: 'null' });
+    useEffect(() => {
+        if (!isTauri) return;
+        const timer = setTimeout(() => {
+            const drafts = [['left', leftActiveComment, leftCommentText], ['right', rightActiveComment, rightCommentText]];
+            for (const [side, active, text] of drafts) {
+                const draft = active ? { ...active, text } : null;
+                const key = JSON.stringify(draft);
+                if (key === lastDraftRef.current[side]) continue;
+                lastDraftRef.current[side] = key;
+                appendJournal({ op: 'draft', side, ...journalDocsRef.current[side], data: draft });
+            }
+        }, 500);
+        return () => clearTimeout(timer);
+    }, [isTauri, leftActiveComment, rightActiveComment, leftCommentText, rightCommentText]);
+
     const [leftBookmarks, setLeftBookmarks] = useState([]);
     const [rightBookmarks, setRightBookmarks] = useState([]);
     const lastExportedLeftBookmarks = useRef(null); // Track last exported bookmark count
@@ -136,29 +247,6 @@ export default function SideBySidePDF() {
         }
     }, []);
 
-    // F11 fullscreen toggle (Tauri only)
-    useEffect(() => {
-        if (!isTauri) return;
-
-        const handleKeyDown = async (e) => {
-            if (e.key === 'F11') {
-                e.preventDefault();
-                try {
-                    const win = window.__TAURI__?.window?.getCurrentWindow?.();
-                    if (win) {
-                        const isFullscreen = await win.isFullscreen();
-                        await win.setFullscreen(!isFullscreen);
-                    }
-                } catch (err) {
-                    console.warn('Failed to toggle fullscreen:', err);
-                }
-            }
-        };
-
-        window.addEventListener('keydown', handleKeyDown);
-        return () => window.removeEventListener('keydown', handleKeyDown);
-    }, [isTauri]);
-
     // Drag & Drop logic moved to after loadPdfFromPath definition to avoid ReferenceError
 
     const [viewMode, setViewMode] = useState(() => localStorage.getItem('pdf_view_mode') || 'single');
@@ -211,7 +299,11 @@ export default function SideBySidePDF() {
     // Calculate fit scale BEFORE first render to avoid wasteful scale=1 render
     useEffect(() => {
         if (leftPDF) {
-            if (scaleMode === 'fit') {
+            if (leftPDF.viewState?.scale) {
+                // Opened from a project: keep its zoom and scroll position
+                setLeftScale(leftPDF.viewState.scale);
+                restoreScroll(leftViewerRef, leftPDF.viewState);
+            } else if (scaleMode === 'fit') {
                 // Calculate fit scale synchronously using RAF for DOM readiness
                 requestAnimationFrame(async () => {
                     try {
@@ -239,7 +331,10 @@ export default function SideBySidePDF() {
 
     useEffect(() => {
         if (rightPDF) {
-            if (scaleMode === 'fit') {
+            if (rightPDF.viewState?.scale) {
+                setRightScale(rightPDF.viewState.scale);
+                restoreScroll(rightViewerRef, rightPDF.viewState);
+            } else if (scaleMode === 'fit') {
                 requestAnimationFrame(async () => {
                     try {
                         const pageObj = await rightPDF.doc.getPage(1);
@@ -303,7 +398,7 @@ export default function SideBySidePDF() {
         window.history.pushState({}, '', urlObj);
     };
 
-    const loadPDFFromURL = useCallback(async (originalUrl, side) => {
+    const loadPDFFromURL = useCallback(async (originalUrl, side, viewState = null) => {
         if (!originalUrl) return;
 
         const url = originalUrl.trim();
@@ -365,7 +460,16 @@ export default function SideBySidePDF() {
             let lastErr;
 
             // Proxy Fallback Chain: Direct -> AllOrigins -> CORS.lol -> corsproxy.io
+            // The desktop app downloads remote PDFs in the backend instead (no proxies)
             const attemptLoad = async () => {
+                if (isTauri && isRemoteCandidate) {
+                    try {
+                        return await fetchRemotePDF(url);
+                    } catch (err) {
+                        throw new Error(err?.message || String(err));
+                    }
+                }
+
                 // 1. Try Direct (or Vite Bridge)
                 try {
                     return await fetchPDF(fetchUrl, null);
@@ -459,15 +563,23 @@ export default function SideBySidePDF() {
                 numPages: pdfDoc.numPages,
                 data: storedData, // Use the preserved copy
                 sourceUrl: sourceUrl, // Store formatted URL for "Open outside"
-                outline: outline // PDF table of contents
+                outline: outline, // PDF table of contents
+                viewState // Page, zoom, scroll, panels and bookmarks from a project file
             };
 
+            const startPage = Math.min(Math.max(1, viewState?.page ?? 1), pdfDoc.numPages);
             if (side === 'left') {
                 setLeftPDF(pdfData);
-                setLeftPage(1);
+                setLeftPage(startPage);
             } else {
                 setRightPDF(pdfData);
-                setRightPage(1);
+                setRightPage(startPage);
+            }
+            if (viewState) {
+                setComments(prev => ({
+                    ...Object.fromEntries(Object.entries(prev).filter(([, c]) => c.side !== side)),
+                    ...restoredComments(viewState, side)
+                }));
             }
             updateUrlParams(side, url);
         } catch (err) {
@@ -480,7 +592,7 @@ export default function SideBySidePDF() {
         } finally {
             setIsUrlLoading(prev => ({ ...prev, [side]: false }));
         }
-    }, []);
+    }, [setComments, isTauri]);
 
     useEffect(() => {
         const params = new URLSearchParams(window.location.search);
@@ -490,29 +602,49 @@ export default function SideBySidePDF() {
         if (rightUrl) loadPDFFromURL(rightUrl, 'right');
     }, [loadPDFFromURL]);
 
-    // Load PDF from local file path (Tauri only)
-    const loadPdfFromPath = useCallback(async (filePath, side) => {
+    // Load PDF from local file path (Tauri only); `knownProbe` skips probing a file sniffed already
+    const loadPdfFromPath = useCallback(async (filePath, side, initialPage = 1, viewState = null, knownProbe = null) => {
         const tauri = window.__TAURI__;
         if (!tauri?.core?.invoke) return;
 
         try {
             setIsUrlLoading(prev => ({ ...prev, [side]: true }));
 
-            // Use global Tauri object
-            const pdfBytes = await tauri.core.invoke('read_pdf_file', { path: filePath });
-
-
-            // Convert to Uint8Array and make a COPY for storage
-            // PDF.js may transfer buffer ownership to its worker, detaching the original
-            const originalData = new Uint8Array(pdfBytes);
-            const dataForPdfJs = originalData.slice(); // Copy for PDF.js to consume
+            const probe = knownProbe ?? await probeFile(filePath);
+            if (!probe.isPdf) throw new Error('Not a PDF file');
+            if (probe.truncated) console.warn(`${filePath} has no %%EOF marker and may be incomplete`);
+
+            const fileInfo = await getPDFFileInfo(filePath);
+
+            let originalData = null;
+            let loadingTask;
+            if (probe.passwordRequired) {
+                // Decrypted by the backend, which keeps the document for annotated exports
+                originalData = await promptAndUnlock(filePath);
+                loadingTask = pdfjsLib.getDocument({ data: originalData.slice(), isEvalSupported: false, verbosity: 0 });
+            } else if (fileInfo.size > RANGE_LOAD_THRESHOLD) {
+                // Large file: let PDF.js pull byte ranges on demand instead of reading it all up front
+                const initialData = await readPDFRange(filePath, 0, Math.min(RANGE_CHUNK_SIZE, fileInfo.size));
+                loadingTask = pdfjsLib.getDocument({
+                    range: createRangeTransport(pdfjsLib, filePath, fileInfo.size, initialData),
+                    rangeChunkSize: RANGE_CHUNK_SIZE,
+                    disableAutoFetch: true,
+                    isEvalSupported: false,
+                    verbosity: 0
+                });
+            } else {
+                // Convert to Uint8Array and make a COPY for storage
+                // PDF.js may transfer buffer ownership to its worker, detaching the original
+                originalData = await readPDFFromPath(filePath);
+                const dataForPdfJs = originalData.slice(); // Copy for PDF.js to consume
 
-            // Load with pdf.js (may detach the buffer)
-            const loadingTask = pdfjsLib.getDocument({ data: dataForPdfJs, isEvalSupported: false, verbosity: 0 });
+                // Load with pdf.js (may detach the buffer)
+                loadingTask = pdfjsLib.getDocument({ data: dataForPdfJs, isEvalSupported: false, verbosity: 0 });
+            }
             const doc = await loadingTask.promise;
 
             // Extract filename from path
-            const fileName = filePath.split('\\').pop() || filePath.split('/').pop() || 'document.pdf';
+            const fileName = filePath.split(/[\\/]/).pop() || 'document.pdf';
 
             // Get outline (bookmarks) if available
             let outline = [];
@@ -522,22 +654,43 @@ export default function SideBySidePDF() {
 
             const pdfData = {
                 doc,
-                data: originalData, // Use the preserved copy
+                data: originalData, // Use the preserved copy (null for range-loaded files, see doc.getData())
+                fileInfo,
+                encrypted: probe.encrypted,
                 numPages: doc.numPages,
                 name: fileName,
                 url: `file:///${filePath}`, // Kept for legacy compatibility if needed
                 sourceUrl: `file:///${filePath}`, // Required for FileInfo link and "Show in folder"
                 sourcePath: filePath, // Store original path for auto-save feature
                 outline,
+                viewState, // Page, zoom, scroll, panels and bookmarks from a project file
             };
 
+            const startPage = Math.min(Math.max(1, initialPage), doc.numPages);
             if (side === 'left') {
                 setLeftPDF(pdfData);
-                setLeftPage(1);
+                setLeftPage(startPage);
             } else {
                 setRightPDF(pdfData);
-                setRightPage(1);
+                setRightPage(startPage);
             }
+
+            // Show the notes stored for this document; the previous file's stay in the store.
+            // Restored comments (project file or journal) win and are written to the store.
+            const restored = restoredComments(viewState, side);
+            Object.values(restored).forEach(comment => saveStoredComment(filePath, comment)
+                .catch(err => console.warn('Failed to store comment:', err)));
+            listStoredComments(filePath, side)
+                .then(stored => setComments(prev => ({
+                    ...Object.fromEntries(Object.entries(prev).filter(([, c]) => c.side !== side)),
+                    ...stored,
+                    ...restored
+                })))
+                .catch(err => console.warn(`Failed to load stored comments for ${filePath}:`, err));
+
+            // Reload (or offer to) when the file is rewritten, e.g. by a build process
+            tauri.core.invoke('watch_pdf_file', { side, path: filePath })
+                .catch(err => console.warn(`Failed to watch ${filePath}:`, err));
         } catch (err) {
             console.error(`Failed to load PDF from path ${filePath}:`, err);
             setLoadingError({
@@ -548,7 +701,7 @@ export default function SideBySidePDF() {
         } finally {
             setIsUrlLoading(prev => ({ ...prev, [side]: false }));
         }
-    }, []);
+    }, [setComments]);
 
     // Native Drag & Drop using Tauri v2 API
     // Uses getCurrentWebview().onDragDropEvent() instead of legacy tauri://file-drop events
@@ -583,10 +736,15 @@ export default function SideBySidePDF() {
                         if (Array.isArray(paths) && paths.length > 0) {
                             const width = window.innerWidth;
                             const side = position.x < width / 2 ? 'left' : 'right';
-                            const pdfPath = paths.find(p => p.toLowerCase().endsWith('.pdf'));
-                            if (pdfPath) {
-                                loadPdfFromPath(pdfPath, side);
-                            }
+                            // Sniff the content, so extensionless or oddly named PDFs work and fake ones don't
+                            Promise.all(paths.map(p => probeFile(p).catch(() => null))).then(probes => {
+                                const pdf = probes.find(probe => probe?.isPdf);
+                                if (pdf) {
+                                    loadPdfFromPath(pdf.path, side, 1, null, pdf);
+                                } else {
+                                    setLoadingError({ side, url: paths[0], message: 'The dropped file is not a PDF.' });
+                                }
+                            });
                         }
                         setDragTarget('none');
                         clearTimeout(dragTimeout);
@@ -609,32 +767,267 @@ export default function SideBySidePDF() {
     }, [isTauri, loadPdfFromPath]);
 
 
-    // Load CLI argument PDFs on mount (Tauri only - fails gracefully in web mode)
+    // Latest view state for the file watcher listener, which is only subscribed once
+    const watchStateRef = useRef({});
+    watchStateRef.current = {
+        leftPDF,
+        rightPDF,
+        leftPage,
+        rightPage,
+        leftScale,
+        rightScale,
+        reloadOnChange: exportSettings.reloadOnChange || 'ask'
+    };
+
+    // Source file rewritten on disk (Tauri only): reload the side, keeping page, zoom and sync offset
+    useEffect(() => {
+        let unlisten = null;
+        let disposed = false;
+
+        listenToWindow('pdf-file-changed', ({ payload }) => {
+            const { side, path, modified, changeToken } = payload;
+            const state = watchStateRef.current;
+            const pdf = side === 'left' ? state.leftPDF : state.rightPDF;
+
+            // The side may show a different file by now, or already have this version
+            if (!pdf || pdf.sourcePath !== path || isSameVersion(pdf.fileInfo, modified, changeToken)) return;
+            if (state.reloadOnChange === 'off') return;
+            if (state.reloadOnChange === 'ask' && !window.confirm(`"${pdf.name}" changed on disk. Reload it?`)) return;
+
+            // Restored like a project view state, so the default zoom is not applied again
+            const viewer = (side === 'left' ? leftViewerRef : rightViewerRef).current;
+            const page = side === 'left' ? state.leftPage : state.rightPage;
+            loadPdfFromPath(path, side, page, {
+                page,
+                scale: side === 'left' ? state.leftScale : state.rightScale,
+                scrollTop: viewer?.scrollTop || 0,
+                scrollLeft: viewer?.scrollLeft || 0
+            });
+        })?.then(fn => {
+            if (disposed) fn();
+            else unlisten = fn;
+        });
+
+        return () => {
+            disposed = true;
+            if (unlisten) unlisten();
+        };
+    }, [loadPdfFromPath]);
+
+    // Project files (.twice, Tauri only): the whole comparison session in one file
+    const projectPathRef = useRef(null);
+
+    const openProject = useCallback(async (projectPath) => {
+        try {
+            const path = projectPath ?? await pickProjectFile();
+            if (!path) return;
+            const project = await readProjectFile(path);
+            projectPathRef.current = path;
+
+            // Applied without persisting, like launch options
+            if (project.viewMode) setViewMode(project.viewMode);
+            setSyncOffset(project.syncOffset ?? 0);
+            setSyncScroll(project.syncScroll !== false);
+
+            for (const side of ['left', 'right']) {
+                const state = project[side];
+                if (!state) {
+                    if (side === 'left') setLeftPDF(null);
+                    else setRightPDF(null);
+                } else if (/^https?:\/\//i.test(state.source)) {
+                    loadPDFFromURL(state.source, side, state);
+                } else {
+                    await loadPdfFromPath(state.source, side, state.page, state);
+                }
+            }
+        } catch (err) {
+            console.error('Failed to open project:', err);
+            alert(`Failed to open project: ${err?.message || err}`);
+        }
+    }, [loadPdfFromPath, loadPDFFromURL]);
+
+    const saveProject = async (saveAs = false) => {
+        const sideState = (side) => {
+            const pdf = side === 'left' ? leftPDF : rightPDF;
+            const source = pdf?.sourcePath || (/^https?:\/\//i.test(pdf?.sourceUrl || '') ? pdf.sourceUrl : null);
+            if (!source) return null;
+            const viewer = (side === 'left' ? leftViewerRef : rightViewerRef).current;
+            const viewerApi = (side === 'left' ? leftComponentRef : rightComponentRef).current;
+            return {
+                source,
+                page: side === 'left' ? leftPage : rightPage,
+                scale: side === 'left' ? leftScale : rightScale,
+                scrollTop: viewer?.scrollTop || 0,
+                scrollLeft: viewer?.scrollLeft || 0,
+                panels: viewerApi?.getPanels?.() || {},
+                comments: Object.values(comments)
+                    .filter(c => c.side === side)
+                    .map(({ side: _side, ...comment }) => comment),
+                bookmarks: side === 'left' ? leftBookmarks : rightBookmarks,
+            };
+        };
+
+        const left = sideState('left');
+        const right = sideState('right');
+        const unsaved = [leftPDF && !left && leftPDF.name, rightPDF && !right && rightPDF.name].filter(Boolean);
+        if (unsaved.length > 0 && !window.confirm(
+            `${unsaved.join(' and ')} was not opened from a file or URL and will not be part of the project. Save anyway?`
+        )) return;
+
+        try {
+            const project = { version: 1, left, right, syncOffset, syncScroll, viewMode };
+            const path = await saveProjectFile(saveAs ? null : projectPathRef.current, project);
+            if (!path) return;
+            projectPathRef.current = path;
+            setToast({ visible: true, message: `Saved project ${path.split(/[\\/]/).pop()}` });
+        } catch (err) {
+            console.error('Failed to save project:', err);
+            alert(err.kind ? err.message : 'Failed to save project.');
+        }
+    };
+
+    // Offer to replay changes the last session never exported (e.g. after a crash)
+    const recoverJournal = useCallback(async () => {
+        const entries = await readJournal();
+        if (entries.length === 0) return;
+        const docs = summarizeJournal(entries);
+        const names = docs.map(doc => doc.source ? `"${doc.source.split(/[\\/]/).pop().split('?')[0]}"` : 'an unsaved document');
+        if (!window.confirm(`The last session ended with unsaved changes to ${names.join(', ')}. Recover them?`)) {
+            await compactJournal();
+            return;
+        }
+
+        // Reopen the most recently changed documents, on their last side where possible
+        const bySide = {};
+        for (const doc of docs.filter(d => d.source)) {
+            const side = doc.side && !bySide[doc.side] ? doc.side : ['left', 'right'].find(s => !bySide[s]);
+            if (!side) break;
+            bySide[side] = doc;
+        }
+        for (const [side, doc] of Object.entries(bySide)) {
+            const viewState = {
+                page: doc.draft?.page ?? 1,
+                comments: Object.values(doc.comments),
+                bookmarks: doc.bookmarks
+            };
+            if (/^https?:\/\//i.test(doc.source)) {
+                await loadPDFFromURL(doc.source, side, viewState);
+            } else {
+                await loadPdfFromPath(doc.source, side, viewState.page, viewState);
+            }
+            if (doc.draft) {
+                const { text, ...active } = doc.draft;
+                if (side === 'left') {
+                    setLeftActiveComment({ ...active, side });
+                    setLeftCommentText(text || '');
+                } else {
+                    setRightActiveComment({ ...active, side });
+                    setRightCommentText(text || '');
+                }
+            }
+        }
+    }, [loadPdfFromPath, loadPDFFromURL, setLeftActiveComment, setLeftCommentText, setRightActiveComment, setRightCommentText]);
+
+    // Review queue (`twice-pdf queue`): the backend tracks position and statuses,
+    // the viewers show the current pair
+    const [queue, setQueue] = useState(null);
+    const queueRef = useRef(null);
+    const showQueue = useCallback(async (next) => {
+        const pair = next.pairs[next.current];
+        const previous = queueRef.current?.pairs[queueRef.current.current];
+        queueRef.current = next;
+        setQueue(next);
+        if (!pair || (previous && previous.left === pair.left && previous.right === pair.right)) return;
+        await loadPdfFromPath(pair.left, 'left');
+        await loadPdfFromPath(pair.right, 'right');
+    }, [loadPdfFromPath]);
+
+    const updateQueue = (request) => {
+        request
+            .then(showQueue)
+            .catch(err => alert(`Review queue: ${err?.message || err}`));
+    };
+
+    // Apply CLI launch options, from this process or forwarded by a later launch
+    // View options are applied without persisting, so a scripted launch doesn't overwrite saved settings
+    const applyLaunchOptions = useCallback(async (options, startup) => {
+        if (options.author) setAuthorName(options.author);
+        if (options.viewMode) setViewMode(options.viewMode);
+        if (options.zoom?.mode === 'fit') {
+            setScaleMode('fit');
+        } else if (options.zoom?.mode === 'level') {
+            setScaleMode('level');
+            setDefaultScaleLevel(options.zoom.level);
+        }
+
+        const offset = options.syncOffset ?? 0;
+        if (options.syncOffset != null) setSyncOffset(offset);
+
+        if (options.project) {
+            openProject(options.project);
+            return;
+        }
+        if (startup && options.queue) {
+            const started = await getQueue();
+            if (started) await showQueue(started);
+            return;
+        }
+        // A fresh start (no files named): recover the previous session first
+        if (startup && !options.left && !options.right && isMainWindow()) {
+            await recoverJournal();
+        }
+
+        const leftStart = options.page ?? 1;
+        if (options.left) {
+            loadPdfFromPath(options.left, 'left', leftStart);
+        }
+        if (options.right) {
+            loadPdfFromPath(options.right, 'right', leftStart + offset);
+        }
+    }, [loadPdfFromPath, openProject, recoverJournal, showQueue]);
+
+    // Load CLI launch options on mount (Tauri only - fails gracefully in web mode)
     useEffect(() => {
-        const loadCliArgs = async () => {
+        const loadLaunchOptions = async () => {
             try {
                 // Use global Tauri object (set by withGlobalTauri: true)
                 const tauri = window.__TAURI__;
                 if (!tauri?.core?.invoke) return;
 
-                const paths = await tauri.core.invoke('get_cli_pdf_paths');
-
-                if (Array.isArray(paths) && paths.length > 0) {
-                    // Load first PDF on left
-                    loadPdfFromPath(paths[0], 'left');
-                    // Load second PDF on right (if provided)
-                    if (paths.length > 1) {
-                        loadPdfFromPath(paths[1], 'right');
-                    }
-                }
+                const options = await tauri.core.invoke('get_launch_options');
+                if (!options) return;
+                await applyLaunchOptions(options, true);
             } catch (err) {
                 // Fails silently in web mode or if Tauri is not ready
-                console.error('Failed to load CLI args:', err);
+                console.error('Failed to load launch options:', err);
             }
         };
 
-        loadCliArgs();
-    }, [loadPdfFromPath]);
+        loadLaunchOptions();
+    }, [applyLaunchOptions]);
+
+    // Files opened while the app is running (`twice-pdf a.pdf b.pdf`, double-click) arrive here
+    // instead of starting another instance. A second full comparison gets a window of its own.
+    useEffect(() => {
+        let unlisten = null;
+        let disposed = false;
+
+        listenToWindow('open-request', ({ payload }) => {
+            const { leftPDF: shownLeft, rightPDF: shownRight } = watchStateRef.current;
+            const request = shownLeft && shownRight && payload.left && payload.right && !payload.project
+                ? openComparisonWindow(payload.left, payload.right)
+                : applyLaunchOptions(payload, false);
+            request.catch(err => console.error('Failed to open forwarded files:', err));
+        })?.then(fn => {
+            if (disposed) fn();
+            else unlisten = fn;
+        });
+
+        return () => {
+            disposed = true;
+            if (unlisten) unlisten();
+        };
+    }, [applyLaunchOptions]);
 
     useEffect(() => {
         localStorage.setItem('pdf_author_name', authorName);
@@ -673,12 +1066,17 @@ export default function SideBySidePDF() {
     }, [setComments, setLeftDirty, setRightDirty]);
 
     useEffect(() => {
+        // Tauri sessions are recovered from the autosave journal instead
+        if (isTauri) {
+            localStorage.removeItem('pdf_comments_backup');
+            return;
+        }
         if (Object.keys(comments).length > 0) {
             localStorage.setItem('pdf_comments_backup', JSON.stringify(comments));
         } else {
             localStorage.removeItem('pdf_comments_backup');
         }
-    }, [comments]);
+    }, [comments, isTauri]);
 
     useEffect(() => {
         const handleBeforeUnload = (e) => {
@@ -1063,7 +1461,7 @@ export default function SideBySidePDF() {
             return;
         }
 
-        if (!pdfData.data) return;
+        if (!pdfData.data && !pdfData.doc) return;
 
         const sideComments = Object.values(comments).filter(c => c.side === side);
         const sideBookmarks = side === 'left' ? leftBookmarks : rightBookmarks;
@@ -1075,13 +1473,6 @@ export default function SideBySidePDF() {
         }
 
         try {
-            // Use pdfExport utility for the heavy lifting
-            const pdfBytes = await exportPDFWithAnnotations(
-                pdfData.data,
-                sideComments,
-                sideBookmarks
-            );
-
             // Generate filename with prefix/suffix from settings
             const filename = generateExportFilename(
                 pdfData.name,
@@ -1089,14 +1480,42 @@ export default function SideBySidePDF() {
                 exportSettings.filenameSuffix
             );
 
-            // Tauri auto-save mode: write directly to source folder
+            // Tauri auto-save mode: the backend annotates the source file and writes directly to its folder
             if (isTauri && exportSettings.autoSaveToSource && pdfData.sourcePath) {
-                const dir = pdfData.sourcePath.substring(0, pdfData.sourcePath.lastIndexOf('\\'));
-                const outputPath = `${dir}\\${filename}`;
-                await savePDFToPath(pdfBytes, outputPath);
-                setToast({ visible: true, message: `Saved as ${filename}` });
+                // Resolved by the backend with std::path, so it works with any OS path separator
+                const outputPath = await resolveExportPath(pdfData.sourcePath, {
+                    prefix: exportSettings.filenamePrefix,
+                    suffix: exportSettings.filenameSuffix,
+                    collisionPolicy: exportSettings.collisionPolicy,
+                    author: authorName,
+                    side
+                });
+                await exportPDFToPath(pdfData.sourcePath, sideComments, sideBookmarks, outputPath, {
+                    // Encrypted files are always rewritten
+                    incremental: exportSettings.incrementalSave && !pdfData.encrypted,
+                    backup: exportSettings.keepBackup !== false,
+                    keepEncryption: exportSettings.keepEncryption !== false
+                });
+                setToast({ visible: true, message: `Saved as ${outputPath.split(/[\\/]/).pop()}` });
+            } else if (isTauri && pdfData.sourcePath) {
+                // Tauri without auto-save: let the user pick the location natively
+                const outputPath = await pickSavePath(filename);
+                if (!outputPath) return;
+                await exportPDFToPath(pdfData.sourcePath, sideComments, sideBookmarks, outputPath, {
+                    // Encrypted files are always rewritten
+                    incremental: exportSettings.incrementalSave && !pdfData.encrypted,
+                    backup: exportSettings.keepBackup !== false,
+                    keepEncryption: exportSettings.keepEncryption !== false
+                });
+                setToast({ visible: true, message: `Saved as ${outputPath.split(/[\\/]/).pop()}` });
             } else {
-                // Standard browser download
+                // Use pdfExport utility for the heavy lifting, then standard browser download
+                // Range-loaded files are only fully read when exporting
+                const pdfBytes = await exportPDFWithAnnotations(
+                    pdfData.data ?? await pdfData.doc.getData(),
+                    sideComments,
+                    sideBookmarks
+                );
                 downloadPDF(pdfBytes, filename);
             }
 
@@ -1109,15 +1528,183 @@ export default function SideBySidePDF() {
                 setRightDirty(false);
             }
             localStorage.removeItem('pdf_comments_backup');
+            // The export commits this document's journaled changes
+            if (isTauri) {
+                compactJournal(journalDoc(pdfData))
+                    .catch(err => console.warn('Failed to compact autosave journal:', err));
+            }
         } catch (err) {
             console.error(`Error exporting ${side} PDF:`, err);
-            alert(`Failed to export ${side} PDF.`);
+            alert(err.kind ? err.message : `Failed to export ${side} PDF.`);
         }
     };
 
+    // Native menu (Tauri): actions apply to the viewer clicked last, the left one at first
+    const activeSideRef = useRef('left');
+    const menuActionRef = useRef(null);
+    menuActionRef.current = (action) => {
+        const openFile = (side) => pickPDFFile()
+            .then(path => path && loadPdfFromPath(path, side))
+            .catch(err => console.error('Failed to open file dialog:', err));
+        switch (action) {
+            case 'openLeft': return openFile('left');
+            case 'openRight': return openFile('right');
+            case 'openProject': return openProject();
+            case 'saveProject': return saveProject();
+            case 'newWindow':
+                return openComparisonWindow()
+                    .catch(err => alert(`Failed to open a window: ${err?.message || err}`));
+            case 'exportLeft': return processPDFAndDownload('left');
+            case 'exportRight': return processPDFAndDownload('right');
+            case 'toggleSync': return handleToggleSync();
+            case 'singlePage': return handleSetViewMode('single');
+            case 'continuous': return handleSetViewMode('continuous');
+            case 'nextPair': return queueRef.current && updateQueue(queueNext());
+            case 'previousPair': return queueRef.current && updateQueue(queuePrevious());
+        }
+
+        const side = activeSideRef.current;
+        const pdf = side === 'left' ? leftPDF : rightPDF;
+        const viewer = (side === 'left' ? leftComponentRef : rightComponentRef).current;
+        if (!pdf || !viewer) return;
+        const page = side === 'left' ? leftPage : rightPage;
+        const scale = side === 'left' ? leftScale : rightScale;
+        switch (action) {
+            case 'zoomIn': return handleScaleChange(Math.min(3, scale + 0.25), side);
+            case 'zoomOut': return handleScaleChange(Math.max(0.5, scale - 0.25), side);
+            case 'fitToPage':
+                viewer.triggerFitToPage();
+                return handleFitToPageSync(side);
+            case 'nextPage': return viewer.goToPage(Math.min(page + 1, pdf.numPages));
+            case 'previousPage': return viewer.goToPage(Math.max(page - 1, 1));
+            case 'firstPage': return viewer.goToPage(1);
+            case 'lastPage': return viewer.goToPage(pdf.numPages);
+            case 'goToPage': {
+                const target = parseInt(window.prompt(`Go to page (1–${pdf.numPages}):`, String(page)), 10);
+                if (target >= 1 && target <= pdf.numPages) viewer.goToPage(target);
+                return;
+            }
+            case 'nextComment':
+            case 'previousComment': {
+                // In reading order; from the open comment, else from the current page
+                const sideComments = Object.values(comments)
+                    .filter(c => c.side === side)
+                    .sort((a, b) => a.page - b.page || a.y - b.y || a.x - b.x);
+                if (sideComments.length === 0) return;
+                const active = side === 'left' ? leftActiveComment : rightActiveComment;
+                const index = sideComments.findIndex(c => c.id === active?.id);
+                const step = action === 'nextComment' ? 1 : -1;
+                let target;
+                if (index >= 0) {
+                    target = sideComments[(index + step + sideComments.length) % sideComments.length];
+                } else if (step > 0) {
+                    target = sideComments.find(c => c.page >= page) ?? sideComments[0];
+                } else {
+                    target = sideComments.findLast(c => c.page <= page) ?? sideComments[sideComments.length - 1];
+                }
+                return viewer.showComment(target);
+            }
+        }
+    };
+
+    useEffect(() => {
+        let unlisten = null;
+        let disposed = false;
+
+        listenToMenu(action => menuActionRef.current(action))?.then(fn => {
+            if (disposed) fn();
+            else unlisten = fn;
+        });
+
+        return () => {
+            disposed = true;
+            if (unlisten) unlisten();
+        };
+    }, []);
+
+    // Recent files and pairs (Tauri): the backend records what this window shows,
+    // the list feeds the upload zones and File ▸ Recent
+    const [recent, setRecent] = useState(null);
+    useEffect(() => {
+        if (!isTauri) return;
+        let unlisten = null;
+        let disposed = false;
+
+        listRecent()
+            .then(list => { if (!disposed) setRecent(list); })
+            .catch(err => console.warn('Failed to read recent files:', err));
+        listenToWindow('recent-changed', ({ payload }) => setRecent(payload))?.then(fn => {
+            if (disposed) fn();
+            else unlisten = fn;
+        });
+
+        return () => {
+            disposed = true;
+            if (unlisten) unlisten();
+        };
+    }, [isTauri]);
+
+    // Debounced, so paging through a document updates the list once it settles
+    useEffect(() => {
+        if (!isTauri || (!leftSourcePath && !rightSourcePath)) return;
+        const timer = setTimeout(() => {
+            recordRecent(
+                leftSourcePath ? { path: leftSourcePath, page: leftPage } : null,
+                rightSourcePath ? { path: rightSourcePath, page: rightPage } : null
+            ).catch(err => console.warn('Failed to record recent files:', err));
+        }, 1000);
+        return () => clearTimeout(timer);
+    }, [isTauri, leftSourcePath, rightSourcePath, leftPage, rightPage]);
+
+    // A pair fills both sides; a file goes to `side`, else an empty side, else the viewer clicked last
+    const openRecent = useCallback(async (entry, side = null) => {
+        if (entry.left) {
+            await loadPdfFromPath(entry.left.path, 'left', entry.left.page);
+            await loadPdfFromPath(entry.right.path, 'right', entry.right.page);
+            return;
+        }
+        const { leftPDF: shownLeft, rightPDF: shownRight } = watchStateRef.current;
+        const target = side ?? (!shownLeft ? 'left' : !shownRight ? 'right' : activeSideRef.current);
+        await loadPdfFromPath(entry.path, target, entry.page);
+    }, [loadPdfFromPath]);
+
+    // Entries picked in File ▸ Recent
+    useEffect(() => {
+        let unlisten = null;
+        let disposed = false;
+
+        listenToWindow('open-recent', ({ payload }) => {
+            openRecent(payload).catch(err => console.error('Failed to open recent file:', err));
+        })?.then(fn => {
+            if (disposed) fn();
+            else unlisten = fn;
+        });
+
+        return () => {
+            disposed = true;
+            if (unlisten) unlisten();
+        };
+    }, [openRecent]);
+
+    const handlePinRecent = (entry, pinned) => {
+        pinRecent(entry, pinned).catch(err => alert(`Failed to pin: ${err?.message || err}`));
+    };
+
+    const handleClearRecent = () => {
+        clearRecent().catch(err => alert(`Failed to clear recent files: ${err?.message || err}`));
+    };
+
     return (
         <div className="flex flex-col h-screen bg-gray-50 overflow-hidden">
-
+            {queue && (
+                <QueueBar
+                    queue={queue}
+                    onPrevious={() => updateQueue(queuePrevious())}
+                    onNext={() => updateQueue(queueNext())}
+                    onGoto={(index) => updateQueue(queueGoto(index))}
+                    onSetStatus={(index, status) => updateQueue(setPairStatus(index, status))}
+                />
+            )}
 
             {loadingError && (
                 <div className="fixed top-4 left-1/2 transform -translate-x-1/2 z-50 animate-in slide-in-from-top fade-in duration-300 w-full max-w-2xl px-4">
@@ -1135,7 +1722,13 @@ export default function SideBySidePDF() {
                 </div>
             )}
 
-            <main className="flex-1 flex overflow-hidden relative">
+            <main
+                className="flex-1 flex overflow-hidden relative"
+                onPointerDownCapture={(e) => {
+                    const side = e.target.closest?.('[data-side]')?.dataset.side;
+                    if (side) activeSideRef.current = side;
+                }}
+            >
                 {/* Drag Overlay - positioned relative to main content area */}
                 {dragTarget !== 'none' && (
                     <div className="absolute inset-0 z-50 pointer-events-none flex">
@@ -1174,6 +1767,10 @@ export default function SideBySidePDF() {
                     onScroll={(e, _side, info) => handleScroll(e, 'left', info)}
                     onUpload={(e) => handleFileUpload(e, 'left')}
                     onExport={() => processPDFAndDownload('left')}
+                    onOpenProject={() => openProject()}
+                    onSaveProject={saveProject}
+                    onNewWindow={() => openComparisonWindow()
+                        .catch(err => alert(`Failed to open a window: ${err?.message || err}`))}
                     comments={comments}
                     deleteComment={deleteComment}
                     activeComment={leftActiveComment}
@@ -1206,6 +1803,11 @@ export default function SideBySidePDF() {
                         }
                     }}
                     onLoadFromPath={(path) => loadPdfFromPath(path, 'left')}
+                    recent={recent}
+                    onOpenRecent={(entry) => openRecent(entry, 'left')
+                        .catch(err => alert(`Failed to open recent file: ${err?.message || err}`))}
+                    onPinRecent={handlePinRecent}
+                    onClearRecent={handleClearRecent}
                     onFitToPage={() => handleFitToPageSync('left')}
                     isLoading={isUrlLoading.left}
                     exportSettings={exportSettings}
@@ -1267,6 +1869,11 @@ export default function SideBySidePDF() {
                         }
                     }}
                     onLoadFromPath={(path) => loadPdfFromPath(path, 'right')}
+                    recent={recent}
+                    onOpenRecent={(entry) => openRecent(entry, 'right')
+                        .catch(err => alert(`Failed to open recent file: ${err?.message || err}`))}
+                    onPinRecent={handlePinRecent}
+                    onClearRecent={handleClearRecent}
                     onFitToPage={() => handleFitToPageSync('right')}
                     isLoading={isUrlLoading.right}
                     // Scale Settings (passed but only Left side triggers settings menu changes usually, but good for consistency)
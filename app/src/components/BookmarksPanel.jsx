@@ -7,8 +7,25 @@ import {
     Plus,
     Pencil,
     Trash2,
+    Upload,
+    Download,
 } from 'lucide-react';
 
+const transferButtonStyle = {
+    flex: 1,
+    padding: '4px',
+    display: 'flex',
+    alignItems: 'center',
+    justifyContent: 'center',
+    gap: '4px',
+    border: '1px solid #ddd',
+    borderRadius: '3px',
+    backgroundColor: '#fff',
+    color: '#666',
+    cursor: 'pointer',
+    fontSize: '10px',
+};
+
 /**
  * BookmarksPanel - Tree view merging PDF outline and user bookmarks
  * Light theme, consistent with app design
@@ -20,6 +37,8 @@ const BookmarksPanel = ({
     onAddBookmark,
     onRemoveBookmark,
     onRenameBookmark,
+    onImportBookmarks,
+    onExportBookmarks,
     currentPage = 1,
 }) => {
     const [filter, setFilter] = useState('all');
@@ -234,6 +253,27 @@ const BookmarksPanel = ({
                     <Plus size={12} />
                     Bookmark Page {currentPage}
                 </button>
+                <div style={{ display: 'flex', gap: '4px', marginTop: '4px' }}>
+                    <button
+                        onClick={() => onImportBookmarks?.()}
+                        style={transferButtonStyle}
+                        className="hover:text-blue-600"
+                        title="Import bookmarks from a file"
+                    >
+                        <Upload size={11} />
+                        Import
+                    </button>
+                    <button
+                        onClick={() => onExportBookmarks?.()}
+                        disabled={!hasBookmarks}
+                        style={{ ...transferButtonStyle, opacity: hasBookmarks ? 1 : 0.5, cursor: hasBookmarks ? 'pointer' : 'default' }}
+                        className={hasBookmarks ? 'hover:text-blue-600' : undefined}
+                        title="Export bookmarks to a file"
+                    >
+                        <Download size={11} />
+                        Export
+                    </button>
+                </div>
             </div>
 
             {/* Content */}
@@ -60,7 +60,8 @@ const FileInfo = ({
                                 }
                             }
                         } catch (err) {
-                            console.error("Failed to open generic file/link:", err);
+                            // show_in_folder rejects with { message, attempts: [{ mechanism, error }] }
+                            console.error("Failed to open generic file/link:", err?.message || err, err?.attempts || '');
                         }
                     } else {
                         // Web fallback
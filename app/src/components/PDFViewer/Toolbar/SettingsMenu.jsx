@@ -8,7 +8,8 @@ import { Github, ChevronDown, ChevronRight } from 'lucide-react';
  * Features:
  * - View mode toggle (PAGE / FULL)
  * - Author name input for annotations
- * - Export settings (Tauri only): auto-save, prefix/suffix
+ * - Export settings (Tauri only): reload on file change, auto-save, incremental save, backup, prefix/suffix patterns, collision policy
+ * - Project files (Tauri only): open / save / save as `.twice`
  * - Advanced settings (collapsible): Alt text fallback mode, show indicator
  * - Attribution links
  */
@@ -30,6 +31,9 @@ const SettingsMenu = ({
     setDefaultScaleLevel,
     altTextSettings,
     setAltTextSettings,
+    onOpenProject,
+    onSaveProject,
+    onNewWindow,
 }) => {
     const [showLevelInput, setShowLevelInput] = useState(false);
     const [tempLevel, setTempLevel] = useState(defaultScaleLevel?.toString() || '100');
@@ -163,6 +167,22 @@ const SettingsMenu = ({
                     <div className="p-1.5 border-t border-gray-200">
                         <div className="text-[10px] text-gray-400 mb-2 font-bold uppercase tracking-tight">Export</div>
 
+                        {/* Reload when the source file changes on disk */}
+                        <div className="flex items-center gap-1.5 mb-2">
+                            <span className="text-[11px] text-gray-600 flex-1">On file change</span>
+                            <select
+                                value={exportSettings.reloadOnChange || 'ask'}
+                                onChange={(e) => setExportSettings(prev => ({
+                                    ...prev, reloadOnChange: e.target.value
+                                }))}
+                                className="text-xs border border-gray-200 px-1 py-0.5 bg-gray-50 focus:outline-none focus:border-gray-400"
+                            >
+                                <option value="auto">Reload</option>
+                                <option value="ask">Ask first</option>
+                                <option value="off">Ignore</option>
+                            </select>
+                        </div>
+
                         {/* Auto-save toggle */}
                         <label className="flex items-center gap-2 text-[11px] mb-2 cursor-pointer text-gray-600">
                             <input
@@ -176,6 +196,54 @@ const SettingsMenu = ({
                             Save to source folder
                         </label>
 
+                        {/* Incremental save toggle */}
+                        <label
+                            className="flex items-center gap-2 text-[11px] mb-2 cursor-pointer text-gray-600"
+                            title="Append annotations instead of rewriting the file, so digital signatures stay valid"
+                        >
+                            <input
+                                type="checkbox"
+                                checked={!!exportSettings.incrementalSave}
+                                onChange={(e) => setExportSettings(prev => ({
+                                    ...prev, incrementalSave: e.target.checked
+                                }))}
+                                className="w-3.5 h-3.5"
+                            />
+                            Keep original bytes
+                        </label>
+
+                        {/* Backup toggle */}
+                        <label
+                            className="flex items-center gap-2 text-[11px] mb-2 cursor-pointer text-gray-600"
+                            title="Keep the previous version of an overwritten file as .bak"
+                        >
+                            <input
+                                type="checkbox"
+                                checked={exportSettings.keepBackup !== false}
+                                onChange={(e) => setExportSettings(prev => ({
+                                    ...prev, keepBackup: e.target.checked
+                                }))}
+                                className="w-3.5 h-3.5"
+                            />
+                            Keep .bak backup
+                        </label>
+
+                        {/* Encryption toggle */}
+                        <label
+                            className="flex items-center gap-2 text-[11px] mb-2 cursor-pointer text-gray-600"
+                            title="Exports of password-protected PDFs keep the same password (otherwise they are saved unprotected)"
+                        >
+                            <input
+                                type="checkbox"
+                                checked={exportSettings.keepEncryption !== false}
+                                onChange={(e) => setExportSettings(prev => ({
+                                    ...prev, keepEncryption: e.target.checked
+                                }))}
+                                className="w-3.5 h-3.5"
+                            />
+                            Keep password protection
+                        </label>
+
                         {/* Prefix input */}
                         <div className="flex items-center gap-1.5 mb-1.5">
                             <span className="text-[10px] text-gray-500 w-10">Prefix</span>
@@ -203,6 +271,71 @@ const SettingsMenu = ({
                                 className="flex-1 text-xs border border-gray-200 px-1.5 py-1 bg-gray-50 focus:outline-none focus:border-gray-400"
                             />
                         </div>
+
+                        {/* Collision policy */}
+                        <div className="flex items-center gap-1.5 mt-1.5">
+                            <span className="text-[10px] text-gray-500 w-10">Exists</span>
+                            <select
+                                value={exportSettings.collisionPolicy || 'overwrite'}
+                                onChange={(e) => setExportSettings(prev => ({
+                                    ...prev, collisionPolicy: e.target.value
+                                }))}
+                                className="flex-1 text-xs border border-gray-200 px-1 py-1 bg-gray-50 focus:outline-none focus:border-gray-400"
+                            >
+                                <option value="overwrite">Overwrite</option>
+                                <option value="number">Add number</option>
+                                <option value="fail">Don't save</option>
+                            </select>
+                        </div>
+                        <div className="text-[9px] text-gray-400 mt-1">
+                            Patterns: {'{name} {date} {author} {side}'}
+                        </div>
+                    </div>
+                )}
+
+                {/* Project files (Tauri only) */}
+                {isTauri && onOpenProject && onSaveProject && (
+                    <div className="p-1.5 border-t border-gray-200">
+                        <div className="text-[10px] text-gray-400 mb-2 font-bold uppercase tracking-tight">Project</div>
+                        <div className="flex border border-gray-200 bg-gray-50 p-0">
+                            <button
+                                onClick={() => { onClose?.(); onOpenProject(); }}
+                                className="flex-1 text-[10px] py-1.5 text-gray-500 hover:bg-gray-200 transition-colors"
+                                title="Restore a comparison saved as a .twice project"
+                            >
+                                Open…
+                            </button>
+                            <button
+                                onClick={() => { onClose?.(); onSaveProject(false); }}
+                                className="flex-1 text-[10px] py-1.5 text-gray-500 hover:bg-gray-200 transition-colors border-l border-gray-200"
+                                title="Save both documents, view state, comments and bookmarks as a project"
+                            >
+                                Save
+                            </button>
+                            <button
+                                onClick={() => { onClose?.(); onSaveProject(true); }}
+                                className="flex-1 text-[10px] py-1.5 text-gray-500 hover:bg-gray-200 transition-colors border-l border-gray-200"
+                                title="Save the project to a new file"
+                            >
+                                Save as…
+                            </button>
+                        </div>
+                    </div>
+                )}
+
+                {/* Comparison windows (Tauri only) */}
+                {isTauri && onNewWindow && (
+                    <div className="p-1.5 border-t border-gray-200">
+                        <div className="text-[10px] text-gray-400 mb-2 font-bold uppercase tracking-tight">Window</div>
+                        <div className="flex border border-gray-200 bg-gray-50 p-0">
+                            <button
+                                onClick={() => { onClose?.(); onNewWindow(); }}
+                                className="flex-1 text-[10px] py-1.5 text-gray-500 hover:bg-gray-200 transition-colors"
+                                title="Open another window for a separate comparison"
+                            >
+                                New comparison window
+                            </button>
+                        </div>
                     </div>
                 )}
 
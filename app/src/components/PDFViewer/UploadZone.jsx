@@ -1,5 +1,8 @@
 import React from 'react';
-import { Upload, Loader2 } from 'lucide-react';
+import { Upload, Loader2, Pin } from 'lucide-react';
+import { pickPDFFile } from '../../utils/tauriFiles';
+
+const fileName = (path) => path.split(/[\\/]/).pop();
 
 /**
  * UploadZone - PDF upload area with drag-drop and URL loading
@@ -9,12 +12,17 @@ import { Upload, Loader2 } from 'lucide-react';
  * - Uses native Tauri file dialog when available (provides full path for "Show in folder")
  * - URL loading (remote or local bridge)
  * - Loading state indicator
+ * - Recent files and pairs, with pinning (Tauri)
  * - Respects environment flags for remote/local options
  */
 const UploadZone = ({
     onUpload,
     onLoadFromPath, // New: callback for loading via file path (Tauri)
     onLoadFromUrl,
+    recent = null,  // `{ files, pairs }` from the backend (Tauri)
+    onOpenRecent,
+    onPinRecent,
+    onClearRecent,
     isLoading = false,
     isDragging = false,
     onDragOver,
@@ -31,22 +39,20 @@ const UploadZone = ({
     else if (allowRemote) placeholder = "https://...";
     else if (allowLocal) placeholder = "folder/file.pdf";
 
+    // Pairs first, then single files
+    const recentEntries = isTauri && recent ? [...recent.pairs, ...recent.files] : [];
+
     // Handle upload click - use native dialog in Tauri for full path access
     const handleUploadClick = async () => {
         if (isLoading) return;
 
         if (isTauri && onLoadFromPath) {
             try {
-                const dialog = window.__TAURI__?.dialog;
-                if (dialog?.open) {
-                    const selected = await dialog.open({
-                        filters: [{ name: 'PDF', extensions: ['pdf'] }],
-                        multiple: false,
-                    });
-                    if (selected) {
-                        // Tauri dialog returns the full path
-                        onLoadFromPath(selected);
-                    }
+                // The dialog runs in Rust so the picked file is added to the backend's file-access scope
+                const selected = await pickPDFFile();
+                if (selected) {
+                    // Tauri dialog returns the full path
+                    onLoadFromPath(selected);
                 }
             } catch (err) {
                 console.error('Failed to open file dialog:', err);
@@ -136,6 +142,59 @@ const UploadZone = ({
                     </form>
                 </>
             )}
+
+            {/* Recent files and pairs */}
+            {recentEntries.length > 0 && (
+                <div className="w-full max-w-xs mt-6">
+                    <div className="flex items-center justify-between mb-1">
+                        <span className="text-gray-400 text-[10px] font-bold uppercase tracking-wider">
+                            Recent
+                        </span>
+                        <button
+                            type="button"
+                            onClick={onClearRecent}
+                            className="text-[10px] text-gray-400 hover:text-blue-600"
+                            title="Forget everything except pinned entries"
+                        >
+                            Clear
+                        </button>
+                    </div>
+                    <ul className="max-h-48 overflow-y-auto border border-gray-200 bg-white">
+                        {recentEntries.map((entry) => {
+                            const key = entry.left ? `${entry.left.path}|${entry.right.path}` : entry.path;
+                            return (
+                                <li key={key} className="group flex items-center hover:bg-blue-50">
+                                    <button
+                                        type="button"
+                                        onClick={() => onOpenRecent?.(entry)}
+                                        disabled={isLoading}
+                                        className="flex-1 min-w-0 text-left text-xs px-2 py-1.5 text-gray-700 truncate disabled:opacity-50"
+                                        title={entry.left ? `${entry.left.path}\n${entry.right.path}` : entry.path}
+                                    >
+                                        {entry.left
+                                            ? `${fileName(entry.left.path)} ↔ ${fileName(entry.right.path)}`
+                                            : fileName(entry.path)}
+                                        <span className="ml-1 text-gray-400">
+                                            p. {entry.left ? `${entry.left.page}/${entry.right.page}` : entry.page}
+                                        </span>
+                                    </button>
+                                    <button
+                                        type="button"
+                                        onClick={() => onPinRecent?.(entry, !entry.pinned)}
+                                        className={`p-1.5 ${entry.pinned
+                                            ? 'text-blue-600'
+                                            : 'text-gray-300 opacity-0 group-hover:opacity-100 hover:text-blue-600'
+                                            }`}
+                                        title={entry.pinned ? 'Unpin' : 'Pin'}
+                                    >
+                                        <Pin className="w-3 h-3" />
+                                    </button>
+                                </li>
+                            );
+                        })}
+                    </ul>
+                </div>
+            )}
         </div>
     );
 };
@@ -0,0 +1,81 @@
+import React from 'react';
+import { ChevronLeft, ChevronRight, Check, Flag, RotateCcw } from 'lucide-react';
+import { queueProgress } from '../utils/reviewQueue';
+
+const fileName = (path) => path.split(/[\\/]/).pop();
+
+const STATUS_MARKS = { pending: '○', reviewed: '✓', flagged: '⚑' };
+
+/**
+ * QueueBar - Navigation and status for the pairs of a review queue
+ * Shown above the viewers when the app was started with `twice-pdf queue`
+ */
+const QueueBar = ({ queue, onPrevious, onNext, onGoto, onSetStatus }) => {
+    const pair = queue.pairs[queue.current];
+    const progress = queueProgress(queue);
+    const isFirst = queue.current === 0;
+    const isLast = queue.current === queue.pairs.length - 1;
+
+    const statusButton = (status, Icon, label, activeClass) => (
+        <button
+            onClick={() => onSetStatus(queue.current, status)}
+            className={`flex items-center gap-1 px-2 py-1 text-xs border rounded-none transition-colors
+                ${pair.status === status ? activeClass : 'border-gray-300 text-gray-600 hover:bg-gray-100'}`}
+            title={`Mark this pair as ${status}`}
+        >
+            <Icon className="w-3.5 h-3.5" />
+            {label}
+        </button>
+    );
+
+    return (
+        <div className="flex items-center gap-3 px-3 py-1.5 bg-white border-b border-gray-300 text-sm">
+            <span className="font-semibold text-gray-700 truncate max-w-[200px]" title={queue.name}>{queue.name}</span>
+
+            <button
+                onClick={onPrevious}
+                disabled={isFirst}
+                className="p-1 text-gray-600 hover:bg-gray-100 disabled:opacity-30 rounded-none"
+                title="Previous pair"
+            >
+                <ChevronLeft className="w-4 h-4" />
+            </button>
+            <select
+                value={queue.current}
+                onChange={(e) => onGoto(Number(e.target.value))}
+                className="text-xs border border-gray-300 rounded-none px-1 py-0.5 max-w-[360px]"
+            >
+                {queue.pairs.map((p, index) => (
+                    <option key={`${p.left}|${p.right}`} value={index}>
+                        {STATUS_MARKS[p.status]} {index + 1}. {fileName(p.left)} ↔ {fileName(p.right)}
+                    </option>
+                ))}
+            </select>
+            <button
+                onClick={onNext}
+                disabled={isLast}
+                className="p-1 text-gray-600 hover:bg-gray-100 disabled:opacity-30 rounded-none"
+                title="Next pair"
+            >
+                <ChevronRight className="w-4 h-4" />
+            </button>
+
+            <div className="flex items-center gap-1">
+                {statusButton('reviewed', Check, 'Reviewed', 'border-green-500 bg-green-50 text-green-700')}
+                {statusButton('flagged', Flag, 'Flagged', 'border-amber-500 bg-amber-50 text-amber-700')}
+                {pair.status !== 'pending' && statusButton('pending', RotateCcw, 'Reset', 'border-gray-300 text-gray-600')}
+            </div>
+
+            <span
+                className="ml-auto text-xs text-gray-500 whitespace-nowrap"
+                title={queue.unmatched.length > 0 ? `Without a partner:\n${queue.unmatched.map(fileName).join('\n')}` : undefined}
+            >
+                {progress.reviewed} of {queue.pairs.length} reviewed
+                {progress.flagged > 0 && ` · ${progress.flagged} flagged`}
+                {queue.unmatched.length > 0 && ` · ${queue.unmatched.length} unmatched`}
+            </span>
+        </div>
+    );
+};
+
+export default QueueBar;
@@ -8,12 +8,14 @@ import { useState, useCallback } from 'react';
  * - Highlights with rectangular regions
  * - Per-side active comment state
  * - Dirty tracking for unsaved changes
+ * - Optional persistence of every change (e.g. the Tauri annotation store)
  * 
  * @param {Object} options
  * @param {string} options.authorName - Default author for new comments
+ * @param {Object} [options.store] - Called after each change: `add(comment)`, `save(comment)`, `remove(comment)`
  * @returns {Object} Annotation state and handlers
  */
-const useAnnotations = ({ authorName = 'Author' } = {}) => {
+const useAnnotations = ({ authorName = 'Author', store } = {}) => {
     // Core annotations state
     const [comments, setComments] = useState({});
 
@@ -59,23 +61,22 @@ const useAnnotations = ({ authorName = 'Author' } = {}) => {
      */
     const addHighlight = useCallback((side, x, y, highlightRects, selectedText, pageNum) => {
         const id = `${side}-highlight-${Date.now()}`;
-        setComments(prev => ({
-            ...prev,
-            [id]: {
-                id,
-                side,
-                x,
-                y,
-                page: pageNum,
-                text: '', // Empty text for pure highlight
-                highlightRects,
-                author: authorName,
-                timestamp: new Date().toISOString()
-            }
-        }));
+        const highlight = {
+            id,
+            side,
+            x,
+            y,
+            page: pageNum,
+            text: '', // Empty text for pure highlight
+            highlightRects,
+            author: authorName,
+            timestamp: new Date().toISOString()
+        };
+        setComments(prev => ({ ...prev, [id]: highlight }));
+        store?.add(highlight);
         if (side === 'left') setLeftDirty(true);
         else setRightDirty(true);
-    }, [authorName]);
+    }, [authorName, store]);
 
     /**
      * Save the active comment with text
@@ -85,16 +86,15 @@ const useAnnotations = ({ authorName = 'Author' } = {}) => {
         const text = side === 'left' ? leftCommentText : rightCommentText;
 
         if (active && text.trim()) {
-            setComments(prev => ({
-                ...prev,
-                [active.id]: {
-                    ...active,
-                    text: text,
-                    author: currentAuthorName || authorName,
-                    page: active.page,
-                    timestamp: new Date().toISOString()
-                }
-            }));
+            const saved = {
+                ...active,
+                text: text,
+                author: currentAuthorName || authorName,
+                page: active.page,
+                timestamp: new Date().toISOString()
+            };
+            setComments(prev => ({ ...prev, [active.id]: saved }));
+            store?.save(saved);
 
             if (side === 'left') {
                 setLeftActiveComment(null);
@@ -106,23 +106,24 @@ const useAnnotations = ({ authorName = 'Author' } = {}) => {
                 setRightDirty(true);
             }
         }
-    }, [leftActiveComment, rightActiveComment, leftCommentText, rightCommentText, authorName]);
+    }, [leftActiveComment, rightActiveComment, leftCommentText, rightCommentText, authorName, store]);
 
     /**
      * Delete a comment by ID
      */
     const deleteComment = useCallback((id) => {
+        const comment = comments[id];
+        if (comment) {
+            if (comment.side === 'left') setLeftDirty(true);
+            else setRightDirty(true);
+            store?.remove(comment);
+        }
         setComments(prev => {
-            const comment = prev[id];
-            if (comment) {
-                if (comment.side === 'left') setLeftDirty(true);
-                else setRightDirty(true);
-            }
             const newComments = { ...prev };
             delete newComments[id];
             return newComments;
         });
-    }, []);
+    }, [comments, store]);
 
     /**
      * Cancel active comment editing
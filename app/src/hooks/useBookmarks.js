@@ -1,96 +1,162 @@
-import { useState, useEffect, useCallback, useMemo, useRef } from 'react';
+import { useState, useEffect, useCallback, useRef } from 'react';
+import {
+    getDocumentFingerprint,
+    getStoredBookmarks,
+    setStoredBookmarks,
+    readLocalBookmarks,
+    writeLocalBookmarks,
+    readLegacyBookmarks,
+    mergeBookmarks,
+    isSameDocument,
+    exportBookmarkSet,
+    importBookmarkSet,
+} from '../utils/bookmarkStore';
+import { appendJournal } from '../utils/journal';
+
+const isTauri = () => '__TAURI__' in window;
+
+const newBookmarkId = () => `bm-${Date.now()}-${Math.random().toString(36).substr(2, 5)}`;
 
 /**
  * useBookmarks - Custom hook for managing user bookmarks
- * 
- * Bookmarks are stored in localStorage keyed by a hash of the PDF name/data.
+ *
+ * Bookmarks follow the document, not its file name: files opened from disk in
+ * Tauri are stored by the backend under their /ID or content hash, everything
+ * else in localStorage under the pdf.js fingerprint. Bookmarks saved by older
+ * versions under `pdf_bookmarks_<name>` are merged in the first time the
+ * document is opened, and the old key is removed once they are stored.
+ * Bookmarks from a project file replace the stored ones.
  * Each bookmark has: id, page, label, created timestamp
- * 
- * @param {string} pdfId - Unique identifier for the PDF (typically filename or hash)
+ *
+ * @param {Object} doc - The open document
+ * @param {string} [doc.name] - File name
+ * @param {string} [doc.sourcePath] - Local path (Tauri)
+ * @param {string} [doc.fingerprint] - pdf.js fingerprint (`pdfDocument.fingerprints[0]`)
+ * @param {Array} [doc.initialBookmarks] - Bookmarks restored from a project file
+ * @param {'left'|'right'} [doc.side] - Viewer side, recorded in the autosave journal
+ * @param {string} [doc.sourceUrl] - URL the document was loaded from, recorded in the autosave journal
  * @param {function} onBookmarksChange - Optional callback when bookmarks change (for export tracking)
  * @returns {Object} Bookmark state and operations
  */
-function useBookmarks(pdfId, onBookmarksChange = null) {
-    const storageKey = useMemo(() => pdfId ? `pdf_bookmarks_${pdfId}` : null, [pdfId]);
-    const prevStorageKeyRef = useRef(storageKey);
-
-    // Helper to load bookmarks from storage
-    const loadBookmarks = useCallback((key) => {
-        if (!key) return [];
-        try {
-            const stored = localStorage.getItem(key);
-            if (stored) {
-                const parsed = JSON.parse(stored);
-                return Array.isArray(parsed) ? parsed : [];
-            }
-        } catch {
-            // Ignore errors during loading
-        }
-        return [];
-    }, []);
+function useBookmarks({
+    name = null,
+    sourcePath = null,
+    fingerprint = null,
+    initialBookmarks = null,
+    side = null,
+    sourceUrl = null,
+} = {}, onBookmarksChange = null) {
+    const useBackend = isTauri() && !!sourcePath;
+    const docKey = useBackend ? `path:${sourcePath}` : fingerprint ? `pdf_bookmarks_${fingerprint}` : null;
+    const legacyKey = name ? `pdf_bookmarks_${name.replace(/[^a-zA-Z0-9]/g, '_').toLowerCase()}` : null;
 
-    // Initialize bookmarks from localStorage
-    const [bookmarks, setBookmarks] = useState(() => loadBookmarks(storageKey));
+    // `changed` marks lists that still have to be written (edits, migrations, project restores),
+    // so loading a document never writes it back. `migratedFrom` is the legacy key to clear
+    // once the list is stored.
+    const [state, setState] = useState({ key: null, bookmarks: [], changed: false, migratedFrom: null });
+    const bookmarks = state.key === docKey ? state.bookmarks : [];
+    const saveQueueRef = useRef(Promise.resolve());
 
-    // Reload bookmarks when pdfId changes (but not on initial mount)
+    // Load bookmarks when the document changes
     useEffect(() => {
-        // Skip initial mount - already handled by useState initializer
-        if (prevStorageKeyRef.current === storageKey) return;
-        prevStorageKeyRef.current = storageKey;
+        if (!docKey) return;
+        let cancelled = false;
 
-        // Load new bookmarks for the new storageKey
-        // eslint-disable-next-line react-hooks/set-state-in-effect -- Syncing with localStorage external system
-        setBookmarks(loadBookmarks(storageKey));
-    }, [storageKey, loadBookmarks]);
+        const load = async () => {
+            if (initialBookmarks) {
+                setState({ key: docKey, bookmarks: initialBookmarks, changed: true, migratedFrom: null });
+                return;
+            }
+            let loaded = [];
+            try {
+                loaded = useBackend ? await getStoredBookmarks(sourcePath) : readLocalBookmarks(docKey);
+            } catch (err) {
+                console.warn('Failed to load bookmarks:', err);
+            }
+            if (cancelled) return;
+            // Migrate bookmarks stored by file name
+            const legacy = legacyKey && legacyKey !== docKey ? readLegacyBookmarks(legacyKey) : null;
+            const merged = legacy ? mergeBookmarks(loaded, legacy) : loaded;
+            if (legacy && merged === loaded) {
+                // Nothing new in it, so there is nothing to wait for
+                localStorage.removeItem(legacyKey);
+            }
+            const migrated = merged !== loaded;
+            setState({ key: docKey, bookmarks: merged, changed: migrated, migratedFrom: migrated ? legacyKey : null });
+        };
+        load();
 
-    // Save bookmarks to localStorage whenever they change
-    useEffect(() => {
-        if (!storageKey) return;
+        return () => { cancelled = true; };
+    }, [docKey, useBackend, sourcePath, legacyKey, initialBookmarks]);
 
-        try {
-            if (bookmarks.length > 0) {
-                localStorage.setItem(storageKey, JSON.stringify(bookmarks));
-            } else {
-                localStorage.removeItem(storageKey);
-            }
-        } catch {
-            // Ignore localStorage errors
+    // Persist edits
+    useEffect(() => {
+        if (!state.changed || state.key !== docKey) return;
+        if (isTauri()) {
+            const source = sourcePath || (/^https?:\/\//i.test(sourceUrl || '') ? sourceUrl : null);
+            appendJournal({ op: 'setBookmarks', side, source, fingerprint, data: state.bookmarks });
+        }
+        // The legacy copy goes only after the migrated list is stored
+        const { migratedFrom } = state;
+        const clearLegacy = () => {
+            if (migratedFrom) localStorage.removeItem(migratedFrom);
+        };
+        if (useBackend) {
+            const list = state.bookmarks;
+            // Chained so an older list never lands after a newer one
+            saveQueueRef.current = saveQueueRef.current
+                .then(() => setStoredBookmarks(sourcePath, list))
+                .then(clearLegacy)
+                .catch(err => console.warn('Failed to save bookmarks:', err));
+        } else {
+            writeLocalBookmarks(docKey, state.bookmarks);
+            clearLegacy();
         }
+    }, [state, docKey, useBackend, sourcePath, sourceUrl, side, fingerprint]);
 
-        // Notify parent of bookmark changes (for isDirty tracking)
+    // Notify parent of bookmark changes (for isDirty tracking)
+    useEffect(() => {
+        if (state.key !== docKey) return;
         if (onBookmarksChange) {
-            onBookmarksChange(bookmarks);
+            onBookmarksChange(state.bookmarks);
         }
-    }, [bookmarks, storageKey, onBookmarksChange]);
+    }, [state, docKey, onBookmarksChange]);
+
+    const updateBookmarks = useCallback((update) => {
+        setState(prev => {
+            const next = update(prev.bookmarks);
+            return next === prev.bookmarks ? prev : { ...prev, bookmarks: next, changed: true };
+        });
+    }, []);
 
     // Add a new bookmark for the given page
     const addBookmark = useCallback((page, label = null) => {
         const newBookmark = {
-            id: `bm-${Date.now()}-${Math.random().toString(36).substr(2, 5)}`,
+            id: newBookmarkId(),
             page,
             label: label || `Page ${page}`,
             created: new Date().toISOString(),
         };
-        setBookmarks(prev => {
+        updateBookmarks(prev => {
             // Check if page is already bookmarked
             const existing = prev.find(b => b.page === page);
             if (existing) return prev;
             return [...prev, newBookmark].sort((a, b) => a.page - b.page);
         });
         return newBookmark.id;
-    }, []);
+    }, [updateBookmarks]);
 
     // Remove a bookmark by ID
     const removeBookmark = useCallback((bookmarkId) => {
-        setBookmarks(prev => prev.filter(b => b.id !== bookmarkId));
-    }, []);
+        updateBookmarks(prev => prev.filter(b => b.id !== bookmarkId));
+    }, [updateBookmarks]);
 
     // Rename a bookmark
     const renameBookmark = useCallback((bookmarkId, newLabel) => {
-        setBookmarks(prev => prev.map(b =>
+        updateBookmarks(prev => prev.map(b =>
             b.id === bookmarkId ? { ...b, label: newLabel } : b
         ));
-    }, []);
+    }, [updateBookmarks]);
 
     // Check if a page is bookmarked
     const isPageBookmarked = useCallback((page) => {
@@ -108,6 +174,48 @@ function useBookmarks(pdfId, onBookmarksChange = null) {
         }
     }, [bookmarks, addBookmark, removeBookmark]);
 
+    // Save the bookmarks as a set file
+    const exportBookmarks = useCallback(() => {
+        return exportBookmarkSet({
+            name: name || 'document.pdf',
+            bookmarks,
+            sourcePath: useBackend ? sourcePath : null,
+            fingerprint,
+        });
+    }, [name, bookmarks, useBackend, sourcePath, fingerprint]);
+
+    // Merge a set file into the bookmarks; pages that already have one keep it.
+    // Returns the number of bookmarks added, or null if cancelled.
+    const importBookmarks = useCallback(async () => {
+        const set = await importBookmarkSet();
+        if (!set) return null;
+
+        const own = useBackend ? await getDocumentFingerprint(sourcePath) : fingerprint;
+        if (set.fingerprint && own && !isSameDocument(set.fingerprint, own)) {
+            const source = set.name ? `"${set.name}"` : 'another document';
+            if (!window.confirm(`These bookmarks were made for ${source}. Import them anyway?`)) return null;
+        }
+
+        const pages = new Set(bookmarks.map(b => b.page));
+        const added = [];
+        for (const b of set.bookmarks) {
+            const page = Number(b?.page);
+            if (!Number.isInteger(page) || page < 1 || pages.has(page)) continue;
+            pages.add(page);
+            added.push({
+                id: newBookmarkId(),
+                page,
+                label: b.label || `Page ${page}`,
+                created: b.created || new Date().toISOString(),
+            });
+        }
+        if (added.length > 0) {
+            updateBookmarks(prev => [...prev, ...added.filter(b => !prev.some(p => p.page === b.page))]
+                .sort((a, b) => a.page - b.page));
+        }
+        return added.length;
+    }, [bookmarks, useBackend, sourcePath, fingerprint, updateBookmarks]);
+
     return {
         bookmarks,
         addBookmark,
@@ -115,6 +223,8 @@ function useBookmarks(pdfId, onBookmarksChange = null) {
         renameBookmark,
         isPageBookmarked,
         toggleBookmark,
+        exportBookmarks,
+        importBookmarks,
     };
 }
 
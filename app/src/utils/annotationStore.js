@@ -0,0 +1,42 @@
+/**
+ * Annotation Store Utilities
+ *
+ * Comments saved by the Rust backend per document (Tauri only). Documents are
+ * matched by their PDF /ID or content hash, so notes come back when the same
+ * file is opened again from any path. The viewer side is not stored.
+ */
+
+const invoke = (command, args) => window.__TAURI__.core.invoke(command, args);
+
+/**
+ * Comments stored for a local PDF
+ * @param {string} path - Full path of the file
+ * @param {'left'|'right'} side - Side the document is shown on, set on every comment
+ * @returns {Promise<Object>} Comments keyed by id, like the `comments` state
+ */
+export const listStoredComments = async (path, side) => {
+    const stored = await invoke('list_comments', { path });
+    return Object.fromEntries(stored.map(comment => [comment.id, { ...comment, side }]));
+};
+
+/**
+ * Store a new comment (or highlight) for a local PDF
+ * @param {string} path - Full path of the file
+ * @param {Object} comment - Comment object with at least an `id`
+ */
+export const addStoredComment = (path, comment) => invoke('add_comment', { path, comment });
+
+/**
+ * Create or update a stored comment
+ * @param {string} path - Full path of the file
+ * @param {Object} comment - Comment object with at least an `id`
+ */
+export const saveStoredComment = (path, comment) => invoke('save_comment', { path, comment });
+
+/**
+ * Remove a stored comment
+ * @param {string} path - Full path of the file
+ * @param {string} id - Comment id
+ * @returns {Promise<boolean>} Whether the comment was stored
+ */
+export const deleteStoredComment = (path, id) => invoke('delete_comment', { path, id });
@@ -0,0 +1,17 @@
+/**
+ * Application Menu Utilities
+ *
+ * The desktop app has a native menu built by the Rust backend. Choosing an
+ * item sends its action id (`openLeft`, `nextPage`, `nextComment`, ...) to
+ * the focused window as a `menu-action` event (Tauri only). Shortcuts can be
+ * changed under `accelerators` in `settings.json` in the app data folder.
+ */
+
+import { listenToWindow } from './comparisonWindows';
+
+/**
+ * Listen to the menu actions sent to this window
+ * @param {function(string): void} handler - Called with the action id
+ * @returns {Promise<function>|null} Unlisten function, or null outside Tauri
+ */
+export const listenToMenu = (handler) => listenToWindow('menu-action', (event) => handler(event.payload));
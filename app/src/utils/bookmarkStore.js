@@ -0,0 +1,144 @@
+/**
+ * Bookmark Store Utilities
+ *
+ * Bookmarks are keyed by a document fingerprint rather than the file name.
+ * In Tauri the Rust backend stores them per document (PDF /ID or content
+ * hash) for files opened from disk; everything else falls back to
+ * localStorage under the pdf.js fingerprint.
+ */
+
+const invoke = (command, args) => window.__TAURI__.core.invoke(command, args);
+
+const isTauri = () => '__TAURI__' in window;
+
+/**
+ * Stable fingerprint of a local PDF, e.g. `id-<hex>` or `sha256-<hex>`
+ * @param {string} path - Full path of the file
+ * @returns {Promise<string>}
+ */
+export const getDocumentFingerprint = (path) => invoke('document_fingerprint', { path });
+
+/**
+ * Bookmarks stored by the backend for a local PDF
+ * @param {string} path - Full path of the file
+ * @returns {Promise<Array>} Bookmarks `{ id, page, label, created }`
+ */
+export const getStoredBookmarks = (path) => invoke('get_bookmarks', { path });
+
+/**
+ * Replace the bookmarks stored by the backend for a local PDF
+ * @param {string} path - Full path of the file
+ * @param {Array} bookmarks - Complete bookmark list
+ */
+export const setStoredBookmarks = (path, bookmarks) => invoke('set_bookmarks', { path, bookmarks });
+
+/**
+ * Bookmarks kept in localStorage under `key`
+ * @param {string} key - localStorage key
+ * @returns {Array}
+ */
+export const readLocalBookmarks = (key) => {
+    try {
+        const parsed = JSON.parse(localStorage.getItem(key));
+        return Array.isArray(parsed) ? parsed : [];
+    } catch {
+        return [];
+    }
+};
+
+/**
+ * Save bookmarks to localStorage, removing the key when the list is empty
+ * @param {string} key - localStorage key
+ * @param {Array} bookmarks
+ */
+export const writeLocalBookmarks = (key, bookmarks) => {
+    try {
+        if (bookmarks.length > 0) {
+            localStorage.setItem(key, JSON.stringify(bookmarks));
+        } else {
+            localStorage.removeItem(key);
+        }
+    } catch {
+        // Ignore localStorage errors
+    }
+};
+
+/**
+ * Bookmarks saved by older versions under the file name (`pdf_bookmarks_<name>`)
+ * @param {string} key - Legacy localStorage key
+ * @returns {Array|null} null if there is no such key
+ */
+export const readLegacyBookmarks = (key) => {
+    try {
+        if (localStorage.getItem(key) === null) return null;
+    } catch {
+        return null;
+    }
+    return readLocalBookmarks(key);
+};
+
+/**
+ * Add `extra` bookmarks for pages that have none in `bookmarks`
+ * @returns {Array} `bookmarks` itself when nothing was added
+ */
+export const mergeBookmarks = (bookmarks, extra) => {
+    const pages = new Set(bookmarks.map(b => b.page));
+    const added = [];
+    for (const b of extra) {
+        if (!Number.isInteger(b?.page) || pages.has(b.page)) continue;
+        pages.add(b.page);
+        added.push(b);
+    }
+    return added.length > 0 ? [...bookmarks, ...added].sort((a, b) => a.page - b.page) : bookmarks;
+};
+
+/**
+ * Whether two fingerprints name the same document. pdf.js fingerprints are the
+ * bare /ID hex, the backend's carry an `id-` prefix.
+ */
+export const isSameDocument = (a, b) => a.replace(/^id-/, '') === b.replace(/^id-/, '');
+
+/**
+ * Export a bookmark set as JSON (native save dialog in Tauri, download in the browser)
+ * @param {Object} options
+ * @param {string} options.name - File name of the PDF
+ * @param {Array} options.bookmarks - Bookmarks to export
+ * @param {string} [options.sourcePath] - Local path, the backend fingerprints it (Tauri)
+ * @param {string} [options.fingerprint] - Fingerprint to record when there is no path
+ * @returns {Promise<string|null>} Saved path in Tauri, null if cancelled
+ */
+export const exportBookmarkSet = async ({ name, bookmarks, sourcePath = null, fingerprint = null }) => {
+    if (isTauri()) {
+        return invoke('export_bookmarks', { path: sourcePath, name, bookmarks });
+    }
+    const json = JSON.stringify({ fingerprint, name, bookmarks }, null, 2);
+    const url = URL.createObjectURL(new Blob([json], { type: 'application/json' }));
+    const a = document.createElement('a');
+    a.href = url;
+    a.download = `${name.replace(/\.pdf$/i, '')}.bookmarks.json`;
+    a.click();
+    URL.revokeObjectURL(url);
+    return null;
+};
+
+/**
+ * Let the user pick a bookmark set file (a set object or a bare bookmark array)
+ * @returns {Promise<{fingerprint?: string, name?: string, bookmarks: Array}|null>} null if cancelled
+ */
+export const importBookmarkSet = async () => {
+    if (isTauri()) {
+        return invoke('import_bookmarks');
+    }
+    const file = await new Promise((resolve) => {
+        const input = document.createElement('input');
+        input.type = 'file';
+        input.accept = '.json,application/json';
+        input.onchange = () => resolve(input.files?.[0] || null);
+        input.click();
+    });
+    if (!file) return null;
+    const parsed = JSON.parse(await file.text());
+    if (Array.isArray(parsed)) return { bookmarks: parsed };
+    if (Array.isArray(parsed?.bookmarks)) return parsed;
+    throw new Error(`${file.name} is not a bookmarks file`);
+};
@@ -0,0 +1,43 @@
+/**
+ * Comparison Window Utilities
+ *
+ * The desktop app can show several comparisons at once, each in its own
+ * window with its own state. The Rust backend creates the windows, tracks
+ * which documents each one shows and restores them on the next start
+ * (Tauri only).
+ */
+
+const invoke = (command, args) => window.__TAURI__.core.invoke(command, args);
+
+const currentWindow = () => window.__TAURI__?.webviewWindow?.getCurrentWebviewWindow?.();
+
+/**
+ * Open another comparison window (or focus the one already showing these documents)
+ * @param {string|null} [left] - Local path for the left side
+ * @param {string|null} [right] - Local path for the right side
+ * @returns {Promise<string>} Window label
+ */
+export const openComparisonWindow = (left = null, right = null) =>
+    invoke('open_comparison_window', { left, right });
+
+/**
+ * Open windows
+ * @returns {Promise<Array>} `{ label, left, right, geometry }`
+ */
+export const listComparisonWindows = () => invoke('list_comparison_windows');
+
+/**
+ * Tell the backend which local documents this window shows (also sets the window title)
+ * @param {string|null} left
+ * @param {string|null} right
+ */
+export const setWindowDocuments = (left, right) => invoke('set_window_documents', { left, right });
+
+/** Whether this is the main window (the others are extra comparison windows) */
+export const isMainWindow = () => (currentWindow()?.label ?? 'main') === 'main';
+
+/**
+ * Listen to an event sent to this window only
+ * @returns {Promise<function>|null} Unlisten function, or null outside Tauri
+ */
+export const listenToWindow = (event, handler) => currentWindow()?.listen(event, handler) ?? null;
@@ -39,5 +39,38 @@ export {
     generateExportFilename
 } from './pdfExport';
 
+// Tauri file utilities
+export { readPDFFromPath, getPDFFileInfo, probeFile, pickPDFFile, fetchRemotePDF, unlockPDF, readPDFRange, createRangeTransport } from './tauriFiles';
+
+// Annotation store (Tauri)
+export { listStoredComments, addStoredComment, saveStoredComment, deleteStoredComment } from './annotationStore';
+
+// Bookmark store
+export {
+    getDocumentFingerprint,
+    getStoredBookmarks,
+    setStoredBookmarks,
+    exportBookmarkSet,
+    importBookmarkSet
+} from './bookmarkStore';
+
+// Project files (Tauri)
+export { pickProjectFile, readProjectFile, saveProjectFile } from './projectFile';
+
+// Autosave journal (Tauri)
+export { appendJournal, readJournal, compactJournal, summarizeJournal } from './journal';
+
+// Review queue (Tauri)
+export { getQueue, queueNext, queuePrevious, queueGoto, setPairStatus, queueProgress } from './reviewQueue';
+
+// Comparison windows (Tauri)
+export { openComparisonWindow, listComparisonWindows, setWindowDocuments, isMainWindow, listenToWindow } from './comparisonWindows';
+
+// Application menu (Tauri)
+export { listenToMenu } from './appMenu';
+
+// Recent files (Tauri)
+export { listRecent, recordRecent, pinRecent, clearRecent } from './recentFiles';
+
 // Version
 export const UTILS_VERSION = '1.0.0';
@@ -0,0 +1,77 @@
+/**
+ * Autosave Journal Utilities
+ *
+ * Every comment, draft and bookmark change is appended to a journal in the
+ * app data folder by the Rust backend (Tauri only), so a crashed webview can
+ * be recovered on the next start. Entries of a document are dropped once it
+ * has been exported.
+ */
+
+const invoke = (command, args) => window.__TAURI__.core.invoke(command, args);
+
+/**
+ * Record a change; failures are logged, never thrown
+ * @param {Object} entry
+ * @param {'addComment'|'saveComment'|'deleteComment'|'draft'|'setBookmarks'} entry.op
+ * @param {'left'|'right'} [entry.side]
+ * @param {string} [entry.source] - Local path or URL of the document
+ * @param {string} [entry.fingerprint] - pdf.js fingerprint, for documents without a local file
+ * @param {*} entry.data - Comment, `{ id }` for deletions, draft comment (or null) or bookmark list
+ */
+export const appendJournal = (entry) => {
+    if (!entry.source && !entry.fingerprint) return Promise.resolve();
+    return invoke('journal_append', { entry })
+        .catch(err => console.warn('Failed to write autosave journal:', err));
+};
+
+/**
+ * Entries not yet committed by an export, oldest first
+ * @returns {Promise<Array>} `{ timestamp, fingerprint, source, side, op, data }`
+ */
+export const readJournal = () => invoke('journal_entries');
+
+/**
+ * Drop the entries of one document, or all entries when called without one
+ * @param {Object} [doc] - `{ source, fingerprint }` as passed to `appendJournal`
+ */
+export const compactJournal = ({ source = null, fingerprint = null } = {}) =>
+    invoke('journal_compact', { source, fingerprint });
+
+/**
+ * Replay journal entries into the final state of each document
+ * @param {Array} entries - From `readJournal`
+ * @returns {Array} `{ fingerprint, source, side, changes, comments, bookmarks, draft, updated }` per
+ *   document, most recently changed first. `comments` are the surviving comments, `bookmarks`
+ *   the last list (undefined if untouched), `draft` the comment being edited (null if none).
+ */
+export const summarizeJournal = (entries) => {
+    const docs = new Map();
+    for (const entry of entries) {
+        let doc = docs.get(entry.fingerprint);
+        if (!doc) {
+            doc = { fingerprint: entry.fingerprint, comments: {}, bookmarks: undefined, draft: null, changes: 0 };
+            docs.set(entry.fingerprint, doc);
+        }
+        doc.source = entry.source || doc.source;
+        doc.side = entry.side || doc.side;
+        doc.updated = entry.timestamp;
+        doc.changes++;
+
+        switch (entry.op) {
+            case 'addComment':
+            case 'saveComment':
+                doc.comments[entry.data.id] = entry.data;
+                break;
+            case 'deleteComment':
+                delete doc.comments[entry.data.id];
+                break;
+            case 'draft':
+                doc.draft = entry.data;
+                break;
+            case 'setBookmarks':
+                doc.bookmarks = entry.data;
+                break;
+        }
+    }
+    return [...docs.values()].sort((a, b) => b.updated - a.updated);
+};
@@ -263,8 +263,10 @@ export const generateExportFilename = (originalName, prefix = '', suffix = '_com
  * This function only works when running inside the Tauri desktop app.
  * @param {Uint8Array} pdfBytes - PDF data
  * @param {string} outputPath - Full path to write to
+ * @param {Object} options
+ * @param {boolean} options.backup - Keep an overwritten file as `<name>.bak`
  */
-export const savePDFToPath = async (pdfBytes, outputPath) => {
+export const savePDFToPath = async (pdfBytes, outputPath, { backup = true } = {}) => {
     // Use global Tauri object (set by withGlobalTauri: true in tauri.conf.json)
     const tauri = window.__TAURI__;
     if (!tauri?.core?.invoke) {
@@ -272,12 +274,113 @@ export const savePDFToPath = async (pdfBytes, outputPath) => {
     }
 
     try {
-        await tauri.core.invoke('write_pdf_file', {
-            path: outputPath,
-            data: Array.from(pdfBytes)
+        // Raw body, so the bytes are not serialised as a JSON number array
+        await tauri.core.invoke('write_pdf_file', pdfBytes, {
+            headers: { path: encodeURIComponent(outputPath), backup: String(backup) }
         });
     } catch (err) {
         console.error('Failed to save PDF via Tauri:', err);
-        throw new Error('Failed to save PDF to path: ' + err.message);
+        throw toFileError(err, 'Failed to save PDF to path');
     }
 };
+
+/**
+ * Export a PDF with annotations natively (Tauri only)
+ * The source is read and the output written by the Rust backend, so the document
+ * bytes never cross IPC - only the comments and bookmarks are sent.
+ * @param {string} sourcePath - Path of the original PDF
+ * @param {Array} comments - Array of comment objects for this PDF
+ * @param {Array} bookmarks - Array of bookmark objects for this PDF
+ * @param {string} outputPath - Full path to write to
+ * @param {Object} options
+ * @param {boolean} options.incremental - Append an incremental update instead of rewriting the file,
+ *   keeping the original bytes (and any digital signatures) intact
+ * @param {boolean} options.backup - Keep an overwritten file as `<name>.bak`
+ * @param {boolean} options.keepEncryption - Write a password-protected source back encrypted
+ *   with the same passwords, instead of decrypted
+ */
+export const exportPDFToPath = async (sourcePath, comments, bookmarks, outputPath, { incremental = false, backup = true, keepEncryption = true } = {}) => {
+    const tauri = window.__TAURI__;
+    if (!tauri?.core?.invoke) {
+        throw new Error('exportPDFToPath is only available in Tauri desktop mode');
+    }
+
+    try {
+        await tauri.core.invoke('export_annotated_pdf', {
+            sourcePath,
+            comments,
+            bookmarks,
+            outputPath,
+            saveMode: incremental ? 'incremental' : 'rewrite',
+            backup,
+            keepEncryption
+        });
+    } catch (err) {
+        console.error('Failed to export PDF via Tauri:', err);
+        throw toFileError(err, 'Failed to export PDF to path');
+    }
+};
+
+/**
+ * Resolve the "save to source" output path next to the source file (Tauri only)
+ * Prefix and suffix may contain `{name}`, `{date}`, `{author}` and `{side}` placeholders.
+ * @param {string} sourcePath - Path of the original PDF
+ * @param {Object} options
+ * @param {string} options.prefix - Filename prefix pattern
+ * @param {string} options.suffix - Filename suffix pattern
+ * @param {'overwrite'|'number'|'fail'} options.collisionPolicy - What to do if the file already exists
+ * @param {string} options.author - Value for `{author}`
+ * @param {'left'|'right'} options.side - Value for `{side}`
+ * @returns {Promise<string>} Full output path
+ */
+export const resolveExportPath = async (sourcePath, { prefix = '', suffix = '_commented', collisionPolicy = 'overwrite', author, side } = {}) => {
+    try {
+        return await window.__TAURI__.core.invoke('resolve_export_path', {
+            sourcePath,
+            prefix,
+            suffix,
+            collisionPolicy,
+            author,
+            side
+        });
+    } catch (err) {
+        throw toFileError(err, 'Cannot export');
+    }
+};
+
+/**
+ * Ask the user where to save an export with the native save dialog (Tauri only)
+ * The chosen path is granted for writing by the backend.
+ * @param {string} fileName - Suggested file name
+ * @param {string} [directory] - Folder the dialog starts in
+ * @returns {Promise<string|null>} Chosen path, or null if cancelled
+ */
+export const pickSavePath = (fileName, directory) =>
+    window.__TAURI__.core.invoke('pick_save_path', { fileName, directory });
+
+// User-facing text for the structured `{ kind, path, message }` errors from the backend
+const FILE_ERROR_HINTS = {
+    permissionDenied: 'Permission denied - the folder or file is read-only.',
+    diskFull: 'The disk is full. The original file was left untouched.',
+    locked: 'The file is open in another program. Close it and try again.',
+    notFound: 'The file or folder no longer exists.',
+    alreadyExists: 'A file with that name already exists.',
+    notGranted: 'Access to this location was not granted. Open the file or pick the folder through a dialog first.',
+    passwordRequired: 'The PDF is password protected. Reopen it and enter the password.',
+    unsupportedEncryption: 'The PDF uses an encryption method that is not supported (only standard password security is).'
+};
+
+/**
+ * Turn a backend file error into an Error with `kind` and a readable message
+ * @param {Object|string} err - Rejection value from invoke
+ * @param {string} prefix - Context for the message
+ * @returns {Error}
+ */
+export const toFileError = (err, prefix) => {
+    const kind = err?.kind || 'other';
+    const detail = FILE_ERROR_HINTS[kind] || err?.message || String(err);
+    const error = new Error(`${prefix}: ${detail}`);
+    error.kind = kind;
+    error.path = err?.path;
+    return error;
+};
@@ -0,0 +1,31 @@
+/**
+ * Project File Utilities
+ *
+ * `.twice` projects capture a whole comparison session (both documents,
+ * sync/view settings, per-side page, zoom, scroll, panels, comments and
+ * bookmarks). Files are read and written by the Rust backend (Tauri only),
+ * which stores local documents relative to the project file.
+ */
+
+const invoke = (command, args) => window.__TAURI__.core.invoke(command, args);
+
+/**
+ * Pick a project file with the native open dialog
+ * @returns {Promise<string|null>} Path, or null if cancelled
+ */
+export const pickProjectFile = () => invoke('pick_project_file');
+
+/**
+ * Read a project; local sources come back as absolute paths
+ * @param {string} path - Full path of the project file
+ * @returns {Promise<Object>} `{ version, left, right, syncOffset, syncScroll, viewMode }`
+ */
+export const readProjectFile = (path) => invoke('read_project_file', { path });
+
+/**
+ * Write a project
+ * @param {string|null} path - Project file to overwrite, or null to pick one in the save dialog
+ * @param {Object} project - Project object as returned by `readProjectFile`
+ * @returns {Promise<string|null>} Saved path, or null if cancelled
+ */
+export const saveProjectFile = (path, project) => invoke('save_project_file', { path, project });
@@ -0,0 +1,38 @@
+/**
+ * Recent Files Utilities
+ *
+ * The Rust backend records the local documents each window shows and the
+ * left/right pairs, with the time, the last page and the document
+ * fingerprint, in `recent.json` in the app data folder (Tauri only).
+ * Every call returns the whole list: `{ files, pairs }`, pinned entries first,
+ * then the most recent. Files are `{ path, page, fingerprint, opened, pinned }`,
+ * pairs `{ left, right, opened, pinned }` with `left`/`right` being
+ * `{ path, page, fingerprint }`. Entries whose files are gone are dropped.
+ */
+
+const invoke = (command, args) => window.__TAURI__.core.invoke(command, args);
+
+/**
+ * The recent list; its files are granted so they can be opened again
+ * @returns {Promise<{files: Array, pairs: Array}>}
+ */
+export const listRecent = () => invoke('list_recent');
+
+/**
+ * Record what this window shows
+ * @param {{path: string, page: number}|null} left
+ * @param {{path: string, page: number}|null} right
+ */
+export const recordRecent = (left, right) => invoke('record_recent', { left, right });
+
+/**
+ * Pin or unpin an entry of the list
+ * @param {Object} entry - File or pair (a pair has `left` and `right`)
+ * @param {boolean} pinned
+ */
+export const pinRecent = (entry, pinned) => entry.left
+    ? invoke('pin_recent_pair', { left: entry.left.path, right: entry.right.path, pinned })
+    : invoke('pin_recent_file', { path: entry.path, pinned });
+
+/** Forget everything except the pinned entries */
+export const clearRecent = () => invoke('clear_recent');
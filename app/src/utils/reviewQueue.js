@@ -0,0 +1,47 @@
+/**
+ * Review Queue Utilities
+ *
+ * `twice-pdf queue` opens a list of document pairs (two folders or a CSV
+ * manifest) that the Rust backend keeps, together with each pair's status
+ * and the position, saved after every change (Tauri only).
+ * Every call returns the whole queue: `{ name, current, pairs, unmatched }`,
+ * each pair being `{ left, right, status }`.
+ */
+
+const invoke = (command, args) => window.__TAURI__.core.invoke(command, args);
+
+/**
+ * The queue the app was started with
+ * @returns {Promise<Object|null>} Queue, or null when not started with `queue`
+ */
+export const getQueue = () => invoke('queue_state');
+
+/** Move to the next pair */
+export const queueNext = () => invoke('queue_next');
+
+/** Move to the previous pair */
+export const queuePrevious = () => invoke('queue_previous');
+
+/**
+ * Move to a pair
+ * @param {number} index - 0-based pair index
+ */
+export const queueGoto = (index) => invoke('queue_goto', { index });
+
+/**
+ * Set the status of a pair
+ * @param {number} index - 0-based pair index
+ * @param {'pending'|'reviewed'|'flagged'} status
+ */
+export const setPairStatus = (index, status) => invoke('queue_set_status', { index, status });
+
+/**
+ * Count pairs by status
+ * @param {Object} queue
+ * @returns {{pending: number, reviewed: number, flagged: number}}
+ */
+export const queueProgress = (queue) => {
+    const counts = { pending: 0, reviewed: 0, flagged: 0 };
+    for (const pair of queue?.pairs || []) counts[pair.status] = (counts[pair.status] || 0) + 1;
+    return counts;
+};
@@ -0,0 +1,148 @@
+/**
+ * Tauri File Utilities
+ *
+ * Binary file transfer between the Rust backend and the webview.
+ * Data crosses IPC as raw ArrayBuffers rather than JSON number arrays.
+ */
+
+/**
+ * Read a local PDF into memory (Tauri only)
+ * The file is streamed in chunks over a channel so large documents never
+ * need a single huge IPC message.
+ * @param {string} path - Full path of the file
+ * @returns {Promise<Uint8Array>} File contents
+ */
+export const readPDFFromPath = async (path) => {
+    const tauri = window.__TAURI__;
+    if (!tauri?.core?.invoke) {
+        throw new Error('readPDFFromPath is only available in Tauri desktop mode');
+    }
+
+    const chunks = [];
+    let received = 0;
+    let expected = null;
+    let done;
+    const complete = new Promise(resolve => { done = resolve; });
+
+    const channel = new tauri.core.Channel();
+    channel.onmessage = (chunk) => {
+        const bytes = new Uint8Array(chunk);
+        chunks.push(bytes);
+        received += bytes.byteLength;
+        if (expected !== null && received >= expected) done();
+    };
+
+    expected = await tauri.core.invoke('read_pdf_file_chunked', { path, onChunk: channel });
+    // Chunks may still be in flight when the command returns
+    if (received >= expected) done();
+    await complete;
+
+    if (chunks.length === 1) return chunks[0];
+    const data = new Uint8Array(received);
+    let offset = 0;
+    for (const chunk of chunks) {
+        data.set(chunk, offset);
+        offset += chunk.byteLength;
+    }
+    return data;
+};
+
+/**
+ * Size, modification time and change token of a local file (Tauri only)
+ * @param {string} path - Full path of the file
+ * @returns {Promise<{path: string, size: number, modified: number|null, changeToken: string}>}
+ */
+export const getPDFFileInfo = (path) => window.__TAURI__.core.invoke('pdf_file_info', { path });
+
+/**
+ * Check whether a local file is a PDF by its content, not its extension (Tauri only)
+ * @param {string} path - Full path of the file
+ * @returns {Promise<{path: string, isPdf: boolean, version: string|null, encrypted: boolean,
+ *   passwordRequired: boolean, linearized: boolean, truncated: boolean, pageCount: number|null, size: number}>}
+ */
+export const probeFile = (path) => window.__TAURI__.core.invoke('probe_file', { path });
+
+/**
+ * Show the native open dialog; the backend grants access to the picked file (Tauri only)
+ * @returns {Promise<string|null>} Full path, or null when cancelled
+ */
+export const pickPDFFile = () => window.__TAURI__.core.invoke('pick_pdf_file');
+
+/**
+ * Download a remote PDF in the backend, which refuses internal addresses (Tauri only)
+ * Rejects with `{ kind, url, message, status }`; kind is one of invalidUrl, blocked,
+ * network, timeout, http, tooLarge or notPdf.
+ * @param {string} url - http(s) URL of the document
+ * @returns {Promise<ArrayBuffer>}
+ */
+export const fetchRemotePDF = async (url) => {
+    const data = await window.__TAURI__.core.invoke('fetch_remote_pdf', { url });
+    return new Uint8Array(data).buffer;
+};
+
+/**
+ * Decrypt a password-protected PDF in the backend (Tauri only)
+ * The backend keeps the decrypted document for annotated exports of the same file.
+ * Rejects with `{ kind: 'passwordRequired' }` when the password is wrong.
+ * @param {string} path - Full path of the file
+ * @param {string} password - User or owner password
+ * @returns {Promise<Uint8Array>} The document as an unencrypted PDF
+ */
+export const unlockPDF = async (path, password) => {
+    const data = await window.__TAURI__.core.invoke('unlock_pdf_file', { path, password });
+    return new Uint8Array(data);
+};
+
+/**
+ * Read a byte range of a local file (Tauri only). The backend returns at most
+ * 16 MiB per call, so larger ranges are read in several requests.
+ * @param {string} path - Full path of the file
+ * @param {number} begin - First byte
+ * @param {number} end - One past the last byte
+ * @returns {Promise<Uint8Array>} Shorter than requested only at the end of the file
+ */
+export const readPDFRange = async (path, begin, end) => {
+    const parts = [];
+    let offset = begin;
+    while (offset < end) {
+        const part = new Uint8Array(await window.__TAURI__.core.invoke('read_pdf_range', {
+            path,
+            offset,
+            length: end - offset
+        }));
+        if (part.length === 0) break;
+        parts.push(part);
+        offset += part.length;
+    }
+    if (parts.length === 1) return parts[0];
+    const data = new Uint8Array(offset - begin);
+    let position = 0;
+    for (const part of parts) {
+        data.set(part, position);
+        position += part.length;
+    }
+    return data;
+};
+
+/**
+ * PDF.js range transport backed by `read_pdf_range`, so only the parts of the
+ * file that are actually rendered get read from disk.
+ * @param {Object} pdfjsLib - The pdfjs-dist module
+ * @param {string} path - Full path of the file
+ * @param {number} size - File size from getPDFFileInfo
+ * @param {Uint8Array} initialData - Leading bytes of the file, already read
+ */
+export const createRangeTransport = (pdfjsLib, path, size, initialData) => {
+    class TauriRangeTransport extends pdfjsLib.PDFDataRangeTransport {
+        requestDataRange(begin, end) {
+            readPDFRange(path, begin, end)
+                .then(chunk => this.onDataRange(begin, chunk))
+                .catch(err => console.error(`Failed to read bytes ${begin}-${end} of ${path}:`, err));
+        }
+
+        abort() {
+            window.__TAURI__.core.invoke('close_pdf_file', { path }).catch(() => { });
+        }
+    }
+    return new TauriRangeTransport(size, initialData);
+};